@@ -0,0 +1,261 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+use chrono::NaiveDate;
+use serde_json::{json, Value};
+use warp::filters::BoxedFilter;
+use warp::http::{header, StatusCode};
+use warp::reply::Response;
+use warp::{Filter, Reply};
+
+use crate::repo::{MissReasonFilter, Submission, SubmissionFilter, SubmissionStatus, User};
+use crate::states::SubmissionType;
+use crate::{reports, webhook, AppState};
+
+const OPENAPI: &str = include_str!("openapi.json");
+const DEFAULT_LIMIT: i64 = 100;
+const MAX_LIMIT: i64 = 1000;
+const XLSX: &str = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet";
+
+type Query = HashMap<String, String>;
+
+pub struct ApiConfig {
+    token: String,
+}
+
+impl ApiConfig {
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        match std::env::var("REST_API_TOKEN") {
+            Ok(token) if token.len() >= 16 => Ok(Some(ApiConfig { token })),
+            Ok(_) => anyhow::bail!("REST_API_TOKEN must be at least 16 characters"),
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+enum Endpoint {
+    Users,
+    User(i64),
+    Submissions(Query),
+    MissReasons(Query),
+    DailyReport(Query),
+    TaskErrors(Query),
+    UserStats(i64),
+    MockProgress(i64),
+}
+
+struct Failure(StatusCode, String);
+
+impl From<anyhow::Error> for Failure {
+    fn from(e: anyhow::Error) -> Self {
+        log::error!("REST API request failed: {:?}", e);
+        Failure(StatusCode::INTERNAL_SERVER_ERROR, "internal error".to_string())
+    }
+}
+
+fn bad_request(message: String) -> Failure {
+    Failure(StatusCode::BAD_REQUEST, message)
+}
+
+fn not_found() -> Failure {
+    Failure(StatusCode::NOT_FOUND, "not found".to_string())
+}
+
+fn json_error(code: StatusCode, message: &str) -> Response {
+    warp::reply::with_status(warp::reply::json(&json!({ "error": message })), code).into_response()
+}
+
+fn date_param(q: &Query, key: &str) -> Result<Option<String>, Failure> {
+    match q.get(key) {
+        None => Ok(None),
+        Some(d) if NaiveDate::parse_from_str(d, "%Y-%m-%d").is_ok() => Ok(Some(d.clone())),
+        Some(d) => Err(bad_request(format!("{} must be YYYY-MM-DD, got {:?}", key, d))),
+    }
+}
+
+fn int_param(q: &Query, key: &str) -> Result<Option<i64>, Failure> {
+    match q.get(key) {
+        None => Ok(None),
+        Some(v) => v.parse::<i64>().map(Some).map_err(|_| bad_request(format!("{} must be an integer", key))),
+    }
+}
+
+fn file(bytes: Vec<u8>, content_type: &str, name: &str) -> Response {
+    if bytes.is_empty() {
+        return StatusCode::NO_CONTENT.into_response();
+    }
+    let reply = warp::reply::with_header(bytes, header::CONTENT_TYPE, content_type);
+    warp::reply::with_header(reply, header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", name)).into_response()
+}
+
+pub fn submission_json(submission: &Submission) -> Value {
+    json!({
+        "id": submission.id,
+        "user_id": submission.user_id,
+        "type": submission.kind.as_str(),
+        "section": submission.section,
+        "topic_id": submission.topic_id,
+        "topic_title": submission.topic_title,
+        "content_type": submission.content_type,
+        "summary": submission.summary,
+        "date": submission.date,
+        "ts": submission.ts,
+        "status": submission.status.as_str(),
+    })
+}
+
+fn user_json(user: &User) -> Value {
+    json!({ "id": user.id, "username": user.username.clone().unwrap_or_default(), "first_name": user.first_name })
+}
+
+async fn find_user(state: &AppState, uid: i64) -> Result<User, Failure> {
+    state.repos.users.get(uid).await?.ok_or_else(not_found)
+}
+
+async fn submissions(state: &AppState, q: Query) -> Result<Response, Failure> {
+    let kind = match q.get("type") {
+        None => None,
+        Some(k) => Some(SubmissionType::parse(k).ok_or_else(|| bad_request("type must be dz or conspect".to_string()))?),
+    };
+    let status = match q.get("status") {
+        None => None,
+        Some(s) => Some(SubmissionStatus::parse(s).ok_or_else(|| bad_request("status must be submitted or withdrawn".to_string()))?),
+    };
+    let filter = SubmissionFilter {
+        from: date_param(&q, "from")?,
+        to: date_param(&q, "to")?,
+        kind,
+        section: q.get("section").cloned(),
+        topic_id: q.get("topic").cloned(),
+        status,
+        user_id: int_param(&q, "user_id")?,
+        active_only: false,
+    };
+    let limit = int_param(&q, "limit")?.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let offset = int_param(&q, "offset")?.unwrap_or(0).max(0);
+
+    let items: Vec<Value> = state.repos.submissions.list(&filter, Some(limit), offset).await?
+        .iter()
+        .map(submission_json)
+        .collect();
+    Ok(warp::reply::json(&json!({ "items": items, "limit": limit, "offset": offset })).into_response())
+}
+
+async fn handle(state: &AppState, endpoint: Endpoint) -> Result<Response, Failure> {
+    match endpoint {
+        Endpoint::Users => {
+            let users: Vec<Value> = state.repos.users.list().await?.iter().map(user_json).collect();
+            Ok(warp::reply::json(&users).into_response())
+        }
+        Endpoint::User(uid) => {
+            let mut user = user_json(&find_user(state, uid).await?);
+            let filter = SubmissionFilter { user_id: Some(uid), ..Default::default() };
+            user["submissions"] = json!(state.repos.submissions.count(&filter).await?);
+            Ok(warp::reply::json(&user).into_response())
+        }
+        Endpoint::Submissions(q) => submissions(state, q).await,
+        Endpoint::MissReasons(q) => {
+            let filter = MissReasonFilter {
+                from: date_param(&q, "from")?,
+                to: date_param(&q, "to")?,
+                user_id: int_param(&q, "user_id")?,
+            };
+            let reasons: Vec<Value> = state.repos.miss_reasons.list(&filter).await?
+                .into_iter()
+                .map(|m| json!({ "user_id": m.user_id, "date": m.date, "reason": m.reason }))
+                .collect();
+            Ok(warp::reply::json(&reasons).into_response())
+        }
+        Endpoint::DailyReport(q) => {
+            let date = date_param(&q, "date")?.ok_or_else(|| bad_request("date is required".to_string()))?;
+            let started = Instant::now();
+            let excel = reports::generate_daily_report(&state.repos, &date).await;
+            state.metrics.report_generated("daily_report", started);
+            Ok(file(excel?, XLSX, &format!("report_{}.xlsx", date)))
+        }
+        Endpoint::TaskErrors(q) => {
+            let since = date_param(&q, "since")?.and_then(|d| NaiveDate::parse_from_str(&d, "%Y-%m-%d").ok());
+            let format = q.get("format").map(String::as_str).unwrap_or("xlsx");
+            if format != "xlsx" && format != "png" {
+                return Err(bad_request("format must be xlsx or png".to_string()));
+            }
+            let started = Instant::now();
+            let report = reports::generate_task_error_report(&state.pool, &state.repos, since).await;
+            state.metrics.report_generated("task_errors", started);
+            let report = report?;
+            if report.heatmap.is_empty() {
+                return Ok(StatusCode::NO_CONTENT.into_response());
+            }
+            Ok(match format {
+                "png" => file(report.heatmap, "image/png", "task_heatmap.png"),
+                _ => file(report.excel, XLSX, "task_errors.xlsx"),
+            })
+        }
+        Endpoint::UserStats(uid) => {
+            find_user(state, uid).await?;
+            let started = Instant::now();
+            let stats = reports::generate_user_stats(&state.pool, &state.repos, uid).await;
+            state.metrics.report_generated("user_stats", started);
+            Ok(file(stats?.1, "image/png", &format!("stats_{}.png", uid)))
+        }
+        Endpoint::MockProgress(uid) => {
+            find_user(state, uid).await?;
+            let started = Instant::now();
+            let progress = reports::generate_mock_progress(&state.pool, uid, state.mock_target_score).await;
+            state.metrics.report_generated("mock_progress", started);
+            Ok(file(progress?.1, "image/png", &format!("mock_progress_{}.png", uid)))
+        }
+    }
+}
+
+async fn serve(state: AppState, config: Arc<ApiConfig>, auth: Option<String>, endpoint: Endpoint) -> Result<Response, warp::Rejection> {
+    let token = auth.as_deref().and_then(|h| h.strip_prefix("Bearer "));
+    if !webhook::secret_matches(token, &config.token) {
+        let reply = json_error(StatusCode::UNAUTHORIZED, "missing or invalid bearer token");
+        return Ok(warp::reply::with_header(reply, header::WWW_AUTHENTICATE, "Bearer").into_response());
+    }
+    Ok(match handle(&state, endpoint).await {
+        Ok(response) => response,
+        Err(Failure(code, message)) => json_error(code, &message),
+    })
+}
+
+pub fn routes(state: AppState, config: ApiConfig) -> BoxedFilter<(Response,)> {
+    let config = Arc::new(config);
+    let v1 = || warp::path!("api" / "v1" / ..);
+    let query = || warp::query::<Query>();
+
+    let openapi = v1()
+        .and(warp::path!("openapi.json"))
+        .and(warp::get())
+        .map(|| warp::reply::with_header(OPENAPI, header::CONTENT_TYPE, "application/json").into_response());
+
+    let users = warp::path!("users").map(|| Endpoint::Users);
+    let user = warp::path!("users" / i64).map(Endpoint::User);
+    let submissions = warp::path!("submissions").and(query()).map(Endpoint::Submissions);
+    let miss_reasons = warp::path!("miss-reasons").and(query()).map(Endpoint::MissReasons);
+    let daily = warp::path!("reports" / "daily").and(query()).map(Endpoint::DailyReport);
+    let task_errors = warp::path!("reports" / "task-errors").and(query()).map(Endpoint::TaskErrors);
+    let stats = warp::path!("reports" / "users" / i64 / "stats").map(Endpoint::UserStats);
+    let mock = warp::path!("reports" / "users" / i64 / "mock-progress").map(Endpoint::MockProgress);
+
+    let endpoint = users
+        .or(user).unify()
+        .or(submissions).unify()
+        .or(miss_reasons).unify()
+        .or(daily).unify()
+        .or(task_errors).unify()
+        .or(stats).unify()
+        .or(mock).unify();
+
+    let api = v1()
+        .and(warp::get())
+        .and(warp::any().map(move || state.clone()))
+        .and(warp::any().map(move || config.clone()))
+        .and(warp::header::optional::<String>("authorization"))
+        .and(endpoint)
+        .and_then(serve);
+
+    openapi.or(api).unify().boxed()
+}
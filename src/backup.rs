@@ -0,0 +1,285 @@
+use std::collections::HashSet;
+use std::io::Write;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+use chrono::{Datelike, Duration, NaiveDateTime, Utc};
+
+use crate::db::{Backend, DbPool};
+
+const STAMP_FORMAT: &str = "%Y%m%d-%H%M%S";
+const DB_FILE: &str = "bot.db";
+const FILES_ARCHIVE: &str = "conspects.tar";
+
+pub struct BackupConfig {
+    pub dir: PathBuf,
+    pub keep_daily: i64,
+    pub keep_weekly: usize,
+}
+
+impl BackupConfig {
+    pub fn from_env() -> anyhow::Result<Self> {
+        Ok(BackupConfig {
+            dir: std::env::var("BACKUP_DIR").unwrap_or_else(|_| "backups".into()).into(),
+            keep_daily: std::env::var("BACKUP_KEEP_DAILY").unwrap_or_else(|_| "7".into()).parse()?,
+            keep_weekly: std::env::var("BACKUP_KEEP_WEEKLY").unwrap_or_else(|_| "4".into()).parse()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Backup {
+    pub name: String,
+    pub created: NaiveDateTime,
+    pub size: u64,
+}
+
+impl Backup {
+    fn parse(name: &str) -> Option<NaiveDateTime> {
+        NaiveDateTime::parse_from_str(name.get(..15)?, STAMP_FORMAT).ok()
+    }
+
+    pub fn size_text(&self) -> String {
+        format!("{:.1} МБ", self.size as f64 / (1024.0 * 1024.0))
+    }
+}
+
+pub struct Backups {
+    pool: DbPool,
+    conspects_dir: PathBuf,
+    config: BackupConfig,
+}
+
+impl Backups {
+    pub fn new(pool: DbPool, conspects_dir: impl Into<PathBuf>, config: BackupConfig) -> Self {
+        Backups { pool, conspects_dir: conspects_dir.into(), config }
+    }
+
+    pub fn enabled(&self) -> bool {
+        Backend::of(&self.pool) == Backend::Sqlite
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.config.dir
+    }
+
+    pub async fn create(&self, reason: &str) -> anyhow::Result<Backup> {
+        if !self.enabled() {
+            anyhow::bail!("Backups are only supported for SQLite, use pg_dump for Postgres");
+        }
+        tokio::fs::create_dir_all(&self.config.dir).await?;
+
+        let stamp = Utc::now().naive_utc().format(STAMP_FORMAT).to_string();
+        let mut name = format!("{}-{}", stamp, reason);
+        let mut n = 1;
+        while tokio::fs::try_exists(self.config.dir.join(&name)).await? {
+            n += 1;
+            name = format!("{}-{}-{}", stamp, reason, n);
+        }
+
+        let partial = self.config.dir.join(format!(".{}.partial", name));
+        tokio::fs::create_dir_all(&partial).await?;
+        let result = self.write_snapshot(&partial).await;
+        if let Err(e) = result {
+            let _ = tokio::fs::remove_dir_all(&partial).await;
+            return Err(e);
+        }
+        tokio::fs::rename(&partial, self.config.dir.join(&name)).await?;
+
+        log::info!("Backup {} created", name);
+        self.load(&name).await
+    }
+
+    async fn write_snapshot(&self, dest: &Path) -> anyhow::Result<()> {
+        sqlx::query("VACUUM INTO $1")
+            .bind(dest.join(DB_FILE).to_string_lossy().to_string())
+            .execute(&self.pool)
+            .await?;
+
+        let src = self.conspects_dir.clone();
+        let archive = dest.join(FILES_ARCHIVE);
+        tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+            let mut builder = tar::Builder::new(std::fs::File::create(archive)?);
+            if src.exists() {
+                builder.append_dir_all(".", &src)?;
+            }
+            builder.into_inner()?.sync_all()?;
+            Ok(())
+        }).await?
+    }
+
+    pub async fn snapshot_before(&self, action: &str) -> anyhow::Result<Option<Backup>> {
+        if !self.enabled() {
+            return Ok(None);
+        }
+        self.create(action).await.map(Some)
+    }
+
+    async fn load(&self, name: &str) -> anyhow::Result<Backup> {
+        let created = Backup::parse(name).ok_or_else(|| anyhow::anyhow!("Malformed backup name {}", name))?;
+        let mut size = 0;
+        for file in [DB_FILE, FILES_ARCHIVE] {
+            if let Ok(meta) = tokio::fs::metadata(self.config.dir.join(name).join(file)).await {
+                size += meta.len();
+            }
+        }
+        Ok(Backup { name: name.to_string(), created, size })
+    }
+
+    pub async fn list(&self) -> anyhow::Result<Vec<Backup>> {
+        let mut backups = Vec::new();
+        let mut entries = match tokio::fs::read_dir(&self.config.dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(backups),
+            Err(e) => return Err(e.into()),
+        };
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if entry.file_type().await?.is_dir() && Backup::parse(&name).is_some() {
+                backups.push(self.load(&name).await?);
+            }
+        }
+        backups.sort_by(|a, b| b.created.cmp(&a.created).then_with(|| b.name.cmp(&a.name)));
+        Ok(backups)
+    }
+
+    pub async fn prune(&self) -> anyhow::Result<usize> {
+        let backups = self.list().await?;
+        let stale = expired(&backups, Utc::now().naive_utc(), self.config.keep_daily, self.config.keep_weekly);
+        for name in &stale {
+            tokio::fs::remove_dir_all(self.config.dir.join(name)).await?;
+            log::info!("Backup {} removed by retention policy", name);
+        }
+        Ok(stale.len())
+    }
+}
+
+fn expired(backups: &[Backup], now: NaiveDateTime, keep_daily: i64, keep_weekly: usize) -> Vec<String> {
+    let daily_cutoff = now - Duration::days(keep_daily);
+    let mut weeks = HashSet::new();
+    let mut stale = Vec::new();
+    for backup in backups {
+        if backup.created >= daily_cutoff {
+            continue;
+        }
+        let week = backup.created.date().iso_week();
+        if weeks.len() < keep_weekly && weeks.insert((week.year(), week.week())) {
+            continue;
+        }
+        stale.push(backup.name.clone());
+    }
+    stale
+}
+
+pub fn sqlite_path(database_url: &str) -> Option<PathBuf> {
+    let rest = database_url.strip_prefix("sqlite:")?;
+    let rest = rest.strip_prefix("//").unwrap_or(rest);
+    let path = rest.split('?').next().unwrap_or_default();
+    if path.is_empty() || path == ":memory:" {
+        return None;
+    }
+    Some(PathBuf::from(path))
+}
+
+pub struct DatabaseLock {
+    _file: std::fs::File,
+}
+
+pub fn lock_database(database_path: &Path) -> anyhow::Result<DatabaseLock> {
+    let mut path = database_path.as_os_str().to_owned();
+    path.push(".lock");
+    let mut file = std::fs::OpenOptions::new().create(true).truncate(false).write(true).open(PathBuf::from(&path))?;
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+        let err = std::io::Error::last_os_error();
+        if err.kind() == std::io::ErrorKind::WouldBlock {
+            anyhow::bail!("{} is in use by a running bot, stop it first", database_path.display());
+        }
+        return Err(err.into());
+    }
+    file.set_len(0)?;
+    writeln!(file, "{}", std::process::id())?;
+    Ok(DatabaseLock { _file: file })
+}
+
+pub async fn restore(backup_dir: &Path, database_path: &Path, conspects_dir: &Path) -> anyhow::Result<()> {
+    let db_file = backup_dir.join(DB_FILE);
+    let archive = backup_dir.join(FILES_ARCHIVE);
+    if !db_file.exists() || !archive.exists() {
+        anyhow::bail!("{} is not a complete backup", backup_dir.display());
+    }
+
+    let restoring = conspects_dir.with_extension("restoring");
+    let _ = tokio::fs::remove_dir_all(&restoring).await;
+    let target = restoring.clone();
+    tokio::task::spawn_blocking(move || -> anyhow::Result<()> {
+        std::fs::create_dir_all(&target)?;
+        tar::Archive::new(std::fs::File::open(archive)?).unpack(&target)?;
+        Ok(())
+    }).await??;
+
+    let incoming = database_path.with_extension("restoring");
+    tokio::fs::copy(&db_file, &incoming).await?;
+    for suffix in ["-wal", "-shm"] {
+        let mut sidecar = database_path.as_os_str().to_owned();
+        sidecar.push(suffix);
+        let _ = tokio::fs::remove_file(PathBuf::from(sidecar)).await;
+    }
+    tokio::fs::rename(&incoming, database_path).await?;
+
+    if conspects_dir.exists() {
+        tokio::fs::remove_dir_all(conspects_dir).await?;
+    }
+    tokio::fs::rename(&restoring, conspects_dir).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backup(created: &str) -> Backup {
+        let created = NaiveDateTime::parse_from_str(created, "%Y-%m-%d %H:%M").unwrap();
+        Backup { name: created.format(STAMP_FORMAT).to_string(), created, size: 0 }
+    }
+
+    #[test]
+    fn retention_keeps_recent_days_and_one_backup_per_older_week() {
+        let now = NaiveDateTime::parse_from_str("2026-10-18 04:00", "%Y-%m-%d %H:%M").unwrap();
+        let backups = vec![
+            backup("2026-10-18 03:00"),
+            backup("2026-10-17 12:30"),
+            backup("2026-10-17 03:00"),
+            backup("2026-10-14 03:00"),
+            backup("2026-10-13 03:00"),
+            backup("2026-10-12 03:00"),
+            backup("2026-10-08 03:00"),
+            backup("2026-10-01 03:00"),
+            backup("2026-09-24 03:00"),
+        ];
+        let stale = expired(&backups, now, 5, 2);
+        assert_eq!(stale, vec![
+            "20261012-030000".to_string(),
+            "20261001-030000".to_string(),
+            "20260924-030000".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn database_lock_is_exclusive_until_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("bot.db");
+        let lock = lock_database(&db).unwrap();
+        assert!(lock_database(&db).is_err());
+        drop(lock);
+        assert!(lock_database(&db).is_ok());
+    }
+
+    #[test]
+    fn sqlite_path_accepts_common_url_forms() {
+        assert_eq!(sqlite_path("sqlite:bot.db"), Some(PathBuf::from("bot.db")));
+        assert_eq!(sqlite_path("sqlite://data/bot.db?mode=rwc"), Some(PathBuf::from("data/bot.db")));
+        assert_eq!(sqlite_path("sqlite:///var/lib/bot.db"), Some(PathBuf::from("/var/lib/bot.db")));
+        assert_eq!(sqlite_path("sqlite::memory:"), None);
+        assert_eq!(sqlite_path("postgres://localhost/bot"), None);
+    }
+}
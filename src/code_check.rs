@@ -0,0 +1,566 @@
+use std::ffi::CString;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::process::ExitStatusExt;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use log::warn;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::process::Command;
+use tokio::sync::Semaphore;
+use tokio::task::JoinHandle;
+
+use crate::keyboards;
+
+const MAX_OUTPUT_BYTES: usize = 64 * 1024;
+const MAX_FILE_BYTES: u64 = 1024 * 1024;
+const MAX_PARALLEL_RUNS: usize = 2;
+const REPORT_SNIPPET_CHARS: usize = 200;
+const MAX_PROCESSES: u64 = 16;
+const JAIL_TMPFS: &std::ffi::CStr = c"size=16m,mode=755";
+const HOST_DIRS: [&str; 6] = ["/usr", "/bin", "/lib", "/lib64", "/lib32", "/sbin"];
+const NOBODY: libc::uid_t = 65534;
+const PIPE_DRAIN_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub struct Checker {
+    python: String,
+    time_secs: u64,
+    memory_mb: u64,
+    slots: Semaphore,
+    available: AtomicBool,
+}
+
+#[derive(PartialEq, Debug)]
+pub enum Verdict {
+    Passed,
+    WrongAnswer,
+    RuntimeError,
+    TimeLimit,
+    OutputLimit,
+}
+
+impl Verdict {
+    fn label(&self) -> &'static str {
+        match self {
+            Verdict::Passed => "✅",
+            Verdict::WrongAnswer => "❌ неверный ответ",
+            Verdict::RuntimeError => "💥 ошибка выполнения",
+            Verdict::TimeLimit => "⏱ превышено время",
+            Verdict::OutputLimit => "📜 слишком большой вывод",
+        }
+    }
+}
+
+pub struct TestOutcome {
+    pub verdict: Verdict,
+    pub expected: String,
+    pub actual: String,
+    pub stderr: String,
+}
+
+fn snippet(s: &str) -> String {
+    let s = s.trim_end();
+    if s.chars().count() > REPORT_SNIPPET_CHARS {
+        format!("{}…", s.chars().take(REPORT_SNIPPET_CHARS).collect::<String>())
+    } else {
+        s.to_string()
+    }
+}
+
+pub fn format_report(outcomes: &[TestOutcome], with_output: bool) -> String {
+    let passed = outcomes.iter().filter(|o| o.verdict == Verdict::Passed).count();
+    let mut text = format!("🧪 Проверка кода: пройдено {} из {} тестов", passed, outcomes.len());
+
+    for (i, o) in outcomes.iter().enumerate() {
+        text.push_str(&format!("\nТест {}: {}", i + 1, o.verdict.label()));
+        match o.verdict {
+            Verdict::WrongAnswer if with_output => {
+                text.push_str(&format!("\n  ожидалось: {}\n  получено: {}", snippet(&o.expected), snippet(&o.actual)));
+            }
+            Verdict::RuntimeError if with_output => {
+                let last_line = o.stderr.lines().rev().find(|l| !l.trim().is_empty()).unwrap_or("");
+                text.push_str(&format!("\n  {}", snippet(last_line)));
+            }
+            Verdict::RuntimeError => {
+                if let Some(name) = exception_name(&o.stderr) {
+                    text.push_str(&format!("\n  {}", name));
+                }
+            }
+            _ => {}
+        }
+    }
+    text
+}
+
+fn exception_name(stderr: &str) -> Option<&str> {
+    let last_line = stderr.lines().rev().find(|l| !l.trim().is_empty())?;
+    let name = last_line.split(':').next()?.trim();
+    let valid = name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+        && (name.ends_with("Error") || name.ends_with("Exception") || name.ends_with("Interrupt"));
+    valid.then_some(name)
+}
+
+pub struct TestSpec {
+    pub section: String,
+    pub topic_id: String,
+    pub tests: Vec<(String, String)>,
+}
+
+fn normalize_output(s: &str) -> String {
+    s.replace("\r\n", "\n")
+        .lines()
+        .map(|l| l.trim_end())
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim_end()
+        .to_string()
+}
+
+pub fn parse_test_spec(spec: &str) -> Result<TestSpec, String> {
+    let mut lines = spec.lines().skip_while(|l| l.trim().is_empty());
+    let topic_id = lines.next().unwrap_or("").trim().to_string();
+    let section = keyboards::SECTIONS.iter()
+        .find(|s| keyboards::get_topic_title(s, &topic_id).is_some())
+        .ok_or_else(|| format!("неизвестная тема «{}»", topic_id))?;
+
+    let mut tests = Vec::new();
+    let mut input: Option<Vec<&str>> = None;
+    let mut output: Option<Vec<&str>> = None;
+
+    for line in lines {
+        let marker = line.trim().strip_prefix("---").map(|m| m.trim().to_lowercase());
+        match marker.as_deref() {
+            Some("ввод") | Some("input") => {
+                match (input.take(), output.take()) {
+                    (Some(i), Some(o)) => tests.push((i.join("\n"), o.join("\n"))),
+                    (Some(_), None) => return Err(format!("у теста {} нет блока «--- вывод»", tests.len() + 1)),
+                    _ => {}
+                }
+                input = Some(Vec::new());
+            }
+            Some("вывод") | Some("output") => {
+                if input.is_none() || output.is_some() {
+                    return Err(format!("перед выводом теста {} нужен блок «--- ввод»", tests.len() + 1));
+                }
+                output = Some(Vec::new());
+            }
+            _ => match (&mut input, &mut output) {
+                (_, Some(o)) => o.push(line),
+                (Some(i), None) => i.push(line),
+                (None, None) if line.trim().is_empty() => {}
+                (None, None) => return Err("текст до первого блока «--- ввод»".to_string()),
+            },
+        }
+    }
+
+    match (input, output) {
+        (Some(i), Some(o)) => tests.push((i.join("\n"), o.join("\n"))),
+        (Some(_), None) => return Err(format!("у теста {} нет блока «--- вывод»", tests.len() + 1)),
+        _ => {}
+    }
+
+    if tests.is_empty() {
+        return Err("нет ни одного теста".to_string());
+    }
+    Ok(TestSpec { section: section.to_string(), topic_id, tests })
+}
+
+struct Jail {
+    root: CString,
+    tmp: CString,
+    work: (CString, CString),
+    binds: Vec<(CString, CString)>,
+    links: Vec<(CString, CString)>,
+    uid_map: CString,
+    gid_map: CString,
+}
+
+fn cstring(path: &Path) -> io::Result<CString> {
+    CString::new(path.as_os_str().as_bytes()).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
+
+impl Jail {
+    fn new(dir: &Path, python: &str) -> io::Result<Self> {
+        let root = dir.join("root");
+        let mut host: Vec<PathBuf> = HOST_DIRS.iter().map(PathBuf::from).collect();
+        let python = Path::new(python);
+        if python.is_absolute() && !host.iter().any(|h| python.starts_with(h)) {
+            if let Some(prefix) = python.parent().and_then(Path::parent) {
+                host.push(prefix.to_path_buf());
+            }
+        }
+
+        let mut binds = Vec::new();
+        let mut links = Vec::new();
+        for dir in host {
+            let inside = root.join(dir.strip_prefix("/").unwrap_or(&dir));
+            match std::fs::symlink_metadata(&dir) {
+                Ok(meta) if meta.file_type().is_symlink() => {
+                    links.push((cstring(&std::fs::read_link(&dir)?)?, cstring(&inside)?));
+                }
+                Ok(meta) if meta.is_dir() => binds.push((cstring(&dir)?, cstring(&inside)?)),
+                _ => {}
+            }
+        }
+
+        let (uid, gid) = match unsafe { (libc::getuid(), libc::getgid()) } {
+            (0, _) => (NOBODY, NOBODY),
+            ids => ids,
+        };
+        Ok(Jail {
+            tmp: cstring(&root.join("tmp"))?,
+            work: (cstring(&dir.join("work"))?, cstring(&root.join("work"))?),
+            root: cstring(&root)?,
+            binds,
+            links,
+            uid_map: CString::new(format!("65534 {} 1", uid))?,
+            gid_map: CString::new(format!("65534 {} 1", gid))?,
+        })
+    }
+}
+
+fn check(ret: libc::c_int) -> io::Result<()> {
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn write_proc(path: &[u8], data: &[u8]) -> io::Result<()> {
+    unsafe {
+        let fd = libc::open(path.as_ptr() as *const libc::c_char, libc::O_WRONLY | libc::O_CLOEXEC);
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let written = libc::write(fd, data.as_ptr() as *const libc::c_void, data.len());
+        libc::close(fd);
+        if written != data.len() as isize {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+fn bind_read_only(src: &CString, dst: &CString) -> io::Result<()> {
+    unsafe {
+        check(libc::mkdir(dst.as_ptr(), 0o755))?;
+        check(libc::mount(src.as_ptr(), dst.as_ptr(), std::ptr::null(), libc::MS_BIND | libc::MS_REC, std::ptr::null()))?;
+
+        let mut st: libc::statvfs = std::mem::zeroed();
+        check(libc::statvfs(src.as_ptr(), &mut st))?;
+        let mut flags = libc::MS_REMOUNT | libc::MS_BIND | libc::MS_RDONLY | libc::MS_NOSUID | libc::MS_NODEV;
+        if st.f_flag & libc::ST_NOEXEC != 0 {
+            flags |= libc::MS_NOEXEC;
+        }
+        check(libc::mount(std::ptr::null(), dst.as_ptr(), std::ptr::null(), flags, std::ptr::null()))?;
+    }
+    Ok(())
+}
+
+// Runs between fork and exec, so only raw syscalls on memory prepared by the parent.
+fn enter_jail(jail: &Jail, cpu_secs: u64, memory_bytes: u64) -> io::Result<()> {
+    let limit = |resource, value: u64, hard: u64| {
+        let rl = libc::rlimit { rlim_cur: value as libc::rlim_t, rlim_max: hard as libc::rlim_t };
+        check(unsafe { libc::setrlimit(resource, &rl) })
+    };
+
+    unsafe {
+        check(libc::setpgid(0, 0))?;
+        // RLIMIT_NPROC is not enforced for root, so a bot running as root hands the jail to nobody.
+        if libc::getuid() == 0 {
+            check(libc::setgroups(0, std::ptr::null()))?;
+            check(libc::setgid(NOBODY))?;
+            check(libc::setuid(NOBODY))?;
+            check(libc::prctl(libc::PR_SET_DUMPABLE, 1, 0, 0, 0))?;
+        }
+        check(libc::unshare(
+            libc::CLONE_NEWUSER | libc::CLONE_NEWNS | libc::CLONE_NEWPID | libc::CLONE_NEWNET | libc::CLONE_NEWIPC | libc::CLONE_NEWUTS
+        ))?;
+    }
+    write_proc(b"/proc/self/setgroups\0", b"deny")?;
+    write_proc(b"/proc/self/uid_map\0", jail.uid_map.as_bytes())?;
+    write_proc(b"/proc/self/gid_map\0", jail.gid_map.as_bytes())?;
+    limit(libc::RLIMIT_CORE, 0, 0)?;
+
+    // The solution becomes init of the new pid namespace: once it exits the kernel
+    // kills everything it spawned, even processes that left the process group.
+    match unsafe { libc::fork() } {
+        -1 => return Err(io::Error::last_os_error()),
+        0 => {}
+        pid => supervise(pid),
+    }
+
+    unsafe {
+        let none = std::ptr::null::<libc::c_char>();
+        check(libc::mount(none, c"/".as_ptr(), none, libc::MS_REC | libc::MS_PRIVATE, std::ptr::null()))?;
+        let tmpfs = c"tmpfs";
+        check(libc::mount(
+            tmpfs.as_ptr(), jail.root.as_ptr(), tmpfs.as_ptr(),
+            libc::MS_NOSUID | libc::MS_NODEV, JAIL_TMPFS.as_ptr() as *const libc::c_void
+        ))?;
+        for (target, link) in &jail.links {
+            check(libc::symlink(target.as_ptr(), link.as_ptr()))?;
+        }
+        for (src, dst) in &jail.binds {
+            bind_read_only(src, dst)?;
+        }
+        bind_read_only(&jail.work.0, &jail.work.1)?;
+        check(libc::mkdir(jail.tmp.as_ptr(), 0o1777))?;
+
+        check(libc::chdir(jail.root.as_ptr()))?;
+        let dot = c".";
+        if libc::syscall(libc::SYS_pivot_root, dot.as_ptr(), dot.as_ptr()) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        check(libc::umount2(dot.as_ptr(), libc::MNT_DETACH))?;
+        check(libc::chdir(c"/work".as_ptr()))?;
+        check(libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0))?;
+    }
+
+    limit(libc::RLIMIT_CPU, cpu_secs, cpu_secs + 1)?;
+    limit(libc::RLIMIT_AS, memory_bytes, memory_bytes)?;
+    limit(libc::RLIMIT_FSIZE, MAX_FILE_BYTES, MAX_FILE_BYTES)?;
+    limit(libc::RLIMIT_NPROC, MAX_PROCESSES, MAX_PROCESSES)?;
+    Ok(())
+}
+
+fn supervise(pid: libc::pid_t) -> ! {
+    unsafe {
+        // Drop our copies of the pipes, including std's exec-error pipe, so the
+        // parent sees EOF as soon as the solution is gone.
+        libc::syscall(libc::SYS_close_range, 0u32, u32::MAX, 0u32);
+        let mut status = 0;
+        while libc::waitpid(pid, &mut status, 0) < 0 {
+            if *libc::__errno_location() != libc::EINTR {
+                libc::_exit(127);
+            }
+        }
+        if libc::WIFSIGNALED(status) {
+            let sig = libc::WTERMSIG(status);
+            libc::signal(sig, libc::SIG_DFL);
+            libc::kill(libc::getpid(), sig);
+            libc::_exit(128 + sig);
+        }
+        libc::_exit(libc::WEXITSTATUS(status))
+    }
+}
+
+fn kill_group(pgid: Option<u32>) {
+    if let Some(pgid) = pgid {
+        unsafe { libc::kill(-(pgid as libc::pid_t), libc::SIGKILL); }
+    }
+}
+
+async fn read_capped<R: AsyncRead + Unpin>(mut reader: R, pgid: Option<u32>) -> (Vec<u8>, bool) {
+    let mut out = Vec::new();
+    let mut chunk = [0u8; 8192];
+    loop {
+        match reader.read(&mut chunk).await {
+            Ok(0) | Err(_) => return (out, false),
+            Ok(n) => {
+                out.extend_from_slice(&chunk[..n]);
+                if out.len() > MAX_OUTPUT_BYTES {
+                    out.truncate(MAX_OUTPUT_BYTES);
+                    kill_group(pgid);
+                    return (out, true);
+                }
+            }
+        }
+    }
+}
+
+async fn drain(task: JoinHandle<(Vec<u8>, bool)>) -> (Vec<u8>, bool) {
+    let abort = task.abort_handle();
+    match tokio::time::timeout(PIPE_DRAIN_TIMEOUT, task).await {
+        Ok(Ok(result)) => result,
+        _ => {
+            abort.abort();
+            (Vec::new(), false)
+        }
+    }
+}
+
+impl Checker {
+    pub fn new(python: String, time_secs: u64, memory_mb: u64) -> Self {
+        Checker { python, time_secs, memory_mb, slots: Semaphore::new(MAX_PARALLEL_RUNS), available: AtomicBool::new(true) }
+    }
+
+    pub fn available(&self) -> bool {
+        self.available.load(Ordering::Relaxed)
+    }
+
+    pub async fn probe(&self) -> bool {
+        let result = self.run(b"print(input())", &[("ok".to_string(), "ok".to_string())]).await;
+        let error = match result.as_deref() {
+            Ok([outcome]) if outcome.verdict == Verdict::Passed => None,
+            Ok([outcome]) => Some(format!("{} {}", outcome.verdict.label(), snippet(&outcome.stderr))),
+            Ok(_) => Some("unexpected outcome count".to_string()),
+            Err(e) => Some(format!("{:#}", e)),
+        };
+        if let Some(error) = &error {
+            warn!(
+                "Code checks disabled: sandbox probe with {} failed ({}). \
+                 The checker needs unprivileged user namespaces (kernel.unprivileged_userns_clone=1, \
+                 user.max_user_namespaces > 0, no seccomp/AppArmor block on unshare in the container).",
+                self.python, error
+            );
+        }
+        self.available.store(error.is_none(), Ordering::Relaxed);
+        error.is_none()
+    }
+
+    async fn run_one(&self, dir: &Path, input: &str, expected: &str) -> anyhow::Result<TestOutcome> {
+        let (cpu_secs, memory_bytes) = (self.time_secs, self.memory_mb * 1024 * 1024);
+        let jail = Jail::new(dir, &self.python)?;
+
+        let mut cmd = Command::new(&self.python);
+        cmd.arg("-I").arg("solution.py")
+            .current_dir(dir)
+            .env_clear()
+            .env("PATH", "/usr/local/bin:/usr/bin:/bin")
+            .env("LANG", "C.UTF-8")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        unsafe {
+            cmd.pre_exec(move || enter_jail(&jail, cpu_secs, memory_bytes));
+        }
+
+        let mut child = cmd.spawn()?;
+        let pgid = child.id();
+
+        let mut stdin = child.stdin.take().ok_or_else(|| anyhow::anyhow!("No stdin"))?;
+        let input = format!("{}\n", input);
+        tokio::spawn(async move {
+            let _ = stdin.write_all(input.as_bytes()).await;
+        });
+        let stdout = child.stdout.take().ok_or_else(|| anyhow::anyhow!("No stdout"))?;
+        let stderr = child.stderr.take().ok_or_else(|| anyhow::anyhow!("No stderr"))?;
+        let out_task = tokio::spawn(read_capped(stdout, pgid));
+        let err_task = tokio::spawn(read_capped(stderr, pgid));
+
+        let wall = Duration::from_secs(self.time_secs * 2 + 1);
+        let (status, timed_out) = match tokio::time::timeout(wall, child.wait()).await {
+            Ok(status) => (status?, false),
+            Err(_) => {
+                kill_group(pgid);
+                (child.wait().await?, true)
+            }
+        };
+        kill_group(pgid);
+
+        let (out, out_overflow) = drain(out_task).await;
+        let (err, _) = drain(err_task).await;
+        let actual = String::from_utf8_lossy(&out).to_string();
+        let stderr = String::from_utf8_lossy(&err).to_string();
+
+        let verdict = if out_overflow {
+            Verdict::OutputLimit
+        } else if timed_out || matches!(status.signal(), Some(libc::SIGXCPU) | Some(libc::SIGKILL)) {
+            Verdict::TimeLimit
+        } else if !status.success() {
+            Verdict::RuntimeError
+        } else if normalize_output(&actual) == normalize_output(expected) {
+            Verdict::Passed
+        } else {
+            Verdict::WrongAnswer
+        };
+
+        Ok(TestOutcome { verdict, expected: expected.to_string(), actual, stderr })
+    }
+
+    pub async fn run(&self, code: &[u8], tests: &[(String, String)]) -> anyhow::Result<Vec<TestOutcome>> {
+        let _slot = self.slots.acquire().await?;
+
+        let dir = std::env::temp_dir().join(format!("code_check_{}_{:016x}", std::process::id(), rand::random::<u64>()));
+        tokio::fs::create_dir_all(dir.join("work")).await?;
+        tokio::fs::create_dir_all(dir.join("root")).await?;
+        tokio::fs::write(dir.join("work").join("solution.py"), code).await?;
+
+        let mut outcomes = Vec::new();
+        let mut result = Ok(());
+        for (input, expected) in tests {
+            match self.run_one(&dir, input, expected).await {
+                Ok(outcome) => outcomes.push(outcome),
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+        result?;
+        Ok(outcomes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outcome(verdict: Verdict, actual: &str, stderr: &str) -> TestOutcome {
+        TestOutcome { verdict, expected: "25".to_string(), actual: actual.to_string(), stderr: stderr.to_string() }
+    }
+
+    #[test]
+    fn test_spec_splits_input_and_output_blocks() {
+        let spec = parse_test_spec("\nop3\n--- ввод\n5\n--- вывод\n25\n\n--- INPUT\n1\n2\n--- Output\n3\n").unwrap();
+        assert_eq!((spec.section.as_str(), spec.topic_id.as_str()), ("Основы Питона", "op3"));
+        assert_eq!(spec.tests, vec![
+            ("5".to_string(), "25\n".to_string()),
+            ("1\n2".to_string(), "3".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_spec_rejects_malformed_input() {
+        let err = |spec: &str| parse_test_spec(spec).err().unwrap();
+        assert_eq!(err("nope\n--- ввод\n1\n--- вывод\n1"), "неизвестная тема «nope»");
+        assert_eq!(err("op3\n--- ввод\n1\n--- ввод\n2\n--- вывод\n2"), "у теста 1 нет блока «--- вывод»");
+        assert_eq!(err("op3\n--- ввод\n1"), "у теста 1 нет блока «--- вывод»");
+        assert_eq!(err("op3\n--- вывод\n1"), "перед выводом теста 1 нужен блок «--- ввод»");
+        assert_eq!(err("op3\nprint(1)\n--- ввод\n1\n--- вывод\n1"), "текст до первого блока «--- ввод»");
+        assert_eq!(err("op3\n\n"), "нет ни одного теста");
+    }
+
+    #[test]
+    fn output_comparison_ignores_trailing_whitespace_and_crlf() {
+        assert_eq!(normalize_output("25  \r\n\r\n"), normalize_output("25"));
+        assert_ne!(normalize_output(" 25"), normalize_output("25"));
+    }
+
+    #[test]
+    fn student_report_hides_output_but_names_the_exception() {
+        let outcomes = vec![
+            outcome(Verdict::Passed, "25", ""),
+            outcome(Verdict::WrongAnswer, "secret output", ""),
+            outcome(Verdict::RuntimeError, "", "Traceback (most recent call last):\n  File \"solution.py\"\nZeroDivisionError: division by zero\n"),
+            outcome(Verdict::RuntimeError, "", "/etc/passwd: something odd\n"),
+            outcome(Verdict::TimeLimit, "", ""),
+        ];
+        let report = format_report(&outcomes, false);
+        assert_eq!(report, "🧪 Проверка кода: пройдено 1 из 5 тестов\n\
+                            Тест 1: ✅\n\
+                            Тест 2: ❌ неверный ответ\n\
+                            Тест 3: 💥 ошибка выполнения\n  ZeroDivisionError\n\
+                            Тест 4: 💥 ошибка выполнения\n\
+                            Тест 5: ⏱ превышено время");
+    }
+
+    #[test]
+    fn admin_report_shows_output_snippets() {
+        let long = "x".repeat(REPORT_SNIPPET_CHARS + 10);
+        let outcomes = vec![
+            outcome(Verdict::WrongAnswer, &long, ""),
+            outcome(Verdict::RuntimeError, "", "Traceback\nValueError: bad\n\n"),
+        ];
+        let report = format_report(&outcomes, true);
+        assert!(report.contains(&format!("\n  ожидалось: 25\n  получено: {}…", "x".repeat(REPORT_SNIPPET_CHARS))));
+        assert!(report.ends_with("Тест 2: 💥 ошибка выполнения\n  ValueError: bad"));
+    }
+}
@@ -0,0 +1,216 @@
+use plotters::prelude::*;
+use plotters::style::FontFamily;
+use teloxide::types::{Message, MessageEntityKind};
+
+use crate::reports::encode_png;
+
+const MAX_LINES: usize = 80;
+const MAX_COLS: usize = 100;
+const FONT_SIZE: f64 = 20.0;
+const LINE_HEIGHT: u32 = 26;
+const PADDING: u32 = 20;
+const GUTTER_COLS: usize = 4;
+
+const BACKGROUND: RGBColor = RGBColor(40, 44, 52);
+const GUTTER: RGBColor = RGBColor(99, 109, 131);
+
+const KEYWORDS: [&str; 35] = [
+    "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class", "continue",
+    "def", "del", "elif", "else", "except", "finally", "for", "from", "global", "if", "import",
+    "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while",
+    "with", "yield",
+];
+
+const BUILTINS: [&str; 24] = [
+    "abs", "all", "any", "bool", "dict", "enumerate", "filter", "float", "input", "int", "len",
+    "list", "map", "max", "min", "open", "print", "range", "reversed", "set", "sorted", "str",
+    "sum", "zip",
+];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Token {
+    Plain,
+    Keyword,
+    Builtin,
+    Str,
+    Comment,
+    Number,
+}
+
+impl Token {
+    fn color(&self) -> RGBColor {
+        match self {
+            Token::Plain => RGBColor(171, 178, 191),
+            Token::Keyword => RGBColor(198, 120, 221),
+            Token::Builtin => RGBColor(97, 175, 239),
+            Token::Str => RGBColor(152, 195, 121),
+            Token::Comment => RGBColor(92, 99, 112),
+            Token::Number => RGBColor(209, 154, 102),
+        }
+    }
+}
+
+pub fn extract_code(msg: &Message) -> Option<(String, Option<String>)> {
+    let text = msg.text()?;
+    let entities = msg.parse_entities()?;
+
+    let mut language = None;
+    let blocks: Vec<&str> = entities.iter()
+        .filter_map(|e| match e.kind() {
+            MessageEntityKind::Pre { language: lang } => {
+                language = language.clone().or(lang.clone());
+                Some(e.text())
+            }
+            MessageEntityKind::Code if e.text().trim() == text.trim() => Some(e.text()),
+            _ => None,
+        })
+        .collect();
+
+    if blocks.is_empty() {
+        None
+    } else {
+        Some((blocks.join("\n\n"), language))
+    }
+}
+
+pub fn code_extension(language: Option<&str>) -> String {
+    match language.map(|l| l.trim().to_lowercase()) {
+        None => "py".to_string(),
+        Some(l) if l.is_empty() || l == "python" || l == "python3" || l == "py" => "py".to_string(),
+        Some(l) if l.chars().all(|c| c.is_ascii_alphanumeric()) && l.len() <= 10 => l,
+        _ => "txt".to_string(),
+    }
+}
+
+fn classify_word(word: &str) -> Token {
+    if KEYWORDS.contains(&word) {
+        Token::Keyword
+    } else if BUILTINS.contains(&word) {
+        Token::Builtin
+    } else {
+        Token::Plain
+    }
+}
+
+fn highlight_line(line: &str, open_triple: &mut Option<&'static str>) -> Vec<(Token, String)> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut tokens: Vec<(Token, String)> = Vec::new();
+    let mut push = |kind: Token, s: String| {
+        match tokens.last_mut() {
+            Some((last, text)) if *last == kind => text.push_str(&s),
+            _ => tokens.push((kind, s)),
+        }
+    };
+
+    let mut i = 0;
+    while i < chars.len() {
+        let rest: String = chars[i..].iter().collect();
+
+        if let Some(delim) = *open_triple {
+            match rest.find(delim) {
+                Some(end) => {
+                    let end = end + delim.len();
+                    push(Token::Str, rest[..end].to_string());
+                    i += rest[..end].chars().count();
+                    *open_triple = None;
+                }
+                None => {
+                    push(Token::Str, rest);
+                    break;
+                }
+            }
+            continue;
+        }
+
+        let c = chars[i];
+        if c == '#' {
+            push(Token::Comment, rest);
+            break;
+        } else if rest.starts_with("\"\"\"") || rest.starts_with("'''") {
+            *open_triple = Some(if c == '"' { "\"\"\"" } else { "'''" });
+            push(Token::Str, rest[..3].to_string());
+            i += 3;
+        } else if c == '"' || c == '\'' {
+            let mut j = i + 1;
+            while j < chars.len() && chars[j] != c {
+                j += if chars[j] == '\\' { 2 } else { 1 };
+            }
+            let end = (j + 1).min(chars.len());
+            push(Token::Str, chars[i..end].iter().collect());
+            i = end;
+        } else if c.is_ascii_digit() {
+            let mut j = i;
+            while j < chars.len() && (chars[j].is_alphanumeric() || chars[j] == '.' || chars[j] == '_') {
+                j += 1;
+            }
+            push(Token::Number, chars[i..j].iter().collect());
+            i = j;
+        } else if c.is_alphabetic() || c == '_' {
+            let mut j = i;
+            while j < chars.len() && (chars[j].is_alphanumeric() || chars[j] == '_') {
+                j += 1;
+            }
+            let word: String = chars[i..j].iter().collect();
+            push(classify_word(&word), word);
+            i = j;
+        } else {
+            push(Token::Plain, c.to_string());
+            i += 1;
+        }
+    }
+    tokens
+}
+
+fn truncate_line(line: &str) -> String {
+    let line = line.replace('\t', "    ");
+    if line.chars().count() > MAX_COLS {
+        format!("{}…", line.chars().take(MAX_COLS - 1).collect::<String>())
+    } else {
+        line
+    }
+}
+
+pub fn render_code_png(code: &str) -> anyhow::Result<Vec<u8>> {
+    let mut lines: Vec<String> = code.lines().map(truncate_line).collect();
+    while lines.last().map(|l| l.trim().is_empty()).unwrap_or(false) {
+        lines.pop();
+    }
+    if lines.is_empty() {
+        return Ok(vec![]);
+    }
+    if lines.len() > MAX_LINES {
+        let hidden = lines.len() - MAX_LINES + 1;
+        lines.truncate(MAX_LINES - 1);
+        lines.push(format!("# … ещё {} строк", hidden));
+    }
+
+    let font = (FontFamily::Monospace, FONT_SIZE).into_font();
+    let char_width = font.box_size("M").map(|(w, _)| w).unwrap_or(12);
+    let cols = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0) + GUTTER_COLS + 1;
+    let size = (
+        PADDING * 2 + char_width * cols as u32,
+        PADDING * 2 + LINE_HEIGHT * lines.len() as u32,
+    );
+
+    let mut buffer = vec![0; (size.0 * size.1 * 3) as usize];
+    {
+        let root = BitMapBackend::with_buffer(&mut buffer, size).into_drawing_area();
+        root.fill(&BACKGROUND)?;
+
+        let mut open_triple = None;
+        for (n, line) in lines.iter().enumerate() {
+            let y = (PADDING + LINE_HEIGHT * n as u32) as i32;
+            let gutter = format!("{:>width$}", n + 1, width = GUTTER_COLS - 1);
+            root.draw_text(&gutter, &font.color(&GUTTER), (PADDING as i32, y))?;
+
+            let mut col = GUTTER_COLS + 1;
+            for (kind, text) in highlight_line(line, &mut open_triple) {
+                let x = (PADDING + char_width * col as u32) as i32;
+                root.draw_text(&text, &font.color(&kind.color()), (x, y))?;
+                col += text.chars().count();
+            }
+        }
+        root.present()?;
+    }
+    encode_png(buffer, size)
+}
@@ -0,0 +1,161 @@
+use std::collections::HashSet;
+
+use chrono::{Duration, NaiveDate, Utc};
+use log::error;
+use rand::seq::SliceRandom;
+use teloxide::prelude::*;
+
+use crate::db::{self, DbPool};
+use crate::handlers;
+use crate::keyboards;
+use crate::reports;
+use crate::repo::SubmissionFilter;
+use crate::AppState;
+
+const ACTIVE_DAYS: i64 = 14;
+const WEAK_CANDIDATES: usize = 3;
+const LEADERBOARD_SIZE: usize = 10;
+
+pub fn choose_weak_number(stats: &[(i64, i64, i64)]) -> Option<i64> {
+    let mut scored: Vec<(f64, i64)> = stats.iter()
+        .map(|(n, earned, total)| ((*earned as f64 + 1.0) / (*total as f64 + 2.0), *n))
+        .collect();
+    scored.sort_by(|a, b| a.0.total_cmp(&b.0));
+    scored.truncate(WEAK_CANDIDATES);
+    scored.choose(&mut rand::thread_rng()).map(|(_, n)| *n)
+}
+
+pub async fn current_streak(pool: &DbPool, user_id: i64) -> anyhow::Result<(i64, i64)> {
+    let dates: Vec<NaiveDate> = db::daily_correct_dates(pool, user_id).await?
+        .iter()
+        .filter_map(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+        .collect();
+    Ok(reports::compute_streaks(&dates, Utc::now().date_naive()))
+}
+
+async fn push_to_student(bot: &Bot, state: &AppState, uid: i64, date: &str) -> anyhow::Result<()> {
+    let stats = db::task_number_stats(&state.pool, uid).await?;
+    let Some(ege_number) = choose_weak_number(&stats) else { return Ok(()) };
+    let Some((task_id, statement, file_id, file_kind, attachments)) = db::pick_task(&state.pool, ege_number, uid).await? else {
+        return Ok(());
+    };
+    if !db::create_daily_task(&state.pool, uid, date, task_id).await? {
+        return Ok(());
+    }
+
+    let chat_id = ChatId(uid);
+    if let Err(e) = handlers::send_task_files(bot, chat_id, state, file_id, &file_kind, &attachments).await {
+        error!("Daily task files for {} not sent: {:?}", uid, e);
+    }
+    bot.send_message(chat_id, format!(
+        "☀️ Задача дня: задание {}\n\n{}\n\nНажми «Ответить» и пришли ответ до конца дня.",
+        ege_number, statement
+    )).reply_markup(keyboards::daily_answer_kb()).await?;
+    Ok(())
+}
+
+async fn active_students(state: &AppState, since: &str) -> anyhow::Result<Vec<i64>> {
+    let filter = SubmissionFilter { from: Some(since.to_string()), active_only: true, ..Default::default() };
+    let mut active: HashSet<i64> = state.repos.submissions.tally(&filter).await?.into_iter().map(|t| t.user_id).collect();
+    active.extend(db::recent_task_solvers(&state.pool, since).await?);
+    Ok(state.repos.users.list().await?.into_iter()
+        .map(|u| u.id)
+        .filter(|id| *id != state.admin_id && active.contains(id))
+        .collect())
+}
+
+pub async fn push_daily_tasks(bot: &Bot, state: &AppState) -> anyhow::Result<()> {
+    let today = Utc::now().date_naive();
+    let date = today.format("%Y-%m-%d").to_string();
+    let since = (today - Duration::days(ACTIVE_DAYS)).format("%Y-%m-%d").to_string();
+
+    let students = active_students(state, &since).await?;
+    let mut batch = state.health.outbound(students.len());
+    for uid in students {
+        batch.sent();
+        if let Err(e) = push_to_student(bot, state, uid, &date).await {
+            error!("Daily task for {} not sent: {:?}", uid, e);
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    Ok(())
+}
+
+pub async fn leaderboard_text(pool: &DbPool) -> anyhow::Result<String> {
+    let today = Utc::now().date_naive();
+    let since = today - Duration::days(6);
+
+    let mut rows = Vec::new();
+    for (uid, name, correct, answered) in db::daily_leaderboard(pool, &since.format("%Y-%m-%d").to_string()).await? {
+        let (streak, _) = current_streak(pool, uid).await?;
+        rows.push((name, correct, answered, streak));
+    }
+    rows.sort_by(|a, b| b.1.cmp(&a.1).then(b.3.cmp(&a.3)).then(a.0.cmp(&b.0)));
+
+    let mut text = format!(
+        "🏆 Задача дня: итоги недели ({} – {})",
+        since.format("%d.%m"), today.format("%d.%m")
+    );
+    if rows.is_empty() {
+        text.push_str("\nНа этой неделе никто не отвечал.");
+    }
+    for (i, (name, correct, answered, streak)) in rows.iter().take(LEADERBOARD_SIZE).enumerate() {
+        let medal = match i { 0 => "🥇", 1 => "🥈", 2 => "🥉", _ => "▫️" };
+        text.push_str(&format!(
+            "\n{} {} — верно {} из {}, серия {} дн.",
+            medal, name, correct, answered, streak
+        ));
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+
+    #[test]
+    fn weak_number_comes_from_the_three_lowest_smoothed_rates() {
+        let stats = [(1, 0, 0), (2, 9, 10), (3, 0, 10), (4, 2, 3), (5, 3, 4), (6, 0, 1)];
+        let picked: BTreeSet<i64> = (0..200).filter_map(|_| choose_weak_number(&stats)).collect();
+        assert_eq!(picked, BTreeSet::from([1, 3, 6]));
+
+        assert_eq!(choose_weak_number(&[(7, 5, 5)]), Some(7));
+        assert_eq!(choose_weak_number(&[]), None);
+    }
+
+    #[tokio::test]
+    async fn leaderboard_ranks_by_correct_answers_then_streak() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = db::init_db(&format!("sqlite:{}", dir.path().join("bot.db").display())).await.unwrap();
+        let today = Utc::now().date_naive();
+        let since = today - Duration::days(6);
+        let header = format!("🏆 Задача дня: итоги недели ({} – {})", since.format("%d.%m"), today.format("%d.%m"));
+        assert_eq!(leaderboard_text(&pool).await.unwrap(), format!("{}\nНа этой неделе никто не отвечал.", header));
+
+        let day = |offset: i64| (today - Duration::days(offset)).format("%Y-%m-%d").to_string();
+        sqlx::query("INSERT INTO ege_tasks (id, ege_number, answer) VALUES (1, 5, '7')").execute(&pool).await.unwrap();
+        sqlx::query("INSERT INTO users (id, username, first_name) VALUES (1, 'anna', 'Anna'), (2, '', 'Oleg'), (3, '', ''), (4, 'ivan', 'Ivan'), (5, 'eve', 'Eve')")
+            .execute(&pool).await.unwrap();
+        for (uid, offset, correct, answered) in [
+            (1, 0, 1, true), (1, 1, 1, true),
+            (2, 0, 1, true), (2, 2, 0, true),
+            (3, 0, 1, true), (3, 9, 1, true),
+            (4, 0, 0, false),
+            (5, 3, 0, true),
+        ] {
+            sqlx::query("INSERT INTO daily_tasks (user_id, date, task_id, is_correct, answered_ts) VALUES ($1, $2, 1, $3, $4)")
+                .bind(uid as i64)
+                .bind(day(offset))
+                .bind(correct as i64)
+                .bind(answered.then(|| format!("{}T10:00:00Z", day(offset))))
+                .execute(&pool).await.unwrap();
+        }
+
+        assert_eq!(leaderboard_text(&pool).await.unwrap(), format!(
+            "{}\n🥇 anna — верно 2 из 2, серия 2 дн.\n🥈 Oleg — верно 1 из 2, серия 1 дн.\n🥉 user_3 — верно 1 из 1, серия 1 дн.\n▫️ eve — верно 0 из 1, серия 0 дн.",
+            header
+        ));
+    }
+}
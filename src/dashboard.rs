@@ -0,0 +1,325 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Arc;
+
+use chrono::{Duration, NaiveDate, Utc};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use warp::filters::BoxedFilter;
+use warp::http::{header, StatusCode};
+use warp::reply::Response;
+use warp::{Filter, Reply};
+
+use crate::repo::SubmissionFilter;
+use crate::{keyboards, reports, AppState};
+
+type HmacSha256 = Hmac<Sha256>;
+
+const SESSION_COOKIE: &str = "dash_session";
+const SESSION_TTL_SECS: i64 = 12 * 3600;
+const LOGIN_MAX_AGE_SECS: i64 = 24 * 3600;
+const MISS_REASONS_DAYS: i64 = 30;
+
+pub struct Dashboard {
+    bot_token: String,
+    bot_username: String,
+    teachers: Vec<i64>,
+    session_key: Vec<u8>,
+}
+
+enum Page {
+    Today(Option<String>),
+    Student(i64),
+    Matrix(Option<String>),
+    Reasons,
+}
+
+impl Dashboard {
+    pub fn new(bot_token: &str, bot_username: &str, admin_id: i64) -> anyhow::Result<Self> {
+        let mut teachers = vec![admin_id];
+        if let Ok(extra) = std::env::var("DASHBOARD_TEACHERS") {
+            for id in extra.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+                teachers.push(id.parse::<i64>().map_err(|_| anyhow::anyhow!("Bad id «{}» in DASHBOARD_TEACHERS", id))?);
+            }
+        }
+        let session_key = Sha256::new()
+            .chain_update(b"dashboard-session\n")
+            .chain_update(bot_token.as_bytes())
+            .finalize()
+            .to_vec();
+        Ok(Dashboard { bot_token: bot_token.to_string(), bot_username: bot_username.to_string(), teachers, session_key })
+    }
+
+    fn verify_login(&self, fields: &HashMap<String, String>, now: i64) -> Result<i64, &'static str> {
+        let hash = fields.get("hash").and_then(|h| hex::decode(h).ok()).ok_or("нет подписи")?;
+
+        let mut pairs: Vec<(&String, &String)> = fields.iter().filter(|(k, _)| k.as_str() != "hash").collect();
+        pairs.sort();
+        let data_check_string = pairs.iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>().join("\n");
+
+        let secret = Sha256::digest(self.bot_token.as_bytes());
+        let mut mac = HmacSha256::new_from_slice(&secret).expect("HMAC accepts any key length");
+        mac.update(data_check_string.as_bytes());
+        mac.verify_slice(&hash).map_err(|_| "неверная подпись")?;
+
+        let auth_date = fields.get("auth_date").and_then(|d| d.parse::<i64>().ok()).ok_or("нет даты входа")?;
+        if now - auth_date > LOGIN_MAX_AGE_SECS || auth_date - now > 60 {
+            return Err("ссылка для входа устарела");
+        }
+        let uid = fields.get("id").and_then(|id| id.parse::<i64>().ok()).ok_or("нет id")?;
+        if !self.teachers.contains(&uid) {
+            return Err("нет доступа");
+        }
+        Ok(uid)
+    }
+
+    fn sign(&self, payload: &str) -> String {
+        let mut mac = HmacSha256::new_from_slice(&self.session_key).expect("HMAC accepts any key length");
+        mac.update(payload.as_bytes());
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    fn issue_session(&self, uid: i64, now: i64) -> String {
+        let payload = format!("{}.{}", uid, now + SESSION_TTL_SECS);
+        format!("{}.{}", payload, self.sign(&payload))
+    }
+
+    fn session_user(&self, cookie: Option<&str>, now: i64) -> Option<i64> {
+        let (payload, signature) = cookie?.rsplit_once('.')?;
+        let mut mac = HmacSha256::new_from_slice(&self.session_key).expect("HMAC accepts any key length");
+        mac.update(payload.as_bytes());
+        mac.verify_slice(&hex::decode(signature).ok()?).ok()?;
+
+        let (uid, expires) = payload.split_once('.')?;
+        let uid = uid.parse::<i64>().ok()?;
+        let expires = expires.parse::<i64>().ok()?;
+        (expires > now && self.teachers.contains(&uid)).then_some(uid)
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;").replace('\'', "&#39;")
+}
+
+fn layout(title: &str, body: &str) -> String {
+    format!(
+        "<!doctype html><html lang=\"ru\"><head><meta charset=\"utf-8\"><title>{title}</title>\
+<style>body{{font-family:sans-serif;margin:2em}}table{{border-collapse:collapse}}td,th{{border:1px solid #ccc;padding:4px 8px}}\
+th{{background:#A7F3D0}}nav a{{margin-right:1em}}.zero{{color:#bbb}}</style></head><body>\
+<nav><a href=\"/dashboard\">Сегодня</a><a href=\"/dashboard/matrix\">Ученики × темы</a>\
+<a href=\"/dashboard/reasons\">Причины пропусков</a><a href=\"/dashboard/logout\">Выйти</a></nav>\
+<h1>{title}</h1>{body}</body></html>",
+        title = escape(title),
+        body = body,
+    )
+}
+
+fn html(body: String) -> Response {
+    warp::reply::html(body).into_response()
+}
+
+fn redirect(to: &str, cookie: Option<String>) -> Response {
+    let mut builder = warp::http::Response::builder()
+        .status(StatusCode::SEE_OTHER)
+        .header(header::LOCATION, to);
+    if let Some(cookie) = cookie {
+        builder = builder.header(header::SET_COOKIE, cookie);
+    }
+    builder.body(warp::hyper::Body::empty()).unwrap_or_default()
+}
+
+fn session_cookie(value: &str, max_age: i64) -> String {
+    format!("{}={}; Path=/dashboard; Max-Age={}; HttpOnly; Secure; SameSite=Lax", SESSION_COOKIE, value, max_age)
+}
+
+fn login_page(dashboard: &Dashboard, error: Option<&str>) -> Response {
+    let mut body = String::new();
+    if let Some(error) = error {
+        let _ = write!(body, "<p style=\"color:#b91c1c\">Не удалось войти: {}</p>", escape(error));
+    }
+    let _ = write!(
+        body,
+        "<script async src=\"https://telegram.org/js/telegram-widget.js?22\" data-telegram-login=\"{}\" \
+data-size=\"large\" data-auth-url=\"/dashboard/auth\"></script>",
+        escape(&dashboard.bot_username)
+    );
+    let page = format!(
+        "<!doctype html><html lang=\"ru\"><head><meta charset=\"utf-8\"><title>Вход</title></head>\
+<body style=\"font-family:sans-serif;margin:2em\"><h1>Панель преподавателя</h1>{}</body></html>",
+        body
+    );
+    let code = if error.is_some() { StatusCode::FORBIDDEN } else { StatusCode::OK };
+    warp::reply::with_status(warp::reply::html(page), code).into_response()
+}
+
+async fn today_page(state: &AppState, date: Option<String>) -> anyhow::Result<String> {
+    let date = date
+        .filter(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").is_ok())
+        .unwrap_or_else(|| Utc::now().format("%Y-%m-%d").to_string());
+
+    let mut body = format!(
+        "<form><input type=\"date\" name=\"date\" value=\"{}\"> <button>Показать</button></form>\
+<h2>Сводка</h2><table><tr><th>Ученик</th><th>ДЗ</th><th>Конспекты</th><th>Причина пропуска</th><th>Последняя тема</th></tr>",
+        escape(&date)
+    );
+    for row in reports::daily_summary(&state.repos, &date).await? {
+        let _ = write!(
+            body,
+            "<tr><td><a href=\"/dashboard/student/{}\">{}</a></td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            row.user_id, escape(&row.name), row.dz_count, row.conspect_count, escape(&row.reason), escape(&row.task_flag)
+        );
+    }
+    body.push_str("</table><h2>Сдачи</h2><table><tr><th>Время</th><th>Ученик</th><th>Тип</th><th>Раздел</th><th>Тема</th><th>Содержание</th></tr>");
+    for (sub, user) in reports::day_submissions(&state.repos, &date).await? {
+        let name = user.map(|u| u.display_name()).unwrap_or_default();
+        let _ = write!(
+            body,
+            "<tr><td>{}</td><td><a href=\"/dashboard/student/{}\">{}</a></td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            escape(&sub.ts), sub.user_id, escape(&name), sub.kind.as_str(), escape(&sub.section), escape(&sub.topic_title), escape(&sub.summary)
+        );
+    }
+    body.push_str("</table>");
+    Ok(layout(&format!("Сдачи за {}", date), &body))
+}
+
+async fn student_page(state: &AppState, uid: i64) -> anyhow::Result<Option<String>> {
+    let Some(user) = state.repos.users.get(uid).await? else {
+        return Ok(None);
+    };
+    let filter = SubmissionFilter { user_id: Some(uid), ..Default::default() };
+    let submissions = state.repos.submissions.list(&filter, None, 0).await?;
+
+    let mut body = format!("<p>Всего сдач: {}</p><table><tr><th>Дата</th><th>Тип</th><th>Раздел</th><th>Тема</th><th>Содержание</th><th>Статус</th></tr>", submissions.len());
+    for sub in submissions {
+        let _ = write!(
+            body,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            escape(&sub.date), sub.kind.as_str(), escape(&sub.section), escape(&sub.topic_title), escape(&sub.summary), sub.status.as_str()
+        );
+    }
+    body.push_str("</table>");
+    let title = match &user.username {
+        Some(username) => format!("Ученик @{}", username),
+        None => format!("Ученик {}", uid),
+    };
+    Ok(Some(layout(&title, &body)))
+}
+
+async fn matrix_page(state: &AppState, section: Option<String>) -> anyhow::Result<String> {
+    let section = section
+        .filter(|s| keyboards::SECTIONS.contains(&s.as_str()))
+        .unwrap_or_else(|| keyboards::SECTIONS[0].to_string());
+    let topics = keyboards::section_topics(&section);
+    let matrix = reports::topic_matrix(&state.repos, &section).await?;
+
+    let mut body = String::from("<p>");
+    for s in keyboards::SECTIONS {
+        let _ = write!(body, "<a href=\"/dashboard/matrix?section={}\">{}</a> ", url::form_urlencoded::byte_serialize(s.as_bytes()).collect::<String>(), escape(s));
+    }
+    body.push_str("</p><table><tr><th>Ученик</th>");
+    for (_, title) in &topics {
+        let _ = write!(body, "<th>{}</th>", escape(title));
+    }
+    body.push_str("</tr>");
+    for (uid, name) in &matrix.students {
+        let _ = write!(body, "<tr><td><a href=\"/dashboard/student/{}\">{}</a></td>", uid, escape(name));
+        for (_, title) in &topics {
+            match matrix.counts.get(&(*uid, title.clone())) {
+                Some(n) => { let _ = write!(body, "<td>{}</td>", n); }
+                None => body.push_str("<td class=\"zero\">0</td>"),
+            }
+        }
+        body.push_str("</tr>");
+    }
+    body.push_str("</table>");
+    Ok(layout(&format!("Ученики × темы: {}", section), &body))
+}
+
+async fn reasons_page(state: &AppState) -> anyhow::Result<String> {
+    let since = (Utc::now() - Duration::days(MISS_REASONS_DAYS)).format("%Y-%m-%d").to_string();
+    let mut body = String::from("<table><tr><th>Дата</th><th>Ученик</th><th>Причина</th></tr>");
+    for (miss, name) in reports::miss_reasons_since(&state.repos, &since).await? {
+        let reason = if miss.reason.is_empty() { "<i>не указана</i>".to_string() } else { escape(&miss.reason) };
+        let _ = write!(
+            body,
+            "<tr><td>{}</td><td><a href=\"/dashboard/student/{}\">{}</a></td><td>{}</td></tr>",
+            escape(&miss.date), miss.user_id, escape(&name), reason
+        );
+    }
+    body.push_str("</table>");
+    Ok(layout(&format!("Причины пропусков за {} дней", MISS_REASONS_DAYS), &body))
+}
+
+async fn serve_page(state: AppState, dashboard: Arc<Dashboard>, cookie: Option<String>, page: Page) -> Result<Response, warp::Rejection> {
+    if dashboard.session_user(cookie.as_deref(), Utc::now().timestamp()).is_none() {
+        return Ok(redirect("/dashboard/login", None));
+    }
+    let rendered = match page {
+        Page::Today(date) => today_page(&state, date).await.map(Some),
+        Page::Student(uid) => student_page(&state, uid).await,
+        Page::Matrix(section) => matrix_page(&state, section).await.map(Some),
+        Page::Reasons => reasons_page(&state).await.map(Some),
+    };
+    match rendered {
+        Ok(Some(body)) => Ok(html(body)),
+        Ok(None) => Ok(warp::reply::with_status(warp::reply::html(layout("Не найдено", "")), StatusCode::NOT_FOUND).into_response()),
+        Err(e) => {
+            log::error!("Dashboard page failed: {:?}", e);
+            Ok(warp::reply::with_status(warp::reply::html(layout("Ошибка", "<p>Не удалось загрузить данные.</p>")), StatusCode::INTERNAL_SERVER_ERROR).into_response())
+        }
+    }
+}
+
+pub fn routes(state: AppState, dashboard: Arc<Dashboard>) -> BoxedFilter<(Response,)> {
+    let with_state = warp::any().map(move || state.clone());
+    let dash = dashboard.clone();
+    let with_dashboard = warp::any().map(move || dash.clone());
+    let session = warp::cookie::optional::<String>(SESSION_COOKIE);
+
+    let login_dash = dashboard.clone();
+    let login = warp::path!("dashboard" / "login")
+        .and(warp::get())
+        .map(move || login_page(&login_dash, None));
+
+    let auth_dash = dashboard.clone();
+    let auth = warp::path!("dashboard" / "auth")
+        .and(warp::get())
+        .and(warp::query::<HashMap<String, String>>())
+        .map(move |fields: HashMap<String, String>| {
+            let now = Utc::now().timestamp();
+            match auth_dash.verify_login(&fields, now) {
+                Ok(uid) => redirect("/dashboard", Some(session_cookie(&auth_dash.issue_session(uid, now), SESSION_TTL_SECS))),
+                Err(reason) => login_page(&auth_dash, Some(reason)),
+            }
+        });
+
+    let logout = warp::path!("dashboard" / "logout")
+        .and(warp::get())
+        .map(|| redirect("/dashboard/login", Some(session_cookie("", 0))));
+
+    let today = warp::path!("dashboard")
+        .and(warp::get())
+        .and(warp::query::<HashMap<String, String>>())
+        .map(|mut q: HashMap<String, String>| Page::Today(q.remove("date")));
+    let student = warp::path!("dashboard" / "student" / i64)
+        .and(warp::get())
+        .map(Page::Student);
+    let matrix = warp::path!("dashboard" / "matrix")
+        .and(warp::get())
+        .and(warp::query::<HashMap<String, String>>())
+        .map(|mut q: HashMap<String, String>| Page::Matrix(q.remove("section")));
+    let reasons = warp::path!("dashboard" / "reasons")
+        .and(warp::get())
+        .map(|| Page::Reasons);
+
+    let pages = with_state
+        .and(with_dashboard)
+        .and(session)
+        .and(today.or(student).unify().or(matrix).unify().or(reasons).unify())
+        .and_then(serve_page);
+
+    login.or(auth).unify()
+        .or(logout).unify()
+        .or(pages).unify()
+        .boxed()
+}
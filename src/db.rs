@@ -1,27 +1,72 @@
-use sqlx::{sqlite::{SqlitePoolOptions, SqliteConnectOptions}, Pool, Sqlite, Row};
-use std::path::Path;
-use std::str::FromStr;
+use futures::TryStreamExt;
+use sqlx::any::AnyPoolOptions;
+use sqlx::{AnyPool, Column, Row};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 
-pub type DbPool = Pool<Sqlite>;
+pub type DbPool = AnyPool;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    Sqlite,
+    Postgres,
+}
+
+impl Backend {
+    pub fn from_url(database_url: &str) -> Result<Self, sqlx::Error> {
+        let scheme = database_url.split(':').next().unwrap_or_default();
+        match scheme {
+            "sqlite" => Ok(Backend::Sqlite),
+            "postgres" | "postgresql" => Ok(Backend::Postgres),
+            _ => Err(sqlx::Error::Configuration(format!("Unsupported database scheme {:?}", scheme).into())),
+        }
+    }
+
+    pub fn of(pool: &DbPool) -> Self {
+        Backend::from_url(pool.connect_options().database_url.as_str()).unwrap_or(Backend::Sqlite)
+    }
+
+    fn ddl(self, sql: &str) -> Cow<'_, str> {
+        match self {
+            Backend::Sqlite => Cow::Borrowed(sql),
+            Backend::Postgres => Cow::Owned(
+                sql.replace("INTEGER PRIMARY KEY AUTOINCREMENT", "BIGSERIAL PRIMARY KEY")
+                    .replace("INTEGER", "BIGINT")
+                    .replace("REAL", "DOUBLE PRECISION")
+            ),
+        }
+    }
+}
 
 pub async fn init_db(database_url: &str) -> Result<DbPool, sqlx::Error> {
-    let connection_options = SqliteConnectOptions::from_str(database_url)?
-        .create_if_missing(true);
+    sqlx::any::install_default_drivers();
+    let backend = Backend::from_url(database_url)?;
+
+    let (connection_url, max_connections) = match backend {
+        Backend::Sqlite if !database_url.contains("mode=") => {
+            let separator = if database_url.contains('?') { '&' } else { '?' };
+            (format!("{}{}mode=rwc", database_url, separator), 5)
+        }
+        Backend::Sqlite => (database_url.to_string(), 5),
+        Backend::Postgres => (database_url.to_string(), 20),
+    };
 
-    let pool = SqlitePoolOptions::new()
-        .max_connections(5)
-        .connect_with(connection_options)
+    let pool = AnyPoolOptions::new()
+        .max_connections(max_connections)
+        .connect(&connection_url)
         .await?;
 
-    sqlx::query(
+    sqlx::query(&backend.ddl(
         "CREATE TABLE IF NOT EXISTS users (
             id INTEGER PRIMARY KEY,
             username TEXT,
             first_name TEXT
         );"
-    ).execute(&pool).await?;
+    )).execute(&pool).await?;
 
-    sqlx::query(
+    ensure_column(&pool, backend, "users", "group_name", "TEXT NOT NULL DEFAULT ''").await?;
+
+    sqlx::query(&backend.ddl(
         "CREATE TABLE IF NOT EXISTS submissions (
             id INTEGER PRIMARY KEY AUTOINCREMENT,
             user_id INTEGER,
@@ -35,118 +80,1299 @@ pub async fn init_db(database_url: &str) -> Result<DbPool, sqlx::Error> {
             message_id INTEGER,
             date TEXT,
             ts TEXT,
+            status TEXT NOT NULL DEFAULT 'submitted',
             FOREIGN KEY (user_id) REFERENCES users(id)
         );"
-    ).execute(&pool).await?;
+    )).execute(&pool).await?;
 
-    sqlx::query(
+    ensure_column(&pool, backend, "submissions", "status", "TEXT NOT NULL DEFAULT 'submitted'").await?;
+
+    sqlx::query(&backend.ddl(
+        "CREATE TABLE IF NOT EXISTS shared_conspects (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            submission_id INTEGER UNIQUE,
+            author_id INTEGER,
+            section TEXT,
+            topic_id TEXT,
+            topic_title TEXT,
+            status TEXT NOT NULL DEFAULT 'pending',
+            ts TEXT,
+            FOREIGN KEY (submission_id) REFERENCES submissions(id)
+        );"
+    )).execute(&pool).await?;
+
+    sqlx::query(&backend.ddl(
+        "CREATE TABLE IF NOT EXISTS ege_tasks (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            ege_number INTEGER NOT NULL,
+            variant TEXT NOT NULL DEFAULT '',
+            statement TEXT,
+            file_id TEXT NOT NULL DEFAULT '',
+            file_kind TEXT NOT NULL DEFAULT '',
+            answer TEXT NOT NULL,
+            answer_kind TEXT NOT NULL DEFAULT 'exact',
+            attachments TEXT NOT NULL DEFAULT '',
+            ts TEXT
+        );"
+    )).execute(&pool).await?;
+
+    ensure_column(&pool, backend, "ege_tasks", "attachments", "TEXT NOT NULL DEFAULT ''").await?;
+
+    sqlx::query(&backend.ddl(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_ege_tasks_variant ON ege_tasks (ege_number, variant) WHERE variant != ''"
+    )).execute(&pool).await?;
+
+    sqlx::query(&backend.ddl(
+        "CREATE TABLE IF NOT EXISTS task_attempts (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            user_id INTEGER,
+            task_id INTEGER,
+            submission_id INTEGER,
+            answer TEXT,
+            is_correct INTEGER NOT NULL,
+            date TEXT,
+            ts TEXT,
+            FOREIGN KEY (user_id) REFERENCES users(id),
+            FOREIGN KEY (task_id) REFERENCES ege_tasks(id)
+        );"
+    )).execute(&pool).await?;
+
+    sqlx::query(&backend.ddl(
+        "CREATE TABLE IF NOT EXISTS daily_tasks (
+            user_id INTEGER,
+            date TEXT,
+            task_id INTEGER,
+            answer TEXT,
+            is_correct INTEGER,
+            answered_ts TEXT,
+            PRIMARY KEY (user_id, date),
+            FOREIGN KEY (user_id) REFERENCES users(id),
+            FOREIGN KEY (task_id) REFERENCES ege_tasks(id)
+        );"
+    )).execute(&pool).await?;
+
+    sqlx::query(&backend.ddl(
+        "CREATE TABLE IF NOT EXISTS flashcards (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            section TEXT,
+            topic_id TEXT,
+            front TEXT NOT NULL,
+            back TEXT NOT NULL,
+            ts TEXT
+        );"
+    )).execute(&pool).await?;
+
+    sqlx::query(&backend.ddl(
+        "CREATE TABLE IF NOT EXISTS card_reviews (
+            user_id INTEGER,
+            card_id INTEGER,
+            ease REAL NOT NULL,
+            interval_days INTEGER NOT NULL,
+            repetitions INTEGER NOT NULL,
+            due_date TEXT NOT NULL,
+            PRIMARY KEY (user_id, card_id),
+            FOREIGN KEY (user_id) REFERENCES users(id),
+            FOREIGN KEY (card_id) REFERENCES flashcards(id)
+        );"
+    )).execute(&pool).await?;
+
+    sqlx::query(&backend.ddl(
+        "CREATE TABLE IF NOT EXISTS card_review_log (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            user_id INTEGER,
+            card_id INTEGER,
+            knew INTEGER NOT NULL,
+            ts TEXT,
+            FOREIGN KEY (user_id) REFERENCES users(id),
+            FOREIGN KEY (card_id) REFERENCES flashcards(id)
+        );"
+    )).execute(&pool).await?;
+
+    sqlx::query(&backend.ddl(
+        "CREATE TABLE IF NOT EXISTS quizzes (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            section TEXT,
+            topic_id TEXT,
+            ts TEXT
+        );"
+    )).execute(&pool).await?;
+
+    sqlx::query(&backend.ddl(
+        "CREATE TABLE IF NOT EXISTS quiz_questions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            quiz_id INTEGER NOT NULL,
+            position INTEGER NOT NULL,
+            question TEXT NOT NULL,
+            options TEXT NOT NULL,
+            correct_option INTEGER NOT NULL,
+            FOREIGN KEY (quiz_id) REFERENCES quizzes(id)
+        );"
+    )).execute(&pool).await?;
+
+    sqlx::query(&backend.ddl(
+        "CREATE TABLE IF NOT EXISTS quiz_polls (
+            poll_id TEXT PRIMARY KEY,
+            question_id INTEGER NOT NULL,
+            user_id INTEGER NOT NULL,
+            sent_ts TEXT,
+            option_id INTEGER,
+            is_correct INTEGER,
+            answered_ts TEXT,
+            FOREIGN KEY (question_id) REFERENCES quiz_questions(id),
+            FOREIGN KEY (user_id) REFERENCES users(id)
+        );"
+    )).execute(&pool).await?;
+
+    sqlx::query(&backend.ddl(
+        "CREATE TABLE IF NOT EXISTS quiz_early_answers (
+            poll_id TEXT PRIMARY KEY,
+            user_id INTEGER NOT NULL,
+            option_id INTEGER NOT NULL,
+            answered_ts TEXT
+        );"
+    )).execute(&pool).await?;
+
+    sqlx::query(&backend.ddl(
+        "CREATE TABLE IF NOT EXISTS code_tests (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            section TEXT,
+            topic_id TEXT,
+            input TEXT,
+            expected TEXT
+        );"
+    )).execute(&pool).await?;
+
+    sqlx::query(&backend.ddl(
+        "CREATE TABLE IF NOT EXISTS mock_variants (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            title TEXT UNIQUE NOT NULL,
+            ts TEXT
+        );"
+    )).execute(&pool).await?;
+
+    sqlx::query(&backend.ddl(
+        "CREATE TABLE IF NOT EXISTS mock_variant_tasks (
+            variant_id INTEGER,
+            ege_number INTEGER,
+            task_id INTEGER,
+            PRIMARY KEY (variant_id, ege_number),
+            FOREIGN KEY (variant_id) REFERENCES mock_variants(id),
+            FOREIGN KEY (task_id) REFERENCES ege_tasks(id)
+        );"
+    )).execute(&pool).await?;
+
+    sqlx::query(&backend.ddl(
+        "CREATE TABLE IF NOT EXISTS mock_attempts (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            user_id INTEGER,
+            variant_id INTEGER,
+            started_ts TEXT,
+            deadline_ts TEXT,
+            finished_ts TEXT,
+            last_notice_min INTEGER,
+            primary_score INTEGER,
+            test_score INTEGER,
+            FOREIGN KEY (user_id) REFERENCES users(id),
+            FOREIGN KEY (variant_id) REFERENCES mock_variants(id)
+        );"
+    )).execute(&pool).await?;
+
+    sqlx::query(&backend.ddl(
+        "CREATE TABLE IF NOT EXISTS mock_answers (
+            attempt_id INTEGER,
+            ege_number INTEGER,
+            answer TEXT,
+            points INTEGER,
+            PRIMARY KEY (attempt_id, ege_number),
+            FOREIGN KEY (attempt_id) REFERENCES mock_attempts(id)
+        );"
+    )).execute(&pool).await?;
+
+    ensure_column(&pool, backend, "mock_answers", "teacher_points", "INTEGER").await?;
+
+    sqlx::query(&backend.ddl(
+        "CREATE TABLE IF NOT EXISTS conspect_likes (
+            shared_id INTEGER,
+            user_id INTEGER,
+            PRIMARY KEY (shared_id, user_id)
+        );"
+    )).execute(&pool).await?;
+
+    sqlx::query(&backend.ddl(
         "CREATE TABLE IF NOT EXISTS miss_reasons (
             user_id INTEGER,
             date TEXT,
             reason TEXT,
             PRIMARY KEY (user_id, date)
         );"
-    ).execute(&pool).await?;
+    )).execute(&pool).await?;
+
+    sqlx::query(&backend.ddl(
+        "CREATE TABLE IF NOT EXISTS webhook_deliveries (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            event TEXT NOT NULL,
+            url TEXT NOT NULL,
+            payload TEXT NOT NULL,
+            status TEXT NOT NULL DEFAULT 'pending',
+            attempts INTEGER NOT NULL DEFAULT 0,
+            last_status_code INTEGER,
+            last_error TEXT,
+            created_ts TEXT NOT NULL,
+            updated_ts TEXT NOT NULL
+        );"
+    )).execute(&pool).await?;
 
     Ok(pool)
 }
 
-pub async fn upsert_user(pool: &DbPool, id: i64, username: Option<String>, first_name: String) -> anyhow::Result<()> {
-    sqlx::query("INSERT OR REPLACE INTO users (id, username, first_name) VALUES (?, ?, ?)")
-        .bind(id)
-        .bind(username.unwrap_or_default())
-        .bind(first_name)
-        .execute(pool)
+async fn ensure_column(pool: &DbPool, backend: Backend, table: &str, column: &str, definition: &str) -> Result<(), sqlx::Error> {
+    if backend == Backend::Postgres {
+        sqlx::query(&format!("ALTER TABLE {} ADD COLUMN IF NOT EXISTS {} {}", table, column, definition))
+            .execute(pool)
+            .await?;
+        return Ok(());
+    }
+
+    let exists: Option<i64> = sqlx::query_scalar("SELECT 1 FROM pragma_table_info($1) WHERE name = $2")
+        .bind(table)
+        .bind(column)
+        .fetch_optional(pool)
         .await?;
+
+    if exists.is_none() {
+        sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))
+            .execute(pool)
+            .await?;
+    }
     Ok(())
 }
 
-pub async fn add_submission(
+pub async fn share_conspect(pool: &DbPool, submission_id: i64, approved: bool) -> anyhow::Result<Option<(i64, String)>> {
+    sqlx::query(
+        "INSERT INTO shared_conspects (submission_id, author_id, section, topic_id, topic_title, status, ts)
+         SELECT id, user_id, section, topic_id, topic_title, 'pending', $1
+         FROM submissions WHERE id = $2 AND type = 'conspect' AND status != 'withdrawn'
+         ON CONFLICT DO NOTHING"
+    )
+        .bind(chrono::Utc::now().to_rfc3339())
+        .bind(submission_id)
+        .execute(pool)
+        .await?;
+
+    if approved {
+        sqlx::query("UPDATE shared_conspects SET status = 'approved' WHERE submission_id = $1")
+            .bind(submission_id)
+            .execute(pool)
+            .await?;
+    }
+
+    let row = sqlx::query_as::<_, (i64, String)>("SELECT id, status FROM shared_conspects WHERE submission_id = $1")
+        .bind(submission_id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row)
+}
+
+pub async fn set_shared_status(pool: &DbPool, shared_id: i64, status: &str) -> anyhow::Result<Option<(i64, String)>> {
+    sqlx::query("UPDATE shared_conspects SET status = $1 WHERE id = $2")
+        .bind(status)
+        .bind(shared_id)
+        .execute(pool)
+        .await?;
+
+    let row = sqlx::query_as::<_, (i64, String)>("SELECT author_id, topic_title FROM shared_conspects WHERE id = $1")
+        .bind(shared_id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row)
+}
+
+pub async fn get_shared_review(pool: &DbPool, shared_id: i64) -> anyhow::Result<Option<(i64, i64, String, String)>> {
+    let row = sqlx::query_as::<_, (i64, i64, String, String)>(
+        "SELECT submission_id, author_id, topic_title, status FROM shared_conspects WHERE id = $1"
+    )
+        .bind(shared_id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row)
+}
+
+pub async fn get_shared_conspect(
+    pool: &DbPool,
+    shared_id: i64
+) -> anyhow::Result<Option<(String, String, String, String, String, i64)>> {
+    let row = sqlx::query_as::<_, (String, String, String, String, String, i64)>(
+        "SELECT COALESCE(NULLIF(u.username, ''), u.first_name, 'ученик'), c.topic_title,
+                s.content_type, s.content_summary, s.photo_file_id,
+                (SELECT COUNT(*) FROM conspect_likes l WHERE l.shared_id = c.id)
+         FROM shared_conspects c
+         JOIN submissions s ON s.id = c.submission_id
+         LEFT JOIN users u ON u.id = c.author_id
+         WHERE c.id = $1 AND s.status != 'withdrawn'"
+    )
+        .bind(shared_id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row)
+}
+
+pub async fn count_shared_by_topic(pool: &DbPool, section: &str) -> anyhow::Result<Vec<(String, i64)>> {
+    let rows = sqlx::query_as::<_, (String, i64)>(
+        "SELECT c.topic_id, COUNT(*) FROM shared_conspects c
+         JOIN submissions s ON s.id = c.submission_id
+         WHERE c.section = $1 AND c.status = 'approved' AND s.status != 'withdrawn'
+         GROUP BY c.topic_id"
+    )
+        .bind(section)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows)
+}
+
+pub async fn list_shared_conspects(pool: &DbPool, section: &str, topic_id: &str) -> anyhow::Result<Vec<(i64, String, i64)>> {
+    let rows = sqlx::query_as::<_, (i64, String, i64)>(
+        "SELECT c.id, COALESCE(NULLIF(u.username, ''), u.first_name, 'ученик') AS author,
+                (SELECT COUNT(*) FROM conspect_likes l WHERE l.shared_id = c.id) AS likes
+         FROM shared_conspects c
+         JOIN submissions s ON s.id = c.submission_id
+         LEFT JOIN users u ON u.id = c.author_id
+         WHERE c.section = $1 AND c.topic_id = $2 AND c.status = 'approved' AND s.status != 'withdrawn'
+         ORDER BY likes DESC, c.id DESC"
+    )
+        .bind(section)
+        .bind(topic_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows)
+}
+
+pub async fn toggle_conspect_like(pool: &DbPool, shared_id: i64, user_id: i64) -> anyhow::Result<i64> {
+    let inserted = sqlx::query("INSERT INTO conspect_likes (shared_id, user_id) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+        .bind(shared_id)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+
+    if inserted.rows_affected() == 0 {
+        sqlx::query("DELETE FROM conspect_likes WHERE shared_id = $1 AND user_id = $2")
+            .bind(shared_id)
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+    }
+
+    let likes: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM conspect_likes WHERE shared_id = $1")
+        .bind(shared_id)
+        .fetch_one(pool)
+        .await?;
+    Ok(likes)
+}
+
+pub async fn add_task(
+    pool: &DbPool,
+    ege_number: i64,
+    statement: &str,
+    file_id: &str,
+    file_kind: &str,
+    answer: &str,
+    answer_kind: crate::tasks::AnswerKind
+) -> anyhow::Result<i64> {
+    let res = sqlx::query(
+        "INSERT INTO ege_tasks (ege_number, statement, file_id, file_kind, answer, answer_kind, ts)
+         VALUES ($1, $2, $3, $4, $5, $6, $7)
+         RETURNING id"
+    )
+        .bind(ege_number)
+        .bind(statement)
+        .bind(file_id)
+        .bind(file_kind)
+        .bind(answer)
+        .bind(answer_kind.as_str())
+        .bind(chrono::Utc::now().to_rfc3339())
+        .fetch_one(pool)
+        .await?;
+    Ok(res.get(0))
+}
+
+pub async fn upsert_task(
+    conn: &mut sqlx::AnyConnection,
+    ege_number: i64,
+    variant: &str,
+    statement: &str,
+    answer: &str,
+    answer_kind: crate::tasks::AnswerKind,
+    attachments: &str
+) -> anyhow::Result<bool> {
+    let existing: Option<i64> = sqlx::query_scalar("SELECT id FROM ege_tasks WHERE ege_number = $1 AND variant = $2")
+        .bind(ege_number)
+        .bind(variant)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+    sqlx::query(
+        "INSERT INTO ege_tasks (ege_number, variant, statement, answer, answer_kind, attachments, ts)
+         VALUES ($1, $2, $3, $4, $5, $6, $7)
+         ON CONFLICT (ege_number, variant) WHERE variant != '' DO UPDATE SET
+            statement = excluded.statement,
+            answer = excluded.answer,
+            answer_kind = excluded.answer_kind,
+            attachments = excluded.attachments"
+    )
+        .bind(ege_number)
+        .bind(variant)
+        .bind(statement)
+        .bind(answer)
+        .bind(answer_kind.as_str())
+        .bind(attachments)
+        .bind(chrono::Utc::now().to_rfc3339())
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(existing.is_none())
+}
+
+pub async fn count_tasks_by_number(pool: &DbPool) -> anyhow::Result<Vec<(i64, i64)>> {
+    let rows = sqlx::query_as::<_, (i64, i64)>(
+        "SELECT ege_number, COUNT(*) FROM ege_tasks GROUP BY ege_number ORDER BY ege_number"
+    )
+        .fetch_all(pool)
+        .await?;
+    Ok(rows)
+}
+
+pub async fn pick_task(pool: &DbPool, ege_number: i64, user_id: i64) -> anyhow::Result<Option<(i64, String, String, String, String)>> {
+    let row = sqlx::query_as::<_, (i64, String, String, String, String)>(
+        "SELECT id, COALESCE(statement, ''), file_id, file_kind, attachments FROM ege_tasks
+         WHERE ege_number = $1
+           AND id NOT IN (SELECT task_id FROM mock_variant_tasks WHERE task_id IS NOT NULL)
+         ORDER BY id IN (SELECT task_id FROM task_attempts WHERE user_id = $2 AND is_correct = 1), RANDOM()
+         LIMIT 1"
+    )
+        .bind(ege_number)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row)
+}
+
+pub async fn get_task_answer(pool: &DbPool, task_id: i64) -> anyhow::Result<Option<(i64, String, String)>> {
+    let row = sqlx::query_as::<_, (i64, String, String)>(
+        "SELECT ege_number, answer, answer_kind FROM ege_tasks WHERE id = $1"
+    )
+        .bind(task_id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row)
+}
+
+pub async fn record_task_attempt(
     pool: &DbPool,
     user_id: i64,
-    kind: &crate::states::SubmissionType,
-    section: &str,
-    topic_id: &str,
-    topic_title: &str,
-    content_type: &str,
-    content_summary: &str,
-    photo_file_id: &str,
-    message_id: i32,
-    date: &str,
+    task_id: i64,
+    submission_id: Option<i64>,
+    answer: &str,
+    is_correct: bool,
     ts: &str
 ) -> anyhow::Result<()> {
-    let type_str = match kind {
-        crate::states::SubmissionType::Dz => "dz",
-        crate::states::SubmissionType::Conspect => "conspect",
-    };
+    sqlx::query(
+        "INSERT INTO task_attempts (user_id, task_id, submission_id, answer, is_correct, date, ts)
+         VALUES ($1, $2, $3, $4, $5, $6, $7)"
+    )
+        .bind(user_id)
+        .bind(task_id)
+        .bind(submission_id)
+        .bind(answer)
+        .bind(is_correct as i64)
+        .bind(ts.get(..10).unwrap_or(ts))
+        .bind(ts)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn recent_task_solvers(pool: &DbPool, since: &str) -> anyhow::Result<Vec<i64>> {
+    let rows: Vec<i64> = sqlx::query_scalar("SELECT DISTINCT user_id FROM task_attempts WHERE date >= $1")
+        .bind(since)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows)
+}
+
+pub async fn solve_counts(pool: &DbPool, since: &str) -> anyhow::Result<Vec<(i64, i64, i64, i64, i64)>> {
+    let rows = sqlx::query_as::<_, (i64, i64, i64, i64, i64)>(
+        "SELECT a.user_id, t.ege_number, CAST(SUM(a.is_correct) AS BIGINT), CAST(0 AS BIGINT), COUNT(*)
+         FROM task_attempts a JOIN ege_tasks t ON t.id = a.task_id
+         WHERE a.date >= $1
+         GROUP BY a.user_id, t.ege_number
+         UNION ALL
+         SELECT m.user_id, ma.ege_number,
+                CAST(SUM(CASE WHEN ma.teacher_points IS NULL THEN COALESCE(ma.points, 0) ELSE 0 END) AS BIGINT),
+                CAST(SUM(COALESCE(ma.teacher_points, 0)) AS BIGINT),
+                CAST(SUM(CASE WHEN ma.ege_number IN (26, 27) THEN 2 ELSE 1 END) AS BIGINT)
+         FROM mock_answers ma JOIN mock_attempts m ON m.id = ma.attempt_id
+         WHERE m.finished_ts IS NOT NULL AND substr(m.finished_ts, 1, 10) >= $2
+         GROUP BY m.user_id, ma.ege_number"
+    )
+        .bind(since)
+        .bind(since)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows)
+}
+
+pub async fn task_number_stats(pool: &DbPool, user_id: i64) -> anyhow::Result<Vec<(i64, i64, i64)>> {
+    let rows = sqlx::query_as::<_, (i64, i64, i64)>(
+        "SELECT b.ege_number, CAST(COALESCE(SUM(a.is_correct), 0) AS BIGINT), COUNT(a.id)
+         FROM (SELECT DISTINCT ege_number FROM ege_tasks) b
+         LEFT JOIN ege_tasks e ON e.ege_number = b.ege_number
+         LEFT JOIN task_attempts a ON a.task_id = e.id AND a.user_id = $1
+         GROUP BY b.ege_number"
+    )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows)
+}
+
+pub async fn create_daily_task(pool: &DbPool, user_id: i64, date: &str, task_id: i64) -> anyhow::Result<bool> {
+    let res = sqlx::query("INSERT INTO daily_tasks (user_id, date, task_id) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING")
+        .bind(user_id)
+        .bind(date)
+        .bind(task_id)
+        .execute(pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn get_pending_daily_task(pool: &DbPool, user_id: i64, date: &str) -> anyhow::Result<Option<i64>> {
+    let id: Option<i64> = sqlx::query_scalar(
+        "SELECT task_id FROM daily_tasks WHERE user_id = $1 AND date = $2 AND answered_ts IS NULL"
+    )
+        .bind(user_id)
+        .bind(date)
+        .fetch_optional(pool)
+        .await?;
+    Ok(id)
+}
+
+pub async fn answer_daily_task(pool: &DbPool, user_id: i64, date: &str, answer: &str, is_correct: bool) -> anyhow::Result<bool> {
+    let res = sqlx::query(
+        "UPDATE daily_tasks SET answer = $1, is_correct = $2, answered_ts = $3
+         WHERE user_id = $4 AND date = $5 AND answered_ts IS NULL"
+    )
+        .bind(answer)
+        .bind(is_correct as i64)
+        .bind(chrono::Utc::now().to_rfc3339())
+        .bind(user_id)
+        .bind(date)
+        .execute(pool)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn daily_correct_dates(pool: &DbPool, user_id: i64) -> anyhow::Result<Vec<String>> {
+    let rows: Vec<String> = sqlx::query_scalar(
+        "SELECT date FROM daily_tasks WHERE user_id = $1 AND is_correct = 1 ORDER BY date"
+    )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows)
+}
+
+pub async fn daily_leaderboard(pool: &DbPool, since: &str) -> anyhow::Result<Vec<(i64, String, i64, i64)>> {
+    let rows = sqlx::query_as::<_, (i64, String, i64, i64)>(
+        "SELECT d.user_id, COALESCE(NULLIF(u.username, ''), NULLIF(u.first_name, ''), 'user_' || d.user_id),
+                CAST(COALESCE(SUM(d.is_correct), 0) AS BIGINT), COUNT(d.answered_ts)
+         FROM daily_tasks d LEFT JOIN users u ON u.id = d.user_id
+         WHERE d.date >= $1
+         GROUP BY d.user_id, u.username, u.first_name
+         HAVING COUNT(d.answered_ts) > 0"
+    )
+        .bind(since)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows)
+}
+
+pub async fn card_retention(pool: &DbPool, user_id: i64, since: &str) -> anyhow::Result<Vec<(String, String, i64, i64, i64, i64)>> {
+    let rows = sqlx::query_as::<_, (String, String, i64, i64, i64, i64)>(
+        "SELECT f.section, f.topic_id,
+                CAST(SUM(l.knew) AS BIGINT), COUNT(*),
+                (SELECT COUNT(*) FROM card_reviews r JOIN flashcards c ON c.id = r.card_id
+                 WHERE r.user_id = $1 AND c.section = f.section AND c.topic_id = f.topic_id AND r.repetitions >= 2),
+                (SELECT COUNT(*) FROM flashcards c WHERE c.section = f.section AND c.topic_id = f.topic_id)
+         FROM card_review_log l JOIN flashcards f ON f.id = l.card_id
+         WHERE l.user_id = $1 AND l.ts >= $2
+         GROUP BY f.section, f.topic_id
+         ORDER BY f.section, f.topic_id"
+    )
+        .bind(user_id)
+        .bind(since)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows)
+}
+
+pub async fn add_flashcards(pool: &DbPool, section: &str, topic_id: &str, cards: &[(String, String)]) -> anyhow::Result<()> {
+    let mut tx = pool.begin().await?;
+    let ts = chrono::Utc::now().to_rfc3339();
+    for (front, back) in cards {
+        sqlx::query("INSERT INTO flashcards (section, topic_id, front, back, ts) VALUES ($1, $2, $3, $4, $5)")
+            .bind(section)
+            .bind(topic_id)
+            .bind(front)
+            .bind(back)
+            .bind(&ts)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+pub async fn flashcard_topics(pool: &DbPool, user_id: i64, today: &str) -> anyhow::Result<Vec<(String, String, i64, i64)>> {
+    let rows = sqlx::query_as::<_, (String, String, i64, i64)>(
+        "SELECT f.section, f.topic_id, COUNT(*),
+                SUM(CASE WHEN r.due_date IS NULL OR r.due_date <= $1 THEN 1 ELSE 0 END)
+         FROM flashcards f
+         LEFT JOIN card_reviews r ON r.card_id = f.id AND r.user_id = $2
+         GROUP BY f.section, f.topic_id
+         ORDER BY f.section, f.topic_id"
+    )
+        .bind(today)
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows)
+}
+
+pub async fn next_due_card(pool: &DbPool, user_id: i64, topic_id: &str, today: &str) -> anyhow::Result<Option<(i64, String)>> {
+    let row = sqlx::query_as::<_, (i64, String)>(
+        "SELECT f.id, f.front
+         FROM flashcards f
+         LEFT JOIN card_reviews r ON r.card_id = f.id AND r.user_id = $1
+         WHERE ($2 = '' OR f.topic_id = $3)
+           AND (r.due_date IS NULL OR r.due_date <= $4)
+         ORDER BY r.due_date IS NULL, r.due_date, f.id
+         LIMIT 1"
+    )
+        .bind(user_id)
+        .bind(topic_id)
+        .bind(topic_id)
+        .bind(today)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row)
+}
+
+pub async fn get_flashcard(pool: &DbPool, card_id: i64) -> anyhow::Result<Option<(String, String)>> {
+    let row = sqlx::query_as::<_, (String, String)>("SELECT front, back FROM flashcards WHERE id = $1")
+        .bind(card_id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row)
+}
 
+pub async fn get_card_state(pool: &DbPool, user_id: i64, card_id: i64) -> anyhow::Result<Option<(f64, i64, i64)>> {
+    let row = sqlx::query_as::<_, (f64, i64, i64)>(
+        "SELECT ease, interval_days, repetitions FROM card_reviews WHERE user_id = $1 AND card_id = $2"
+    )
+        .bind(user_id)
+        .bind(card_id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row)
+}
+
+pub async fn save_card_review(
+    pool: &DbPool,
+    user_id: i64,
+    card_id: i64,
+    state: &crate::flashcards::CardState,
+    due_date: &str,
+    knew: bool
+) -> anyhow::Result<()> {
+    let mut tx = pool.begin().await?;
     sqlx::query(
-        "INSERT INTO submissions (
-            user_id, type, section, topic_id, topic_title, content_type,
-            content_summary, photo_file_id, message_id, date, ts
-        ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+        "INSERT INTO card_reviews (user_id, card_id, ease, interval_days, repetitions, due_date)
+         VALUES ($1, $2, $3, $4, $5, $6)
+         ON CONFLICT (user_id, card_id) DO UPDATE SET
+            ease = excluded.ease,
+            interval_days = excluded.interval_days,
+            repetitions = excluded.repetitions,
+            due_date = excluded.due_date"
     )
         .bind(user_id)
-        .bind(type_str)
+        .bind(card_id)
+        .bind(state.ease)
+        .bind(state.interval_days)
+        .bind(state.repetitions)
+        .bind(due_date)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("INSERT INTO card_review_log (user_id, card_id, knew, ts) VALUES ($1, $2, $3, $4)")
+        .bind(user_id)
+        .bind(card_id)
+        .bind(knew as i64)
+        .bind(chrono::Utc::now().to_rfc3339())
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(())
+}
+
+pub async fn create_quiz(pool: &DbPool, section: &str, topic_id: &str, questions: &[crate::quiz::QuizQuestion]) -> anyhow::Result<i64> {
+    let mut tx = pool.begin().await?;
+    let quiz_id: i64 = sqlx::query_scalar("INSERT INTO quizzes (section, topic_id, ts) VALUES ($1, $2, $3) RETURNING id")
         .bind(section)
         .bind(topic_id)
-        .bind(topic_title)
-        .bind(content_type)
-        .bind(content_summary)
-        .bind(photo_file_id)
-        .bind(message_id)
-        .bind(date)
-        .bind(ts)
-        .execute(pool)
+        .bind(chrono::Utc::now().to_rfc3339())
+        .fetch_one(&mut *tx)
+        .await?;
+    for (i, q) in questions.iter().enumerate() {
+        sqlx::query(
+            "INSERT INTO quiz_questions (quiz_id, position, question, options, correct_option) VALUES ($1, $2, $3, $4, $5)"
+        )
+            .bind(quiz_id)
+            .bind(i as i64 + 1)
+            .bind(&q.question)
+            .bind(q.options.join("\n"))
+            .bind(q.correct as i64)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(quiz_id)
+}
+
+pub async fn get_quiz(pool: &DbPool, quiz_id: i64) -> anyhow::Result<Option<(String, String)>> {
+    let row = sqlx::query_as::<_, (String, String)>("SELECT section, topic_id FROM quizzes WHERE id = $1")
+        .bind(quiz_id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row)
+}
+
+pub async fn get_quiz_questions(pool: &DbPool, quiz_id: i64) -> anyhow::Result<Vec<(i64, String, String, i64)>> {
+    let rows = sqlx::query_as::<_, (i64, String, String, i64)>(
+        "SELECT id, question, options, correct_option FROM quiz_questions WHERE quiz_id = $1 ORDER BY position"
+    )
+        .bind(quiz_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows)
+}
+
+pub async fn record_quiz_poll(pool: &DbPool, poll_id: &str, question_id: i64, user_id: i64) -> anyhow::Result<()> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("INSERT INTO quiz_polls (poll_id, question_id, user_id, sent_ts) VALUES ($1, $2, $3, $4) ON CONFLICT DO NOTHING")
+        .bind(poll_id)
+        .bind(question_id)
+        .bind(user_id)
+        .bind(chrono::Utc::now().to_rfc3339())
+        .execute(&mut *tx)
         .await?;
 
+    let early = sqlx::query_as::<_, (i64, String)>(
+        "SELECT option_id, answered_ts FROM quiz_early_answers WHERE poll_id = $1 AND user_id = $2"
+    )
+        .bind(poll_id)
+        .bind(user_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+    if let Some((option_id, answered_ts)) = early {
+        set_quiz_answer(&mut tx, poll_id, user_id, option_id, &answered_ts).await?;
+        sqlx::query("DELETE FROM quiz_early_answers WHERE poll_id = $1").bind(poll_id).execute(&mut *tx).await?;
+    }
+    tx.commit().await?;
     Ok(())
 }
 
-pub async fn is_waiting_for_reason(pool: &DbPool, user_id: i64) -> anyhow::Result<bool> {
-    let result = sqlx::query("SELECT 1 FROM miss_reasons WHERE user_id = ? AND reason = ''")
+async fn set_quiz_answer(conn: &mut sqlx::AnyConnection, poll_id: &str, user_id: i64, option_id: i64, answered_ts: &str) -> anyhow::Result<bool> {
+    let res = sqlx::query(
+        "UPDATE quiz_polls
+         SET option_id = $1,
+             is_correct = (SELECT CASE WHEN correct_option = $2 THEN 1 ELSE 0 END FROM quiz_questions WHERE id = quiz_polls.question_id),
+             answered_ts = $3
+         WHERE poll_id = $4 AND user_id = $5 AND option_id IS NULL"
+    )
+        .bind(option_id)
+        .bind(option_id)
+        .bind(answered_ts)
+        .bind(poll_id)
+        .bind(user_id)
+        .execute(conn)
+        .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+pub async fn answer_quiz_poll(pool: &DbPool, poll_id: &str, user_id: i64, option_id: i64) -> anyhow::Result<bool> {
+    let now = chrono::Utc::now().to_rfc3339();
+    let mut tx = pool.begin().await?;
+    if set_quiz_answer(&mut tx, poll_id, user_id, option_id, &now).await? {
+        tx.commit().await?;
+        return Ok(true);
+    }
+
+    // Telegram may deliver the answer before deliver_quiz has stored the poll it just sent.
+    let known: Option<i64> = sqlx::query_scalar("SELECT 1 FROM quiz_polls WHERE poll_id = $1")
+        .bind(poll_id)
+        .fetch_optional(&mut *tx)
+        .await?;
+    if known.is_some() {
+        return Ok(false);
+    }
+    sqlx::query("INSERT INTO quiz_early_answers (poll_id, user_id, option_id, answered_ts) VALUES ($1, $2, $3, $4) ON CONFLICT DO NOTHING")
+        .bind(poll_id)
+        .bind(user_id)
+        .bind(option_id)
+        .bind(&now)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(true)
+}
+
+pub async fn replace_code_tests(pool: &DbPool, section: &str, topic_id: &str, tests: &[(String, String)]) -> anyhow::Result<()> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM code_tests WHERE section = $1 AND topic_id = $2")
+        .bind(section)
+        .bind(topic_id)
+        .execute(&mut *tx)
+        .await?;
+    for (input, expected) in tests {
+        sqlx::query("INSERT INTO code_tests (section, topic_id, input, expected) VALUES ($1, $2, $3, $4)")
+            .bind(section)
+            .bind(topic_id)
+            .bind(input)
+            .bind(expected)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+pub async fn get_code_tests(pool: &DbPool, section: &str, topic_id: &str) -> anyhow::Result<Vec<(String, String)>> {
+    let rows = sqlx::query_as::<_, (String, String)>(
+        "SELECT input, expected FROM code_tests WHERE section = $1 AND topic_id = $2 ORDER BY id"
+    )
+        .bind(section)
+        .bind(topic_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows)
+}
+
+pub async fn variant_task_ids(pool: &DbPool, variant: &str) -> anyhow::Result<Vec<(i64, i64)>> {
+    let rows = sqlx::query_as::<_, (i64, i64)>(
+        "SELECT ege_number, id FROM ege_tasks WHERE variant = $1 ORDER BY ege_number"
+    )
+        .bind(variant)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows)
+}
+
+pub async fn publish_mock_variant(pool: &DbPool, title: &str, tasks: &[(i64, i64)]) -> anyhow::Result<i64> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query("INSERT INTO mock_variants (title, ts) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+        .bind(title)
+        .bind(chrono::Utc::now().to_rfc3339())
+        .execute(&mut *tx)
+        .await?;
+    let variant_id: i64 = sqlx::query_scalar("SELECT id FROM mock_variants WHERE title = $1")
+        .bind(title)
+        .fetch_one(&mut *tx)
+        .await?;
+
+    sqlx::query("DELETE FROM mock_variant_tasks WHERE variant_id = $1")
+        .bind(variant_id)
+        .execute(&mut *tx)
+        .await?;
+    for (ege_number, task_id) in tasks {
+        sqlx::query("INSERT INTO mock_variant_tasks (variant_id, ege_number, task_id) VALUES ($1, $2, $3)")
+            .bind(variant_id)
+            .bind(ege_number)
+            .bind(task_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+    Ok(variant_id)
+}
+
+pub async fn list_open_mock_variants(pool: &DbPool, user_id: i64) -> anyhow::Result<Vec<(i64, String)>> {
+    let rows = sqlx::query_as::<_, (i64, String)>(
+        "SELECT id, title FROM mock_variants
+         WHERE id NOT IN (SELECT variant_id FROM mock_attempts WHERE user_id = $1)
+         ORDER BY id DESC"
+    )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows)
+}
+
+pub async fn get_active_mock_attempt(pool: &DbPool, user_id: i64) -> anyhow::Result<Option<i64>> {
+    let id: Option<i64> = sqlx::query_scalar(
+        "SELECT id FROM mock_attempts WHERE user_id = $1 AND finished_ts IS NULL ORDER BY id DESC LIMIT 1"
+    )
         .bind(user_id)
         .fetch_optional(pool)
         .await?;
-    Ok(result.is_some())
+    Ok(id)
 }
 
-pub async fn save_miss_reason(pool: &DbPool, user_id: i64, reason: &str) -> anyhow::Result<()> {
-    sqlx::query("UPDATE miss_reasons SET reason = ? WHERE user_id = ? AND reason = ''")
-        .bind(reason)
+pub async fn start_mock_attempt(pool: &DbPool, user_id: i64, variant_id: i64, duration_min: i64) -> anyhow::Result<i64> {
+    let now = chrono::Utc::now();
+    let res = sqlx::query(
+        "INSERT INTO mock_attempts (user_id, variant_id, started_ts, deadline_ts, last_notice_min)
+         VALUES ($1, $2, $3, $4, $5)
+         RETURNING id"
+    )
         .bind(user_id)
+        .bind(variant_id)
+        .bind(now.to_rfc3339())
+        .bind((now + chrono::Duration::minutes(duration_min)).to_rfc3339())
+        .bind(duration_min)
+        .fetch_one(pool)
+        .await?;
+    Ok(res.get(0))
+}
+
+pub async fn get_mock_attempt(pool: &DbPool, attempt_id: i64) -> anyhow::Result<Option<(i64, i64, String, bool)>> {
+    let row = sqlx::query_as::<_, (i64, i64, String, i64)>(
+        "SELECT user_id, variant_id, deadline_ts, CASE WHEN finished_ts IS NULL THEN 0 ELSE 1 END
+         FROM mock_attempts WHERE id = $1"
+    )
+        .bind(attempt_id)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row.map(|(user_id, variant_id, deadline, finished)| (user_id, variant_id, deadline, finished != 0)))
+}
+
+pub async fn get_mock_task(pool: &DbPool, variant_id: i64, ege_number: i64) -> anyhow::Result<Option<(i64, String, String, String, String)>> {
+    let row = sqlx::query_as::<_, (i64, String, String, String, String)>(
+        "SELECT t.id, COALESCE(t.statement, ''), t.file_id, t.file_kind, t.attachments
+         FROM mock_variant_tasks vt JOIN ege_tasks t ON t.id = vt.task_id
+         WHERE vt.variant_id = $1 AND vt.ege_number = $2"
+    )
+        .bind(variant_id)
+        .bind(ege_number)
+        .fetch_optional(pool)
+        .await?;
+    Ok(row)
+}
+
+pub async fn save_mock_answer(pool: &DbPool, attempt_id: i64, ege_number: i64, answer: &str) -> anyhow::Result<()> {
+    sqlx::query("INSERT INTO mock_answers (attempt_id, ege_number, answer) VALUES ($1, $2, $3)
+         ON CONFLICT (attempt_id, ege_number) DO UPDATE SET answer = excluded.answer, points = NULL")
+        .bind(attempt_id)
+        .bind(ege_number)
+        .bind(answer)
         .execute(pool)
         .await?;
     Ok(())
 }
 
-pub async fn delete_user_fully(pool: &DbPool, conspects_dir: &str, identifier: &str) -> anyhow::Result<()> {
-    let user_opt = if let Ok(id) = identifier.parse::<i64>() {
-        sqlx::query("SELECT id FROM users WHERE id = ?").bind(id).fetch_optional(pool).await?
-    } else {
-        sqlx::query("SELECT id FROM users WHERE username = ?").bind(identifier).fetch_optional(pool).await?
-    };
+pub async fn get_mock_answered(pool: &DbPool, attempt_id: i64) -> anyhow::Result<Vec<i64>> {
+    let rows: Vec<i64> = sqlx::query_scalar("SELECT ege_number FROM mock_answers WHERE attempt_id = $1 ORDER BY ege_number")
+        .bind(attempt_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows)
+}
 
-    let user_id = match user_opt {
-        Some(row) => row.get::<i64, _>("id"),
-        None => return Err(anyhow::anyhow!("User not found")),
-    };
+pub async fn mock_grading_rows(pool: &DbPool, attempt_id: i64) -> anyhow::Result<Vec<(i64, String, String, Option<String>)>> {
+    let rows = sqlx::query_as::<_, (i64, String, String, Option<String>)>(
+        "SELECT vt.ege_number, t.answer, t.answer_kind, a.answer
+         FROM mock_attempts m
+         JOIN mock_variant_tasks vt ON vt.variant_id = m.variant_id
+         JOIN ege_tasks t ON t.id = vt.task_id
+         LEFT JOIN mock_answers a ON a.attempt_id = m.id AND a.ege_number = vt.ege_number
+         WHERE m.id = $1
+         ORDER BY vt.ege_number"
+    )
+        .bind(attempt_id)
+        .fetch_all(pool)
+        .await?;
+    Ok(rows)
+}
 
-    sqlx::query("DELETE FROM submissions WHERE user_id = ?").bind(user_id).execute(pool).await?;
-    sqlx::query("DELETE FROM miss_reasons WHERE user_id = ?").bind(user_id).execute(pool).await?;
-    sqlx::query("DELETE FROM users WHERE id = ?").bind(user_id).execute(pool).await?;
+pub async fn finish_mock_attempt(pool: &DbPool, attempt_id: i64, points: &[(i64, i64)], primary: i64, test: i64) -> anyhow::Result<bool> {
+    let mut tx = pool.begin().await?;
+    let claimed = sqlx::query(
+        "UPDATE mock_attempts SET finished_ts = $1, primary_score = $2, test_score = $3
+         WHERE id = $4 AND finished_ts IS NULL"
+    )
+        .bind(chrono::Utc::now().to_rfc3339())
+        .bind(primary)
+        .bind(test)
+        .bind(attempt_id)
+        .execute(&mut *tx)
+        .await?;
+    if claimed.rows_affected() == 0 {
+        return Ok(false);
+    }
 
-    let user_path = format!("{}/{}", conspects_dir, user_id);
-    if Path::new(&user_path).exists() {
-        tokio::fs::remove_dir_all(user_path).await?;
+    for (ege_number, pts) in points {
+        sqlx::query(
+            "INSERT INTO mock_answers (attempt_id, ege_number, answer, points) VALUES ($1, $2, '', $3)
+             ON CONFLICT (attempt_id, ege_number) DO UPDATE SET points = excluded.points"
+        )
+            .bind(attempt_id)
+            .bind(ege_number)
+            .bind(pts)
+            .execute(&mut *tx)
+            .await?;
     }
+    tx.commit().await?;
+    Ok(true)
+}
+
+pub async fn set_teacher_points(pool: &DbPool, attempt_id: i64, ege_number: i64, points: i64) -> anyhow::Result<bool> {
+    let updated = sqlx::query(
+        "UPDATE mock_answers SET teacher_points = $1
+         WHERE attempt_id = $2 AND ege_number = $3
+           AND attempt_id IN (SELECT id FROM mock_attempts WHERE finished_ts IS NOT NULL)"
+    )
+        .bind(points)
+        .bind(attempt_id)
+        .bind(ege_number)
+        .execute(pool)
+        .await?;
+    Ok(updated.rows_affected() > 0)
+}
+
+pub async fn mock_primary_score(pool: &DbPool, attempt_id: i64) -> anyhow::Result<i64> {
+    let primary: i64 = sqlx::query_scalar(
+        "SELECT CAST(COALESCE(SUM(COALESCE(teacher_points, points, 0)), 0) AS BIGINT) FROM mock_answers WHERE attempt_id = $1"
+    )
+        .bind(attempt_id)
+        .fetch_one(pool)
+        .await?;
+    Ok(primary)
+}
+
+pub async fn set_mock_scores(pool: &DbPool, attempt_id: i64, primary: i64, test: i64) -> anyhow::Result<()> {
+    sqlx::query("UPDATE mock_attempts SET primary_score = $1, test_score = $2 WHERE id = $3")
+        .bind(primary)
+        .bind(test)
+        .bind(attempt_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn set_user_group(pool: &DbPool, identifier: &str, group: &str) -> anyhow::Result<bool> {
+    let updated = if let Ok(id) = identifier.parse::<i64>() {
+        sqlx::query("UPDATE users SET group_name = $1 WHERE id = $2").bind(group).bind(id).execute(pool).await?
+    } else {
+        sqlx::query("UPDATE users SET group_name = $1 WHERE username = $2").bind(group).bind(identifier).execute(pool).await?
+    };
+    Ok(updated.rows_affected() > 0)
+}
+
+pub async fn list_running_mock_attempts(pool: &DbPool) -> anyhow::Result<Vec<(i64, i64, String, i64)>> {
+    let rows = sqlx::query_as::<_, (i64, i64, String, i64)>(
+        "SELECT id, user_id, deadline_ts, COALESCE(last_notice_min, 0) FROM mock_attempts WHERE finished_ts IS NULL"
+    )
+        .fetch_all(pool)
+        .await?;
+    Ok(rows)
+}
+
+pub async fn set_mock_notice(pool: &DbPool, attempt_id: i64, minutes: i64) -> anyhow::Result<()> {
+    sqlx::query("UPDATE mock_attempts SET last_notice_min = $1 WHERE id = $2")
+        .bind(minutes)
+        .bind(attempt_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
 
+pub async fn delete_user_rows(pool: &DbPool, user_id: i64) -> anyhow::Result<()> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM conspect_likes WHERE user_id = $1 OR shared_id IN (SELECT id FROM shared_conspects WHERE author_id = $2)")
+        .bind(user_id).bind(user_id).execute(&mut *tx).await?;
+    sqlx::query("DELETE FROM shared_conspects WHERE author_id = $1").bind(user_id).execute(&mut *tx).await?;
+    sqlx::query("DELETE FROM task_attempts WHERE user_id = $1").bind(user_id).execute(&mut *tx).await?;
+    sqlx::query("DELETE FROM daily_tasks WHERE user_id = $1").bind(user_id).execute(&mut *tx).await?;
+    sqlx::query("DELETE FROM card_reviews WHERE user_id = $1").bind(user_id).execute(&mut *tx).await?;
+    sqlx::query("DELETE FROM card_review_log WHERE user_id = $1").bind(user_id).execute(&mut *tx).await?;
+    sqlx::query("DELETE FROM quiz_polls WHERE user_id = $1").bind(user_id).execute(&mut *tx).await?;
+    sqlx::query("DELETE FROM quiz_early_answers WHERE user_id = $1").bind(user_id).execute(&mut *tx).await?;
+    sqlx::query("DELETE FROM mock_answers WHERE attempt_id IN (SELECT id FROM mock_attempts WHERE user_id = $1)")
+        .bind(user_id).execute(&mut *tx).await?;
+    sqlx::query("DELETE FROM mock_attempts WHERE user_id = $1").bind(user_id).execute(&mut *tx).await?;
+    sqlx::query("DELETE FROM submissions WHERE user_id = $1").bind(user_id).execute(&mut *tx).await?;
+    sqlx::query("DELETE FROM miss_reasons WHERE user_id = $1").bind(user_id).execute(&mut *tx).await?;
+    sqlx::query("DELETE FROM users WHERE id = $1").bind(user_id).execute(&mut *tx).await?;
+    tx.commit().await?;
     Ok(())
 }
 
 pub async fn reset_database(pool: &DbPool) -> anyhow::Result<()> {
+    sqlx::query("DELETE FROM conspect_likes").execute(pool).await?;
+    sqlx::query("DELETE FROM shared_conspects").execute(pool).await?;
+    sqlx::query("DELETE FROM task_attempts").execute(pool).await?;
+    sqlx::query("DELETE FROM daily_tasks").execute(pool).await?;
+    sqlx::query("DELETE FROM card_reviews").execute(pool).await?;
+    sqlx::query("DELETE FROM card_review_log").execute(pool).await?;
+    sqlx::query("DELETE FROM quiz_polls").execute(pool).await?;
+    sqlx::query("DELETE FROM quiz_early_answers").execute(pool).await?;
+    sqlx::query("DELETE FROM mock_answers").execute(pool).await?;
+    sqlx::query("DELETE FROM mock_attempts").execute(pool).await?;
     sqlx::query("DELETE FROM submissions").execute(pool).await?;
     sqlx::query("DELETE FROM miss_reasons").execute(pool).await?;
     sqlx::query("DELETE FROM users").execute(pool).await?;
     Ok(())
+}
+
+const TABLES: &[&str] = &[
+    "users", "submissions", "shared_conspects", "conspect_likes", "miss_reasons",
+    "ege_tasks", "task_attempts", "daily_tasks",
+    "flashcards", "card_reviews", "card_review_log",
+    "quizzes", "quiz_questions", "quiz_polls", "quiz_early_answers", "code_tests",
+    "mock_variants", "mock_variant_tasks", "mock_attempts", "mock_answers",
+    "webhook_deliveries",
+];
+
+const FOREIGN_KEYS: &[(&str, &str, &str)] = &[
+    ("submissions", "user_id", "users"),
+    ("shared_conspects", "submission_id", "submissions"),
+    ("task_attempts", "user_id", "users"),
+    ("task_attempts", "task_id", "ege_tasks"),
+    ("daily_tasks", "user_id", "users"),
+    ("daily_tasks", "task_id", "ege_tasks"),
+    ("card_reviews", "user_id", "users"),
+    ("card_reviews", "card_id", "flashcards"),
+    ("card_review_log", "user_id", "users"),
+    ("card_review_log", "card_id", "flashcards"),
+    ("quiz_questions", "quiz_id", "quizzes"),
+    ("quiz_polls", "question_id", "quiz_questions"),
+    ("quiz_polls", "user_id", "users"),
+    ("mock_variant_tasks", "variant_id", "mock_variants"),
+    ("mock_variant_tasks", "task_id", "ege_tasks"),
+    ("mock_attempts", "user_id", "users"),
+    ("mock_attempts", "variant_id", "mock_variants"),
+    ("mock_answers", "attempt_id", "mock_attempts"),
+];
+
+pub struct CopiedTable {
+    pub table: &'static str,
+    pub copied: u64,
+    pub orphaned: u64,
+}
+
+pub async fn copy_database(source: &DbPool, target: &DbPool) -> anyhow::Result<Vec<CopiedTable>> {
+    let mut tx = target.begin().await?;
+    let mut copied = Vec::new();
+    let mut ids: HashMap<&str, HashSet<i64>> = HashMap::new();
+
+    for table in TABLES {
+        let existing: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {}", table)).fetch_one(&mut *tx).await?;
+        if existing > 0 {
+            return Err(anyhow::anyhow!("Table {} in the target database is not empty", table));
+        }
+
+        let select = format!("SELECT * FROM {}", table);
+        let mut rows = sqlx::query(&select).fetch(source);
+        let mut count = 0;
+        let mut orphaned = 0;
+        let mut has_id = false;
+        let references: Vec<_> = FOREIGN_KEYS.iter().filter(|(t, _, _)| t == table).collect();
+        while let Some(row) = rows.try_next().await? {
+            let orphan = references.iter().any(|(_, column, parent)| {
+                matches!(row.try_get::<Option<i64>, _>(*column), Ok(Some(id)) if !ids.get(parent).is_some_and(|p| p.contains(&id)))
+            });
+            if orphan {
+                orphaned += 1;
+                continue;
+            }
+
+            let columns: Vec<&str> = row.columns().iter().map(|c| c.name()).collect();
+            let placeholders: Vec<String> = (1..=columns.len()).map(|i| format!("${}", i)).collect();
+            let sql = format!("INSERT INTO {} ({}) VALUES ({})", table, columns.join(", "), placeholders.join(", "));
+
+            let mut insert = sqlx::query(&sql);
+            for i in 0..columns.len() {
+                insert = if let Ok(value) = row.try_get::<Option<i64>, _>(i) {
+                    insert.bind(value)
+                } else if let Ok(value) = row.try_get::<Option<f64>, _>(i) {
+                    insert.bind(value)
+                } else {
+                    insert.bind(row.try_get::<Option<String>, _>(i)?)
+                };
+            }
+            insert.persistent(false).execute(&mut *tx).await?;
+
+            has_id = columns.contains(&"id");
+            if has_id {
+                ids.entry(*table).or_default().insert(row.try_get("id")?);
+            }
+            count += 1;
+        }
+
+        if has_id && Backend::of(target) == Backend::Postgres {
+            sqlx::query(&format!("SELECT setval(pg_get_serial_sequence('{0}', 'id'), MAX(id)) FROM {0}", table))
+                .execute(&mut *tx)
+                .await?;
+        }
+        copied.push(CopiedTable { table, copied: count, orphaned });
+    }
+
+    tx.commit().await?;
+    Ok(copied)
+}
+
+pub async fn add_webhook_delivery(pool: &DbPool, event: &str, url: &str, payload: &str) -> anyhow::Result<i64> {
+    let now = chrono::Utc::now().to_rfc3339();
+    let res = sqlx::query(
+        "INSERT INTO webhook_deliveries (event, url, payload, created_ts, updated_ts) VALUES ($1, $2, $3, $4, $5)
+         RETURNING id"
+    )
+        .bind(event)
+        .bind(url)
+        .bind(payload)
+        .bind(&now)
+        .bind(&now)
+        .fetch_one(pool)
+        .await?;
+    Ok(res.get(0))
+}
+
+pub async fn record_webhook_attempt(
+    pool: &DbPool,
+    delivery_id: i64,
+    status: &str,
+    status_code: Option<i64>,
+    error: Option<&str>
+) -> anyhow::Result<()> {
+    sqlx::query(
+        "UPDATE webhook_deliveries
+         SET status = $1, attempts = attempts + 1, last_status_code = $2, last_error = $3, updated_ts = $4
+         WHERE id = $5"
+    )
+        .bind(status)
+        .bind(status_code)
+        .bind(error)
+        .bind(chrono::Utc::now().to_rfc3339())
+        .bind(delivery_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+pub async fn pending_webhook_deliveries(pool: &DbPool) -> anyhow::Result<Vec<(i64, String, String, String, i64)>> {
+    let rows = sqlx::query_as::<_, (i64, String, String, String, i64)>(
+        "SELECT id, event, url, payload, attempts FROM webhook_deliveries WHERE status = 'pending' ORDER BY id"
+    )
+        .fetch_all(pool)
+        .await?;
+    Ok(rows)
 }
\ No newline at end of file
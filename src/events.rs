@@ -0,0 +1,294 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use log::{error, warn};
+use serde_json::{json, Value};
+use sha2::Sha256;
+
+use crate::api;
+use crate::db::{self, DbPool};
+use crate::repo::SubmissionRepo;
+
+pub const SUBMISSION_CREATED: &str = "submission.created";
+pub const ALBUM_FLUSHED: &str = "album.flushed";
+pub const REVIEW_DECIDED: &str = "review.decided";
+pub const USER_REGISTERED: &str = "user.registered";
+pub const USER_BLOCKED: &str = "user.blocked";
+pub const MISS_REASON_SAVED: &str = "miss_reason.saved";
+
+const ALL_EVENTS: [&str; 6] = [SUBMISSION_CREATED, ALBUM_FLUSHED, REVIEW_DECIDED, USER_REGISTERED, USER_BLOCKED, MISS_REASON_SAVED];
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const RETRY_DELAYS_SECS: [u64; 4] = [5, 30, 120, 600];
+
+pub struct EventConfig {
+    pub urls: Vec<url::Url>,
+    pub secret: String,
+    pub events: Vec<String>,
+    pub retry_delays: Vec<Duration>,
+}
+
+impl EventConfig {
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let Ok(urls) = std::env::var("EVENT_WEBHOOK_URLS") else {
+            return Ok(None);
+        };
+        let urls = urls.split(',')
+            .map(str::trim)
+            .filter(|u| !u.is_empty())
+            .map(url::Url::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+        if urls.is_empty() {
+            return Ok(None);
+        }
+        let secret = std::env::var("EVENT_WEBHOOK_SECRET")
+            .map_err(|_| anyhow::anyhow!("EVENT_WEBHOOK_SECRET required when EVENT_WEBHOOK_URLS is set"))?;
+
+        let events: Vec<String> = match std::env::var("EVENT_WEBHOOK_EVENTS") {
+            Ok(list) => list.split(',').map(str::trim).filter(|e| !e.is_empty()).map(String::from).collect(),
+            Err(_) => ALL_EVENTS.iter().map(|e| e.to_string()).collect(),
+        };
+        if let Some(unknown) = events.iter().find(|e| !ALL_EVENTS.contains(&e.as_str())) {
+            anyhow::bail!("Unknown event «{}» in EVENT_WEBHOOK_EVENTS, expected one of {}", unknown, ALL_EVENTS.join(", "));
+        }
+
+        let retry_delays = RETRY_DELAYS_SECS.iter().map(|s| Duration::from_secs(*s)).collect();
+        Ok(Some(EventConfig { urls, secret, events, retry_delays }))
+    }
+}
+
+pub struct Events {
+    pool: DbPool,
+    submissions: Arc<dyn SubmissionRepo>,
+    config: Option<EventConfig>,
+    client: reqwest::Client,
+}
+
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+impl Events {
+    pub fn new(pool: DbPool, submissions: Arc<dyn SubmissionRepo>, config: Option<EventConfig>) -> anyhow::Result<Self> {
+        let client = reqwest::Client::builder().timeout(REQUEST_TIMEOUT).build()?;
+        Ok(Events { pool, submissions, config, client })
+    }
+
+    pub async fn emit(self: &Arc<Self>, event: &'static str, data: Value) {
+        let Some(config) = &self.config else { return };
+        if !config.events.iter().any(|e| e == event) {
+            return;
+        }
+
+        let payload = json!({ "event": event, "ts": Utc::now().to_rfc3339(), "data": data }).to_string();
+        for url in config.urls.iter() {
+            match db::add_webhook_delivery(&self.pool, event, url.as_str(), &payload).await {
+                Ok(id) => {
+                    tokio::spawn(self.clone().deliver(id, event.to_string(), url.to_string(), payload.clone(), 0));
+                }
+                Err(e) => error!("Cannot log webhook delivery of {} to {}: {:?}", event, url, e),
+            }
+        }
+    }
+
+    pub async fn resume_pending(self: &Arc<Self>) -> anyhow::Result<usize> {
+        if self.config.is_none() {
+            return Ok(0);
+        }
+        let pending = db::pending_webhook_deliveries(&self.pool).await?;
+        let count = pending.len();
+        for (id, event, url, payload, attempts) in pending {
+            tokio::spawn(self.clone().deliver(id, event, url, payload, attempts as usize));
+        }
+        Ok(count)
+    }
+
+    async fn post(&self, secret: &str, delivery_id: i64, event: &str, url: &str, payload: &str) -> Result<u16, (Option<u16>, String)> {
+        let response = self.client.post(url)
+            .header("content-type", "application/json")
+            .header("x-bot-event", event)
+            .header("x-bot-delivery", delivery_id.to_string())
+            .header("x-bot-signature", sign(secret, payload.as_bytes()))
+            .body(payload.to_string())
+            .send()
+            .await
+            .map_err(|e| (None, e.to_string()))?;
+        let code = response.status();
+        if code.is_success() {
+            Ok(code.as_u16())
+        } else {
+            Err((Some(code.as_u16()), format!("HTTP {}", code)))
+        }
+    }
+
+    async fn deliver(self: Arc<Self>, delivery_id: i64, event: String, url: String, payload: String, done_attempts: usize) {
+        let Some(config) = &self.config else { return };
+        let max_attempts = config.retry_delays.len() + 1;
+
+        for attempt in done_attempts..max_attempts {
+            if attempt > 0 {
+                tokio::time::sleep(config.retry_delays[attempt - 1]).await;
+            }
+            let (status, code, err) = match self.post(&config.secret, delivery_id, &event, &url, &payload).await {
+                Ok(code) => ("delivered", Some(code), None),
+                Err((code, e)) if attempt + 1 == max_attempts => ("failed", code, Some(e)),
+                Err((code, e)) => ("pending", code, Some(e)),
+            };
+            if let Some(e) = &err {
+                warn!("Webhook {} #{} to {} failed (attempt {}/{}): {}", event, delivery_id, url, attempt + 1, max_attempts, e);
+            }
+            if let Err(e) = db::record_webhook_attempt(&self.pool, delivery_id, status, code.map(i64::from), err.as_deref()).await {
+                error!("Cannot update webhook delivery #{}: {:?}", delivery_id, e);
+            }
+            if status != "pending" {
+                return;
+            }
+        }
+    }
+
+    pub async fn submission_created(self: &Arc<Self>, submission_id: i64) {
+        match self.submissions.get(submission_id).await {
+            Ok(Some(submission)) => self.emit(SUBMISSION_CREATED, api::submission_json(&submission)).await,
+            Ok(None) => {}
+            Err(e) => error!("Cannot load submission {} for webhook: {:?}", submission_id, e),
+        }
+    }
+
+    pub async fn review_decided(self: &Arc<Self>, shared_id: i64) {
+        match db::get_shared_review(&self.pool, shared_id).await {
+            Ok(Some((submission_id, user_id, topic_title, decision))) => {
+                self.emit(REVIEW_DECIDED, json!({
+                    "shared_id": shared_id,
+                    "submission_id": submission_id,
+                    "user_id": user_id,
+                    "topic_title": topic_title,
+                    "decision": decision,
+                })).await;
+            }
+            Ok(None) => {}
+            Err(e) => error!("Cannot load review {} for webhook: {:?}", shared_id, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    use sqlx::Row;
+    use warp::http::StatusCode;
+    use warp::Filter;
+
+    use super::*;
+    use crate::repo::Repos;
+
+    const SECRET: &str = "test-secret";
+
+    type Received = Arc<Mutex<Vec<(String, String, String)>>>;
+
+    async fn flaky_receiver() -> (std::net::SocketAddr, Received) {
+        let received: Received = Arc::default();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let log = received.clone();
+        let route = warp::post()
+            .and(warp::path("hook"))
+            .and(warp::header::<String>("x-bot-event"))
+            .and(warp::header::<String>("x-bot-signature"))
+            .and(warp::body::bytes())
+            .map(move |event: String, signature: String, body: warp::hyper::body::Bytes| {
+                log.lock().unwrap().push((event, signature, String::from_utf8(body.to_vec()).unwrap()));
+                let status = if calls.fetch_add(1, Ordering::SeqCst) == 0 { StatusCode::INTERNAL_SERVER_ERROR } else { StatusCode::OK };
+                warp::reply::with_status("", status)
+            });
+        let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        (addr, received)
+    }
+
+    #[test]
+    fn signature_is_hex_hmac_sha256_of_the_body() {
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        assert_ne!(sign(SECRET, b"{}"), sign("other", b"{}"));
+    }
+
+    #[test]
+    fn config_from_env_uses_the_retry_schedule() {
+        for var in ["EVENT_WEBHOOK_URLS", "EVENT_WEBHOOK_SECRET", "EVENT_WEBHOOK_EVENTS"] {
+            std::env::remove_var(var);
+        }
+        assert!(EventConfig::from_env().unwrap().is_none());
+
+        std::env::set_var("EVENT_WEBHOOK_URLS", "https://a.example/hook, ,https://b.example/hook");
+        assert!(EventConfig::from_env().is_err());
+
+        std::env::set_var("EVENT_WEBHOOK_SECRET", SECRET);
+        let config = EventConfig::from_env().unwrap().unwrap();
+        assert_eq!(config.urls.len(), 2);
+        assert_eq!(config.events.len(), ALL_EVENTS.len());
+        let delays: Vec<u64> = config.retry_delays.iter().map(Duration::as_secs).collect();
+        assert_eq!(delays, [5, 30, 120, 600]);
+
+        std::env::set_var("EVENT_WEBHOOK_EVENTS", "user.blocked,user.unknown");
+        assert!(EventConfig::from_env().is_err());
+        std::env::set_var("EVENT_WEBHOOK_EVENTS", "user.blocked");
+        assert_eq!(EventConfig::from_env().unwrap().unwrap().events, [USER_BLOCKED]);
+
+        for var in ["EVENT_WEBHOOK_URLS", "EVENT_WEBHOOK_SECRET", "EVENT_WEBHOOK_EVENTS"] {
+            std::env::remove_var(var);
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_delivery_is_retried_and_signed() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = db::init_db(&format!("sqlite:{}", dir.path().join("bot.db").display())).await.unwrap();
+        let (addr, received) = flaky_receiver().await;
+
+        let config = EventConfig {
+            urls: vec![format!("http://{}/hook", addr).parse().unwrap()],
+            secret: SECRET.to_string(),
+            events: vec![USER_REGISTERED.to_string()],
+            retry_delays: vec![Duration::from_millis(50), Duration::from_millis(50)],
+        };
+        let events = Arc::new(Events::new(pool.clone(), Repos::in_memory().submissions, Some(config)).unwrap());
+        events.emit(USER_REGISTERED, json!({ "user_id": 7 })).await;
+        events.emit(USER_BLOCKED, json!({ "user_id": 7 })).await;
+
+        let mut row = None;
+        for _ in 0..100 {
+            let r = sqlx::query("SELECT status, attempts, last_status_code, last_error FROM webhook_deliveries")
+                .fetch_one(&pool).await.unwrap();
+            if r.get::<String, _>("status") != "pending" {
+                row = Some(r);
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let row = row.expect("delivery never left the pending state");
+        assert_eq!(row.get::<String, _>("status"), "delivered");
+        assert_eq!(row.get::<i64, _>("attempts"), 2);
+        assert_eq!(row.get::<Option<i64>, _>("last_status_code"), Some(200));
+        assert_eq!(row.get::<Option<String>, _>("last_error"), None);
+
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM webhook_deliveries").fetch_one(&pool).await.unwrap();
+        assert_eq!(count, 1);
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 2);
+        for (event, signature, body) in received.iter() {
+            assert_eq!(event, USER_REGISTERED);
+            assert_eq!(signature, &sign(SECRET, body.as_bytes()));
+            assert!(signature.starts_with("sha256="));
+            let payload: Value = serde_json::from_str(body).unwrap();
+            assert_eq!(payload["data"]["user_id"], 7);
+        }
+        assert_eq!(received[0].2, received[1].2);
+    }
+}
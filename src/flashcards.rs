@@ -0,0 +1,148 @@
+use chrono::{Duration, NaiveDate};
+
+use crate::keyboards;
+
+const MIN_EASE: f64 = 1.3;
+const QUALITY_KNEW: f64 = 4.0;
+const QUALITY_FORGOT: f64 = 1.0;
+
+#[derive(Clone, Copy, Debug)]
+pub struct CardState {
+    pub ease: f64,
+    pub interval_days: i64,
+    pub repetitions: i64,
+}
+
+impl Default for CardState {
+    fn default() -> Self {
+        CardState { ease: 2.5, interval_days: 0, repetitions: 0 }
+    }
+}
+
+pub fn schedule(prev: CardState, knew: bool) -> CardState {
+    let q = if knew { QUALITY_KNEW } else { QUALITY_FORGOT };
+    let ease = (prev.ease + 0.1 - (5.0 - q) * (0.08 + (5.0 - q) * 0.02)).max(MIN_EASE);
+
+    if !knew {
+        return CardState { ease, interval_days: 1, repetitions: 0 };
+    }
+
+    let interval_days = match prev.repetitions {
+        0 => 1,
+        1 => 6,
+        _ => ((prev.interval_days as f64) * prev.ease).round() as i64,
+    };
+    CardState { ease, interval_days, repetitions: prev.repetitions + 1 }
+}
+
+pub fn due_date(today: NaiveDate, state: &CardState) -> NaiveDate {
+    today + Duration::days(state.interval_days)
+}
+
+pub struct CardSpec {
+    pub section: String,
+    pub topic_id: String,
+    pub cards: Vec<(String, String)>,
+}
+
+pub fn parse_cards(spec: &str) -> Result<CardSpec, String> {
+    let mut lines = spec.lines().map(str::trim).filter(|l| !l.is_empty());
+    let topic_id = lines.next().unwrap_or("").to_string();
+    let section = keyboards::SECTIONS.iter()
+        .find(|s| keyboards::get_topic_title(s, &topic_id).is_some())
+        .ok_or_else(|| format!("неизвестная тема «{}»", topic_id))?;
+
+    let mut cards = Vec::new();
+    for (i, line) in lines.enumerate() {
+        let Some((front, back)) = line.split_once("::") else {
+            return Err(format!("в строке {} нет разделителя «::»", i + 2));
+        };
+        let (front, back) = (front.trim(), back.trim());
+        if front.is_empty() || back.is_empty() {
+            return Err(format!("в строке {} пустой вопрос или ответ", i + 2));
+        }
+        cards.push((front.to_string(), back.to_string()));
+    }
+
+    if cards.is_empty() {
+        return Err("нет ни одной карточки".to_string());
+    }
+    Ok(CardSpec { section: section.to_string(), topic_id, cards })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+
+    fn review(mut state: CardState, answers: &[bool]) -> CardState {
+        for knew in answers {
+            state = schedule(state, *knew);
+        }
+        state
+    }
+
+    #[test]
+    fn known_cards_follow_sm2_intervals() {
+        let first = schedule(CardState::default(), true);
+        assert_eq!((first.interval_days, first.repetitions, first.ease), (1, 1, 2.5));
+
+        let third = review(CardState::default(), &[true, true, true]);
+        assert_eq!((third.interval_days, third.repetitions), (15, 3));
+
+        let next = schedule(CardState { ease: 2.0, interval_days: 10, repetitions: 3 }, true);
+        assert_eq!((next.interval_days, next.repetitions, next.ease), (20, 4, 2.0));
+
+        let today = NaiveDate::from_ymd_opt(2024, 5, 30).unwrap();
+        assert_eq!(due_date(today, &third), NaiveDate::from_ymd_opt(2024, 6, 14).unwrap());
+    }
+
+    #[test]
+    fn forgotten_cards_restart_and_lose_ease_down_to_the_floor() {
+        let forgot = review(CardState::default(), &[true, true, false]);
+        assert_eq!((forgot.interval_days, forgot.repetitions), (1, 0));
+        assert!((forgot.ease - 1.96).abs() < 1e-9);
+
+        let relearned = review(forgot, &[true, true]);
+        assert_eq!((relearned.interval_days, relearned.repetitions), (6, 2));
+
+        assert_eq!(review(CardState::default(), &[false; 5]).ease, MIN_EASE);
+    }
+
+    #[test]
+    fn card_spec_needs_a_topic_and_separated_lines() {
+        let spec = parse_cards("op3\nrange(3) :: 0, 1, 2\n\nbreak::выход из цикла").unwrap();
+        assert_eq!((spec.section.as_str(), spec.topic_id.as_str()), ("Основы Питона", "op3"));
+        assert_eq!(spec.cards[0], ("range(3)".to_string(), "0, 1, 2".to_string()));
+
+        assert_eq!(parse_cards("op99\na::b").err().unwrap(), "неизвестная тема «op99»");
+        assert_eq!(parse_cards("op3\na - b").err().unwrap(), "в строке 2 нет разделителя «::»");
+        assert_eq!(parse_cards("op3\na:: ").err().unwrap(), "в строке 2 пустой вопрос или ответ");
+        assert_eq!(parse_cards("op3").err().unwrap(), "нет ни одной карточки");
+    }
+
+    #[tokio::test]
+    async fn retention_counts_cards_of_the_same_topic_id_per_section() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = db::init_db(&format!("sqlite:{}", dir.path().join("bot.db").display())).await.unwrap();
+        let cards = |n: usize| (0..n).map(|i| (format!("q{}", i), "a".to_string())).collect::<Vec<_>>();
+        db::add_flashcards(&pool, "Основы Питона", "op1", &cards(2)).await.unwrap();
+        db::add_flashcards(&pool, "ЕГЭ 1-27", "op1", &cards(3)).await.unwrap();
+        for sql in [
+            "INSERT INTO users (id, username, first_name) VALUES (1, 'anna', 'Anna')",
+            "INSERT INTO card_reviews (user_id, card_id, ease, interval_days, repetitions, due_date) VALUES
+                (1, 1, 2.5, 6, 2, '2024-06-01'), (1, 3, 2.5, 1, 1, '2024-06-01')",
+            "INSERT INTO card_review_log (user_id, card_id, knew, ts) VALUES
+                (1, 1, 1, '2024-05-20T10:00:00Z'), (1, 1, 1, '2024-05-21T10:00:00Z'), (1, 3, 0, '2024-05-21T10:00:00Z'),
+                (1, 2, 1, '2024-03-01T10:00:00Z')",
+        ] {
+            sqlx::query(sql).execute(&pool).await.unwrap();
+        }
+
+        let rows = db::card_retention(&pool, 1, "2024-05-01").await.unwrap();
+        assert_eq!(rows, vec![
+            ("ЕГЭ 1-27".to_string(), "op1".to_string(), 0, 1, 0, 3),
+            ("Основы Питона".to_string(), "op1".to_string(), 2, 2, 1, 2),
+        ]);
+    }
+}
@@ -1,22 +1,41 @@
 use teloxide::{
+    net::Download,
     prelude::*,
-    types::{InputFile, MediaKind, MessageKind, MessageId},
+    types::{InlineKeyboardMarkup, InputFile, InputMedia, InputMediaPhoto, MediaKind, MessageKind, MessageId, PollAnswer},
 };
-use chrono::Utc;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use chrono::{NaiveDate, Utc};
 use rand::seq::SliceRandom;
 use log::{info, error};
+use serde_json::json;
 
 use crate::{
+    code_check,
+    code_preview,
+    daily,
     db,
+    events,
+    flashcards::{self, CardState},
+    import,
     keyboards::{self, main_kb, sections_kb, topics_kb, admin_kb},
-    reports,
+    metrics::Metrics,
+    mock,
+    quiz,
+    repo::{self, NewSubmission, Submission, SubmissionFilter, SubmissionStatus},
+    reports::{self, ConspectSelection},
     states::{DialogueState, SubmissionType},
+    tasks::{self, AnswerKind},
     AppState,
 };
 
 pub type MyDialogue = Dialogue<DialogueState, teloxide::dispatching::dialogue::InMemStorage<DialogueState>>;
 pub type HandlerResult = Result<(), Box<dyn std::error::Error + Send + Sync>>;
 
+const HISTORY_PAGE_SIZE: i64 = 8;
+const MAX_CODE_BYTES: u32 = 100 * 1024;
+
 fn get_praise() -> String {
     let phrases = vec![
         "Молодец, отличная работа!", "Здорово, так держать!", "Круто, ты справился!",
@@ -27,6 +46,282 @@ fn get_praise() -> String {
     phrases.choose(&mut rng).unwrap_or(&"Принято!").to_string()
 }
 
+pub fn admin_submission_kb(kind: &SubmissionType, submission_id: i64) -> InlineKeyboardMarkup {
+    match kind {
+        SubmissionType::Conspect => keyboards::admin_share_kb(submission_id),
+        SubmissionType::Dz => InlineKeyboardMarkup::default(),
+    }
+}
+
+fn file_ids_of(joined: &str) -> Vec<&str> {
+    joined.split(';').filter(|f| !f.is_empty()).collect()
+}
+
+async fn send_submission_content(bot: &Bot, chat_id: ChatId, content_type: &str, summary: String, file_id: &str) -> HandlerResult {
+    match content_type {
+        "photo" => {
+            let mut req = bot.send_photo(chat_id, InputFile::file_id(file_id));
+            if !summary.is_empty() { req = req.caption(summary); }
+            req.await?;
+        }
+        "photo_album" => {
+            let ids: Vec<&str> = file_ids_of(file_id);
+            for chunk in ids.chunks(10) {
+                bot.send_media_group(chat_id, chunk.iter().map(|f| {
+                    InputMedia::Photo(InputMediaPhoto::new(InputFile::file_id(*f)))
+                })).await?;
+            }
+        }
+        "document" => {
+            bot.send_document(chat_id, InputFile::file_id(file_id)).await?;
+        }
+        _ => {
+            if !summary.is_empty() { bot.send_message(chat_id, summary).await?; }
+        }
+    }
+    Ok(())
+}
+
+fn within_grace(ts: &str, grace_minutes: i64) -> bool {
+    chrono::DateTime::parse_from_rfc3339(ts)
+        .map(|t| Utc::now().signed_duration_since(t) <= chrono::Duration::minutes(grace_minutes))
+        .unwrap_or(false)
+}
+
+async fn send_zip_parts(bot: &Bot, chat_id: ChatId, parts: Vec<PathBuf>, base_name: &str) -> HandlerResult {
+    let total = parts.len();
+    let mut result = Ok(());
+
+    for (i, part) in parts.iter().enumerate() {
+        if result.is_err() { break; }
+        let name = if total == 1 {
+            format!("{}.zip", base_name)
+        } else {
+            format!("{}_part{}_of_{}.zip", base_name, i + 1, total)
+        };
+        result = bot.send_document(chat_id, InputFile::file(part).file_name(name)).await.map(|_| ());
+    }
+
+    for part in parts {
+        let _ = tokio::fs::remove_file(part).await;
+    }
+    result?;
+    Ok(())
+}
+
+async fn send_conspects_archive(bot: &Bot, chat_id: ChatId, state: &AppState, uid: i64, selection: ConspectSelection) -> HandlerResult {
+    bot.send_message(chat_id, "Архивирую твои конспекты, подожди пару секунд...").await?;
+
+    match reports::archive_user_conspects(&state.conspects_dir, uid, &selection).await {
+        Ok(parts) if !parts.is_empty() => {
+            send_zip_parts(bot, chat_id, parts, "my_conspects").await?;
+        }
+        Ok(_) => {
+            bot.send_message(chat_id, "По этому запросу сохранённых конспектов нет.").await?;
+        }
+        Err(e) => {
+            error!("Archive error: {:?}", e);
+            bot.send_message(chat_id, "Не удалось собрать архив, попробуй позже.").await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn send_task_files(bot: &Bot, chat_id: ChatId, state: &AppState, file_id: String, file_kind: &str, attachments: &str) -> HandlerResult {
+    match file_kind {
+        "photo" => { bot.send_photo(chat_id, InputFile::file_id(file_id)).await?; }
+        "document" => { bot.send_document(chat_id, InputFile::file_id(file_id)).await?; }
+        _ => {}
+    }
+    for name in attachments.split(';').filter(|n| !n.is_empty()) {
+        let path = Path::new(&state.task_files_dir).join(name);
+        if let Err(e) = bot.send_document(chat_id, InputFile::file(path).file_name(name.to_string())).await {
+            error!("Task attachment {} not sent: {:?}", name, e);
+        }
+    }
+    Ok(())
+}
+
+async fn send_task(bot: &Bot, chat_id: ChatId, state: &AppState, dialogue: &MyDialogue, uid: i64, ege_number: i64) -> HandlerResult {
+    let Some((task_id, statement, file_id, file_kind, attachments)) = db::pick_task(&state.pool, ege_number, uid).await? else {
+        dialogue.exit().await?;
+        bot.send_message(chat_id, format!("Для задания {} в банке пока нет задач.", ege_number)).await?;
+        return Ok(());
+    };
+
+    send_task_files(bot, chat_id, state, file_id, &file_kind, &attachments).await?;
+
+    dialogue.update(DialogueState::WaitingForTaskAnswer { task_id }).await?;
+    bot.send_message(chat_id, format!(
+        "🎯 Задание {} (задача #{})\n\n{}\n\nОтправь ответ одним сообщением.",
+        ege_number, task_id, statement
+    )).await?;
+    Ok(())
+}
+
+fn next_mock_number(answered: &[i64], current: i64) -> Option<i64> {
+    (current + 1..=27).chain(1..current)
+        .find(|n| !answered.contains(n))
+}
+
+async fn finish_mock(bot: &Bot, state: &AppState, attempt_id: i64) -> HandlerResult {
+    mock::finish_attempt(bot, &state.pool, state.admin_id, &state.mock_score_table, attempt_id).await?;
+    Ok(())
+}
+
+async fn send_mock_task(bot: &Bot, chat_id: ChatId, state: &AppState, dialogue: &MyDialogue, attempt_id: i64, ege_number: i64) -> HandlerResult {
+    let Some((_, variant_id, deadline_ts, finished)) = db::get_mock_attempt(&state.pool, attempt_id).await? else {
+        return Ok(());
+    };
+    if finished {
+        dialogue.exit().await?;
+        bot.send_message(chat_id, "Этот пробник уже завершён.").await?;
+        return Ok(());
+    }
+    let left = mock::minutes_left(&deadline_ts);
+    if left < 0 {
+        dialogue.exit().await?;
+        return finish_mock(bot, state, attempt_id).await;
+    }
+
+    let Some((_, statement, file_id, file_kind, attachments)) = db::get_mock_task(&state.pool, variant_id, ege_number).await? else {
+        bot.send_message(chat_id, format!("В варианте нет задания {}.", ege_number)).await?;
+        return Ok(());
+    };
+    send_task_files(bot, chat_id, state, file_id, &file_kind, &attachments).await?;
+
+    dialogue.update(DialogueState::MockExam { attempt_id, ege_number }).await?;
+    bot.send_message(chat_id, format!(
+        "🧪 Задание {} из 27 · осталось {}\n\n{}\n\nОтправь ответ одним сообщением.",
+        ege_number, mock::format_left(left), statement
+    )).reply_markup(keyboards::mock_task_kb(attempt_id)).await?;
+    Ok(())
+}
+
+async fn mock_grid_text(state: &AppState, attempt_id: i64) -> anyhow::Result<(String, InlineKeyboardMarkup)> {
+    let answered = db::get_mock_answered(&state.pool, attempt_id).await?;
+    let left = match db::get_mock_attempt(&state.pool, attempt_id).await? {
+        Some((_, _, deadline_ts, _)) => mock::minutes_left(&deadline_ts),
+        None => 0,
+    };
+    let text = format!(
+        "🧪 Пробник: отвечено {} из 27, осталось {}.\nВыбери задание:",
+        answered.len(), mock::format_left(left)
+    );
+    Ok((text, keyboards::mock_grid_kb(attempt_id, &answered)))
+}
+
+async fn send_next_card(bot: &Bot, chat_id: ChatId, state: &AppState, uid: i64, scope: &str) -> HandlerResult {
+    let today = Utc::now().format("%Y-%m-%d").to_string();
+    match db::next_due_card(&state.pool, uid, scope, &today).await? {
+        Some((card_id, front)) => {
+            bot.send_message(chat_id, format!("🃏 {}", front))
+                .reply_markup(keyboards::flashcard_kb(card_id, scope))
+                .await?;
+        }
+        None => {
+            bot.send_message(chat_id, "На сегодня карточек больше нет. Возвращайся завтра!").await?;
+        }
+    }
+    Ok(())
+}
+
+async fn download_code(bot: &Bot, file_id: &str) -> anyhow::Result<Option<Vec<u8>>> {
+    let file = bot.get_file(file_id.to_string()).await?;
+    if file.size > MAX_CODE_BYTES {
+        return Ok(None);
+    }
+    let mut code = Vec::new();
+    bot.download_file(&file.path, &mut code).await?;
+    Ok(Some(code))
+}
+
+async fn notify_admin(bot: &Bot, state: &AppState, text: String, code: Option<&str>, kb: InlineKeyboardMarkup) -> HandlerResult {
+    let preview = match code.map(code_preview::render_code_png) {
+        Some(Ok(png)) if !png.is_empty() => Some(png),
+        Some(Err(e)) => {
+            error!("Code preview failed: {:?}", e);
+            None
+        }
+        _ => None,
+    };
+
+    match preview {
+        Some(png) => {
+            let caption: String = text.chars().take(1000).collect();
+            bot.send_photo(UserId(state.admin_id as u64), InputFile::memory(png).file_name("code.png"))
+                .caption(caption)
+                .reply_markup(kb)
+                .await?;
+        }
+        None => {
+            bot.send_message(UserId(state.admin_id as u64), text).reply_markup(kb).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn check_submitted_code(bot: Bot, state: AppState, uid: i64, author: String, topic_title: String, code: Vec<u8>, tests: Vec<(String, String)>) -> HandlerResult {
+    bot.send_message(UserId(uid as u64), "🔍 Запускаю автопроверку кода...").await?;
+    let outcomes = match state.code_checker.run(&code, &tests).await {
+        Ok(outcomes) => outcomes,
+        Err(e) => {
+            error!("Code check failed: {:?}", e);
+            bot.send_message(UserId(uid as u64), "Автопроверка сейчас недоступна, работу посмотрит преподаватель.").await?;
+            return Ok(());
+        }
+    };
+
+    bot.send_message(UserId(uid as u64), code_check::format_report(&outcomes, false)).await?;
+    bot.send_message(
+        UserId(state.admin_id as u64),
+        format!("Код от @{} ({}):\n{}", author, topic_title, code_check::format_report(&outcomes, true))
+    ).await?;
+    Ok(())
+}
+
+pub async fn send_task_error_report(bot: &Bot, chat_id: ChatId, pool: &db::DbPool, repos: &repo::Repos, metrics: &Metrics, since: Option<NaiveDate>) -> HandlerResult {
+    let started = Instant::now();
+    let report = reports::generate_task_error_report(pool, repos, since).await?;
+    metrics.report_generated("task_errors", started);
+    bot.send_message(chat_id, report.text).await?;
+    if !report.heatmap.is_empty() {
+        bot.send_photo(chat_id, InputFile::memory(report.heatmap).file_name("task_heatmap.png")).await?;
+    }
+    if !report.excel.is_empty() {
+        bot.send_document(chat_id, InputFile::memory(report.excel).file_name("task_errors.xlsx")).await?;
+    }
+    Ok(())
+}
+
+async fn backup_before(bot: &Bot, chat_id: ChatId, state: &AppState, action: &str, cancelled: &str) -> anyhow::Result<bool> {
+    if let Err(e) = state.backups.snapshot_before(action).await {
+        error!("Backup before {} failed: {:?}", action, e);
+        bot.send_message(chat_id, format!("Не удалось сделать резервную копию, {}.", cancelled)).await?;
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+async fn user_submission(state: &AppState, uid: i64, submission_id: i64) -> anyhow::Result<Option<Submission>> {
+    Ok(state.repos.submissions.get(submission_id).await?.filter(|s| s.user_id == uid))
+}
+
+async fn history_page(state: &AppState, uid: i64, page: i64) -> anyhow::Result<(String, InlineKeyboardMarkup)> {
+    let filter = SubmissionFilter { user_id: Some(uid), ..Default::default() };
+    let total = state.repos.submissions.count(&filter).await?;
+    let total_pages = (total + HISTORY_PAGE_SIZE - 1) / HISTORY_PAGE_SIZE;
+    let page = page.clamp(0, (total_pages - 1).max(0));
+    let items = state.repos.submissions.list(&filter, Some(HISTORY_PAGE_SIZE), page * HISTORY_PAGE_SIZE).await?;
+
+    let text = if total == 0 {
+        "Ты пока ничего не сдал(а).".to_string()
+    } else {
+        format!("🗂️ История сдач (всего: {}).\nНажми на работу, чтобы посмотреть её:", total)
+    };
+    Ok((text, keyboards::history_kb(&items, page, total_pages)))
+}
+
 pub async fn message_handler(
     bot: Bot,
     msg: Message,
@@ -38,8 +333,17 @@ pub async fn message_handler(
         None => return Ok(()),
     };
 
-    if let Err(e) = db::upsert_user(&state.pool, user.id.0 as i64, user.username.clone(), user.first_name.clone()).await {
-        error!("Failed to update user: {:?}", e);
+    let profile = repo::User { id: user.id.0 as i64, username: user.username.clone(), first_name: user.first_name.clone() };
+    match state.repos.users.upsert(profile).await {
+        Ok(true) => {
+            state.events.emit(events::USER_REGISTERED, json!({
+                "user_id": user.id.0,
+                "username": user.username,
+                "first_name": user.first_name,
+            })).await;
+        }
+        Ok(false) => {}
+        Err(e) => error!("Failed to update user: {:?}", e),
     }
 
     let text = msg.text().unwrap_or("");
@@ -70,14 +374,67 @@ pub async fn message_handler(
                         .await?;
                 }
                 "📁 Мои конспекты" => {
-                    bot.send_message(msg.chat.id, "Архивирую твои конспекты, подожди пару секунд...").await?;
-                    let zip_data = reports::archive_user_conspects(&state.conspects_dir, uid).await;
-                    match zip_data {
-                        Ok(data) if !data.is_empty() => {
-                            bot.send_document(msg.chat.id, InputFile::memory(data).file_name("my_conspects.zip")).await?;
+                    bot.send_message(msg.chat.id, "Какие конспекты скачать?")
+                        .reply_markup(keyboards::conspects_kb())
+                        .await?;
+                }
+                "🗂️ История сдач" => {
+                    let (text, kb) = history_page(&state, uid, 0).await?;
+                    bot.send_message(msg.chat.id, text).reply_markup(kb).await?;
+                }
+                "🎯 Задачи ЕГЭ" => {
+                    dialogue.update(DialogueState::ChoosingTaskTopic).await?;
+                    bot.send_message(msg.chat.id, "Выбери номер задания:")
+                        .reply_markup(topics_kb("ЕГЭ 1-27"))
+                        .await?;
+                }
+                "🧪 Пробник" => {
+                    if let Some(attempt_id) = db::get_active_mock_attempt(&state.pool, uid).await? {
+                        let (text, kb) = mock_grid_text(&state, attempt_id).await?;
+                        bot.send_message(msg.chat.id, text).reply_markup(kb).await?;
+                    } else {
+                        let variants = db::list_open_mock_variants(&state.pool, uid).await?;
+                        let text = if variants.is_empty() {
+                            "Сейчас нет доступных пробников.".to_string()
+                        } else {
+                            format!(
+                                "🧪 Пробник: 27 заданий, {} на решение.\nОтсчёт начнётся сразу после выбора варианта.",
+                                mock::format_left(mock::MOCK_DURATION_MIN)
+                            )
+                        };
+                        bot.send_message(msg.chat.id, text).reply_markup(keyboards::mock_variants_kb(&variants)).await?;
+                    }
+                }
+                "🃏 Карточки" => {
+                    let today = Utc::now().format("%Y-%m-%d").to_string();
+                    let topics = db::flashcard_topics(&state.pool, uid, &today).await?;
+                    if topics.is_empty() {
+                        bot.send_message(msg.chat.id, "Карточек пока нет.").await?;
+                    } else {
+                        bot.send_message(msg.chat.id, "🃏 Карточки по теории. Рядом с темой — сколько карточек ждут повторения.\nВыбери тему:")
+                            .reply_markup(keyboards::flashcard_topics_kb(&topics))
+                            .await?;
+                    }
+                }
+                "📖 Библиотека" => {
+                    bot.send_message(msg.chat.id, "📖 Библиотека конспектов одноклассников.\nВыбери раздел:")
+                        .reply_markup(keyboards::library_sections_kb())
+                        .await?;
+                }
+                "📈 Моя статистика" => {
+                    let started = Instant::now();
+                    let result = reports::generate_user_stats(&state.pool, &state.repos, uid).await;
+                    state.metrics.report_generated("user_stats", started);
+                    match result {
+                        Ok((text, png)) => {
+                            bot.send_message(msg.chat.id, text).await?;
+                            if !png.is_empty() {
+                                bot.send_photo(msg.chat.id, InputFile::memory(png).file_name("my_stats.png")).await?;
+                            }
                         }
-                        _ => {
-                            bot.send_message(msg.chat.id, "У тебя пока нет сохранённых конспектов.").await?;
+                        Err(e) => {
+                            error!("Stats error: {:?}", e);
+                            bot.send_message(msg.chat.id, "Не удалось собрать статистику, попробуй позже.").await?;
                         }
                     }
                 }
@@ -90,8 +447,13 @@ pub async fn message_handler(
                     }
                 }
                 _ => {
-                    if db::is_waiting_for_reason(&state.pool, uid).await.unwrap_or(false) {
-                        db::save_miss_reason(&state.pool, uid, text).await?;
+                    if state.repos.miss_reasons.is_waiting(uid).await.unwrap_or(false) {
+                        state.repos.miss_reasons.answer(uid, text).await?;
+                        state.events.emit(events::MISS_REASON_SAVED, json!({
+                            "user_id": uid,
+                            "date": Utc::now().format("%Y-%m-%d").to_string(),
+                            "reason": text,
+                        })).await;
                         bot.send_message(msg.chat.id, "Причина сохранена, спасибо.").await?;
                     }
                     else if text.to_lowercase().starts_with("дз") || text.to_lowercase().starts_with("конспект") {
@@ -124,17 +486,32 @@ pub async fn message_handler(
                         return Ok(());
                     }
 
-                    db::add_submission(
-                        &state.pool, uid, &kind, &section, &topic_id, &topic_title,
-                        "photo", &summary, &file_id, msg.id.0, &date, &ts
-                    ).await?;
+                    let submission_id = state.repos.submissions.add(NewSubmission {
+                        user_id: uid,
+                        kind: kind.clone(),
+                        section: section.clone(),
+                        topic_id: topic_id.clone(),
+                        topic_title: topic_title.clone(),
+                        content_type: "photo".to_string(),
+                        summary: summary.clone(),
+                        file_id: file_id.clone(),
+                        message_id: msg.id.0,
+                        date: date.clone(),
+                        ts: ts.clone(),
+                    }).await?;
+                    state.metrics.submission(&kind, &section);
+                    state.events.submission_created(submission_id).await;
 
                     if matches!(kind, SubmissionType::Conspect) {
-                        if let Err(e) = reports::save_file_to_disk(&bot, &file_id, &state.conspects_dir, uid, &section, &topic_id).await {
-                            error!("Ошибка сохранения фото: {:?}", e);
-                            bot.send_message(msg.chat.id, "⚠️ Файл не удалось сохранить на диск. Попробуй еще раз.").await?;
-                        } else {
-                            info!("Фото сохранено для user {}", uid);
+                        match reports::save_file_to_disk(&bot, &file_id, &state.conspects_dir, uid, &section, &topic_id, submission_id).await {
+                            Ok(bytes) => {
+                                state.metrics.stored_bytes("conspect", bytes);
+                                info!("Фото сохранено для user {}", uid);
+                            }
+                            Err(e) => {
+                                error!("Ошибка сохранения фото: {:?}", e);
+                                bot.send_message(msg.chat.id, "⚠️ Файл не удалось сохранить на диск. Попробуй еще раз.").await?;
+                            }
                         }
                     }
 
@@ -147,7 +524,7 @@ pub async fn message_handler(
                                                                             match kind { SubmissionType::Dz => "ДЗ", SubmissionType::Conspect => "Конспект"},
                                                                             user.username.as_deref().unwrap_or("noname"),
                                                                             topic_title, summary
-                    )).await?;
+                    )).reply_markup(admin_submission_kb(&kind, submission_id)).await?;
 
                     dialogue.exit().await?;
                     return Ok(());
@@ -158,17 +535,32 @@ pub async fn message_handler(
                     let file_name = doc.document.file_name.clone().unwrap_or_else(|| "document".to_string());
                     let caption = doc.caption.clone().unwrap_or_else(|| file_name.clone());
 
-                    db::add_submission(
-                        &state.pool, uid, &kind, &section, &topic_id, &topic_title,
-                        "document", &caption, file_id, msg.id.0, &date, &ts
-                    ).await?;
+                    let submission_id = state.repos.submissions.add(NewSubmission {
+                        user_id: uid,
+                        kind: kind.clone(),
+                        section: section.clone(),
+                        topic_id: topic_id.clone(),
+                        topic_title: topic_title.clone(),
+                        content_type: "document".to_string(),
+                        summary: caption.clone(),
+                        file_id: file_id.clone(),
+                        message_id: msg.id.0,
+                        date: date.clone(),
+                        ts: ts.clone(),
+                    }).await?;
+                    state.metrics.submission(&kind, &section);
+                    state.events.submission_created(submission_id).await;
 
                     if matches!(kind, SubmissionType::Conspect) {
-                        if let Err(e) = reports::save_file_to_disk(&bot, file_id, &state.conspects_dir, uid, &section, &topic_id).await {
-                            error!("Ошибка сохранения документа: {:?}", e);
-                            bot.send_message(msg.chat.id, "⚠️ Ошибка сохранения файла.").await?;
-                        } else {
-                            info!("Документ сохранен для user {}", uid);
+                        match reports::save_file_to_disk(&bot, file_id, &state.conspects_dir, uid, &section, &topic_id, submission_id).await {
+                            Ok(bytes) => {
+                                state.metrics.stored_bytes("conspect", bytes);
+                                info!("Документ сохранен для user {}", uid);
+                            }
+                            Err(e) => {
+                                error!("Ошибка сохранения документа: {:?}", e);
+                                bot.send_message(msg.chat.id, "⚠️ Ошибка сохранения файла.").await?;
+                            }
                         }
                     }
 
@@ -177,11 +569,42 @@ pub async fn message_handler(
                         .reply_markup(main_kb(uid == state.admin_id))
                         .await?;
 
-                    bot.send_message(UserId(state.admin_id as u64), format!("📄 Новый {} (ФАЙЛ) от @{}: {} - {}",
-                                                                            match kind { SubmissionType::Dz => "ДЗ", SubmissionType::Conspect => "Конспект"},
-                                                                            user.username.as_deref().unwrap_or("noname"),
-                                                                            topic_title, caption
-                    )).await?;
+                    let is_python = file_name.to_lowercase().ends_with(".py");
+                    let code = if is_python {
+                        download_code(&bot, file_id).await.unwrap_or_else(|e| {
+                            error!("Code download failed: {:?}", e);
+                            None
+                        })
+                    } else {
+                        None
+                    };
+                    let code_text = code.as_ref().map(|c| String::from_utf8_lossy(c).to_string());
+
+                    notify_admin(&bot, &state, format!("📄 Новый {} (ФАЙЛ) от @{}: {} - {}",
+                                                       match kind { SubmissionType::Dz => "ДЗ", SubmissionType::Conspect => "Конспект"},
+                                                       user.username.as_deref().unwrap_or("noname"),
+                                                       topic_title, caption
+                    ), code_text.as_deref(), admin_submission_kb(&kind, submission_id)).await?;
+
+                    if matches!(kind, SubmissionType::Dz) && is_python && state.code_checker.available() {
+                        let tests = db::get_code_tests(&state.pool, &section, &topic_id).await?;
+                        match code {
+                            Some(code) if !tests.is_empty() => {
+                                let (bot, state) = (bot.clone(), state.clone());
+                                let author = user.username.clone().unwrap_or_else(|| "noname".to_string());
+                                let topic_title = topic_title.clone();
+                                tokio::spawn(async move {
+                                    if let Err(e) = check_submitted_code(bot, state, uid, author, topic_title, code, tests).await {
+                                        error!("Code check delivery failed: {:?}", e);
+                                    }
+                                });
+                            }
+                            None if !tests.is_empty() => {
+                                bot.send_message(msg.chat.id, "Файл с кодом слишком большой для автопроверки.").await?;
+                            }
+                            _ => {}
+                        }
+                    }
 
                     dialogue.exit().await?;
                     return Ok(());
@@ -191,14 +614,35 @@ pub async fn message_handler(
             if !text.is_empty() {
                 let summary = if text.len() > 300 { format!("{}...", &text[..297]) } else { text.to_string() };
 
-                db::add_submission(
-                    &state.pool, uid, &kind, &section, &topic_id, &topic_title,
-                    "text", &summary, "", msg.id.0, &date, &ts
-                ).await?;
+                let submission_id = state.repos.submissions.add(NewSubmission {
+                    user_id: uid,
+                    kind: kind.clone(),
+                    section: section.clone(),
+                    topic_id: topic_id.clone(),
+                    topic_title: topic_title.clone(),
+                    content_type: "text".to_string(),
+                    summary: summary.clone(),
+                    file_id: String::new(),
+                    message_id: msg.id.0,
+                    date: date.clone(),
+                    ts: ts.clone(),
+                }).await?;
+                state.metrics.submission(&kind, &section);
+                state.events.submission_created(submission_id).await;
+
+                let code = code_preview::extract_code(&msg);
 
                 if matches!(kind, SubmissionType::Conspect) {
-                    if let Err(e) = reports::save_text_to_disk(text, &state.conspects_dir, uid, &section, &topic_id).await {
-                        error!("Ошибка сохранения текста: {:?}", e);
+                    let saved = match &code {
+                        Some((code, language)) => {
+                            let ext = code_preview::code_extension(language.as_deref());
+                            reports::save_text_to_disk(code, &ext, &state.conspects_dir, uid, &section, &topic_id, submission_id).await
+                        }
+                        None => reports::save_text_to_disk(text, "txt", &state.conspects_dir, uid, &section, &topic_id, submission_id).await,
+                    };
+                    match saved {
+                        Ok(bytes) => state.metrics.stored_bytes("conspect", bytes),
+                        Err(e) => error!("Ошибка сохранения текста: {:?}", e),
                     }
                 }
 
@@ -207,11 +651,11 @@ pub async fn message_handler(
                     .reply_markup(main_kb(uid == state.admin_id))
                     .await?;
 
-                bot.send_message(UserId(state.admin_id as u64), format!("✅ Новый {} от @{}: {} - {}",
-                                                                        match kind { SubmissionType::Dz => "ДЗ", SubmissionType::Conspect => "Конспект"},
-                                                                        user.username.as_deref().unwrap_or("noname"),
-                                                                        topic_title, summary
-                )).await?;
+                notify_admin(&bot, &state, format!("✅ Новый {} от @{}: {} - {}",
+                                                   match kind { SubmissionType::Dz => "ДЗ", SubmissionType::Conspect => "Конспект"},
+                                                   user.username.as_deref().unwrap_or("noname"),
+                                                   topic_title, summary
+                ), code.as_ref().map(|(c, _)| c.as_str()), admin_submission_kb(&kind, submission_id)).await?;
 
                 dialogue.exit().await?;
                 return Ok(());
@@ -220,15 +664,331 @@ pub async fn message_handler(
             bot.send_message(msg.chat.id, "Пожалуйста, отправь фото, файл или текст.").await?;
         }
 
+        Some(DialogueState::WaitingForTaskAnswer { task_id }) => {
+            if text.trim().is_empty() {
+                bot.send_message(msg.chat.id, "Пришли ответ текстом.").await?;
+                return Ok(());
+            }
+
+            let Some((ege_number, canonical, kind)) = db::get_task_answer(&state.pool, task_id).await? else {
+                dialogue.exit().await?;
+                bot.send_message(msg.chat.id, "Задача не найдена, выбери другую.").reply_markup(main_kb(uid == state.admin_id)).await?;
+                return Ok(());
+            };
+
+            let kind = AnswerKind::parse(&kind).unwrap_or(AnswerKind::default_for(ege_number));
+            let is_correct = tasks::check_answer(text, &canonical, kind);
+            let date = Utc::now().format("%Y-%m-%d").to_string();
+            let ts = Utc::now().to_rfc3339();
+            let verdict = if is_correct { "верно" } else { "неверно" };
+            let answer: String = text.trim().chars().take(200).collect();
+
+            let submission_id = state.repos.submissions.add(NewSubmission {
+                user_id: uid,
+                kind: SubmissionType::Dz,
+                section: "ЕГЭ 1-27".to_string(),
+                topic_id: format!("ege{}", ege_number),
+                topic_title: format!("Задание {}", ege_number),
+                content_type: "task_answer".to_string(),
+                summary: format!("Задача #{}: {} ({})", task_id, answer, verdict),
+                file_id: String::new(),
+                message_id: msg.id.0,
+                date: date.clone(),
+                ts: ts.clone(),
+            }).await?;
+            state.metrics.submission(&SubmissionType::Dz, "ЕГЭ 1-27");
+            state.events.submission_created(submission_id).await;
+            db::record_task_attempt(&state.pool, uid, task_id, Some(submission_id), &answer, is_correct, &ts).await?;
+
+            dialogue.exit().await?;
+            let reply = if is_correct {
+                format!("✅ Верно! {}", get_praise())
+            } else {
+                "❌ Неверно. Разбери задачу и попробуй следующую.".to_string()
+            };
+            bot.send_message(msg.chat.id, reply).reply_markup(keyboards::task_next_kb(ege_number)).await?;
+        }
+
+        Some(DialogueState::WaitingForDailyAnswer { task_id }) => {
+            if text.trim().is_empty() {
+                bot.send_message(msg.chat.id, "Пришли ответ текстом.").await?;
+                return Ok(());
+            }
+
+            dialogue.exit().await?;
+            let Some((ege_number, canonical, kind)) = db::get_task_answer(&state.pool, task_id).await? else {
+                bot.send_message(msg.chat.id, "Задача не найдена.").await?;
+                return Ok(());
+            };
+
+            let kind = AnswerKind::parse(&kind).unwrap_or(AnswerKind::default_for(ege_number));
+            let is_correct = tasks::check_answer(text, &canonical, kind);
+            let date = Utc::now().format("%Y-%m-%d").to_string();
+            let ts = Utc::now().to_rfc3339();
+            let answer: String = text.trim().chars().take(200).collect();
+
+            if !db::answer_daily_task(&state.pool, uid, &date, &answer, is_correct).await? {
+                bot.send_message(msg.chat.id, "Задача дня уже закрыта, жди новую завтра утром.").await?;
+                return Ok(());
+            }
+            db::record_task_attempt(&state.pool, uid, task_id, None, &answer, is_correct, &ts).await?;
+
+            let (streak, best) = daily::current_streak(&state.pool, uid).await?;
+            let reply = if is_correct {
+                format!("✅ Верно! {}\n🔥 Серия: {} дн. (рекорд: {} дн.)", get_praise(), streak, best)
+            } else {
+                format!("❌ Неверно. Правильный ответ: {}\nСерия начнётся заново завтра.", canonical)
+            };
+            bot.send_message(msg.chat.id, reply).reply_markup(main_kb(uid == state.admin_id)).await?;
+        }
+
+        Some(DialogueState::MockExam { attempt_id, ege_number }) => {
+            if text.trim().is_empty() {
+                bot.send_message(msg.chat.id, "Пришли ответ текстом.").await?;
+                return Ok(());
+            }
+
+            let Some((_, _, deadline_ts, finished)) = db::get_mock_attempt(&state.pool, attempt_id).await? else {
+                dialogue.exit().await?;
+                return Ok(());
+            };
+            if finished || mock::minutes_left(&deadline_ts) < 0 {
+                dialogue.exit().await?;
+                bot.send_message(msg.chat.id, "Время пробника вышло, ответ не принят.").await?;
+                return finish_mock(&bot, &state, attempt_id).await;
+            }
+
+            let answer: String = text.trim().chars().take(200).collect();
+            db::save_mock_answer(&state.pool, attempt_id, ege_number, &answer).await?;
+
+            let answered = db::get_mock_answered(&state.pool, attempt_id).await?;
+            match next_mock_number(&answered, ege_number) {
+                Some(next) => {
+                    send_mock_task(&bot, msg.chat.id, &state, &dialogue, attempt_id, next).await?;
+                }
+                None => {
+                    bot.send_message(msg.chat.id, "Ответы на все задания записаны. Можно проверить их или завершить пробник.")
+                        .reply_markup(keyboards::mock_grid_kb(attempt_id, &answered))
+                        .await?;
+                }
+            }
+        }
+
+        Some(DialogueState::AdminWaitingForMockVariant) => {
+            let title = text.trim();
+            let tasks = db::variant_task_ids(&state.pool, title).await?;
+            let missing: Vec<String> = (1..=27)
+                .filter(|n| !tasks.iter().any(|(num, _)| num == n))
+                .map(|n| n.to_string())
+                .collect();
+
+            if title.is_empty() || !missing.is_empty() {
+                bot.send_message(msg.chat.id, format!(
+                    "В варианте «{}» не хватает заданий: {}.\nДозагрузи их через импорт и пришли название ещё раз.",
+                    title, missing.join(", ")
+                )).await?;
+                return Ok(());
+            }
+
+            if !backup_before(&bot, msg.chat.id, &state, "mock-publish", "публикация отменена").await? {
+                return Ok(());
+            }
+            db::publish_mock_variant(&state.pool, title, &tasks).await?;
+            dialogue.update(DialogueState::AdminPanel).await?;
+            bot.send_message(msg.chat.id, format!("Пробник «{}» опубликован.", title)).reply_markup(admin_kb()).await?;
+        }
+
+        Some(DialogueState::AdminWaitingForCodeTests) => {
+            let spec = if let Some(doc) = msg.document() {
+                let file = bot.get_file(doc.file.id.clone()).await?;
+                let mut data = Vec::new();
+                bot.download_file(&file.path, &mut data).await?;
+                String::from_utf8_lossy(&data).to_string()
+            } else {
+                text.to_string()
+            };
+
+            match code_check::parse_test_spec(&spec) {
+                Ok(spec) => {
+                    if !backup_before(&bot, msg.chat.id, &state, "code-tests", "тесты не заменены").await? {
+                        return Ok(());
+                    }
+                    db::replace_code_tests(&state.pool, &spec.section, &spec.topic_id, &spec.tests).await?;
+                    dialogue.update(DialogueState::AdminPanel).await?;
+                    bot.send_message(msg.chat.id, format!(
+                        "Сохранено тестов для {} / {}: {}. Сданные .py файлы будут проверяться автоматически.",
+                        spec.section, spec.topic_id, spec.tests.len()
+                    )).reply_markup(admin_kb()).await?;
+                }
+                Err(e) => {
+                    bot.send_message(msg.chat.id, format!("Не удалось разобрать тесты: {}", e)).await?;
+                }
+            }
+        }
+
+        Some(DialogueState::AdminWaitingForCards) => {
+            match flashcards::parse_cards(text) {
+                Ok(spec) => {
+                    db::add_flashcards(&state.pool, &spec.section, &spec.topic_id, &spec.cards).await?;
+                    dialogue.update(DialogueState::AdminPanel).await?;
+                    bot.send_message(msg.chat.id, format!(
+                        "Добавлено карточек для {} / {}: {}.",
+                        spec.section, spec.topic_id, spec.cards.len()
+                    )).reply_markup(admin_kb()).await?;
+                }
+                Err(e) => {
+                    bot.send_message(msg.chat.id, format!("Не удалось разобрать карточки: {}", e)).await?;
+                }
+            }
+        }
+
+        Some(DialogueState::AdminWaitingForQuiz) => {
+            match quiz::parse_quiz(text) {
+                Ok(spec) => {
+                    let quiz_id = db::create_quiz(&state.pool, &spec.section, &spec.topic_id, &spec.questions).await?;
+                    dialogue.update(DialogueState::AdminPanel).await?;
+                    bot.send_message(msg.chat.id, format!(
+                        "Квиз для {} / {} сохранён ({} вопр.), рассылаю ученикам...",
+                        spec.section, spec.topic_id, spec.questions.len()
+                    )).reply_markup(admin_kb()).await?;
+
+                    let (bot, state) = (bot.clone(), state.clone());
+                    tokio::spawn(async move {
+                        let text = match quiz::deliver_quiz(&bot, &state.pool, state.repos.users.as_ref(), &state.health, state.admin_id, quiz_id).await {
+                            Ok(reached) => format!("❓ Квиз разослан: {} учеников.", reached),
+                            Err(e) => {
+                                error!("Quiz {} delivery failed: {:?}", quiz_id, e);
+                                "Ошибка рассылки квиза".to_string()
+                            }
+                        };
+                        let _ = bot.send_message(UserId(state.admin_id as u64), text).await;
+                    });
+                }
+                Err(e) => {
+                    bot.send_message(msg.chat.id, format!("Не удалось разобрать квиз: {}", e)).await?;
+                }
+            }
+        }
+
+        Some(DialogueState::AdminWaitingForTaskNumber) => {
+            match text.trim().parse::<i64>() {
+                Ok(n) if (1..=27).contains(&n) => {
+                    dialogue.update(DialogueState::AdminWaitingForTaskStatement { ege_number: n }).await?;
+                    bot.send_message(msg.chat.id, format!(
+                        "Задание {}. Пришли условие: текст, фото или файл (условие можно написать в подписи).", n
+                    )).await?;
+                }
+                _ => {
+                    bot.send_message(msg.chat.id, "Нужен номер задания от 1 до 27.").await?;
+                }
+            }
+        }
+
+        Some(DialogueState::AdminWaitingForTaskStatement { ege_number }) => {
+            let (file_id, file_kind) = if let Some(photo) = msg.photo().and_then(|p| p.last()) {
+                (photo.file.id.clone(), "photo")
+            } else if let Some(doc) = msg.document() {
+                (doc.file.id.clone(), "document")
+            } else {
+                (String::new(), "")
+            };
+            let statement = msg.caption().or(msg.text()).unwrap_or("").to_string();
+
+            if statement.is_empty() && file_id.is_empty() {
+                bot.send_message(msg.chat.id, "Пришли условие текстом, фото или файлом.").await?;
+                return Ok(());
+            }
+
+            dialogue.update(DialogueState::AdminWaitingForTaskAnswer {
+                ege_number, statement, file_id, file_kind: file_kind.to_string()
+            }).await?;
+            bot.send_message(msg.chat.id, format!(
+                "Пришли правильный ответ.\nПо умолчанию для задания {} проверка: {}.\n\
+                 Можно указать явно: «exact: ответ», «set: 1 3 4» (порядок не важен), «seq: 10 20» (числа по порядку).",
+                ege_number, AnswerKind::default_for(ege_number).as_str()
+            )).await?;
+        }
+
+        Some(DialogueState::AdminWaitingForTaskAnswer { ege_number, statement, file_id, file_kind }) => {
+            let (kind, answer) = tasks::parse_answer_spec(text, ege_number);
+            if tasks::normalize(&answer, kind).is_empty() {
+                bot.send_message(msg.chat.id, "Ответ не может быть пустым.").await?;
+                return Ok(());
+            }
+
+            let task_id = db::add_task(&state.pool, ege_number, &statement, &file_id, &file_kind, &answer, kind).await?;
+            dialogue.update(DialogueState::AdminPanel).await?;
+            bot.send_message(msg.chat.id, format!(
+                "Задача #{} для задания {} добавлена (ответ: {}, проверка: {}).",
+                task_id, ege_number, answer, kind.as_str()
+            )).reply_markup(admin_kb()).await?;
+        }
+
+        Some(DialogueState::AdminWaitingForTaskImport) => {
+            let Some(doc) = msg.document() else {
+                bot.send_message(msg.chat.id, "Пришли таблицу .xlsx/.csv или файл-вложение документом.").await?;
+                return Ok(());
+            };
+            let file_name = doc.file_name.clone().unwrap_or_default();
+            let lower = file_name.to_lowercase();
+
+            let file = bot.get_file(doc.file.id.clone()).await?;
+            let mut data = Vec::new();
+            bot.download_file(&file.path, &mut data).await?;
+
+            if lower.ends_with(".xlsx") || lower.ends_with(".csv") {
+                if !backup_before(&bot, msg.chat.id, &state, "task-import", "импорт отменён").await? {
+                    return Ok(());
+                }
+                match import::import_tasks(&state.pool, &file_name, &data, &state.task_files_dir).await {
+                    Ok(report) => {
+                        dialogue.update(DialogueState::AdminPanel).await?;
+                        bot.send_message(msg.chat.id, report.summary()).reply_markup(admin_kb()).await?;
+                    }
+                    Err(e) => {
+                        bot.send_message(msg.chat.id, format!("Импорт не выполнен: {}", e)).await?;
+                    }
+                }
+            } else {
+                let Some(safe_name) = Path::new(&file_name).file_name().and_then(|n| n.to_str()).filter(|n| !n.is_empty()) else {
+                    bot.send_message(msg.chat.id, "У файла нет имени, его нельзя сослать из таблицы.").await?;
+                    return Ok(());
+                };
+                tokio::fs::create_dir_all(&state.task_files_dir).await?;
+                let bytes = data.len() as u64;
+                tokio::fs::write(Path::new(&state.task_files_dir).join(safe_name), data).await?;
+                state.metrics.stored_bytes("task_file", bytes);
+                bot.send_message(msg.chat.id, format!("Вложение «{}» сохранено. Можно прислать ещё файлы или таблицу.", safe_name)).await?;
+            }
+        }
+
+        Some(DialogueState::WaitingForConspectRange) => {
+            let dates: Vec<NaiveDate> = text.split_whitespace()
+                .filter_map(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+                .collect();
+
+            if dates.len() != 2 || dates[0] > dates[1] {
+                bot.send_message(msg.chat.id, "Пришли две даты через пробел, например: 2025-01-01 2025-01-31").await?;
+                return Ok(());
+            }
+
+            dialogue.exit().await?;
+            send_conspects_archive(&bot, msg.chat.id, &state, uid, ConspectSelection::Dates(dates[0], dates[1])).await?;
+        }
+
         Some(DialogueState::AdminWaitingForExportUser) => {
             let target = text.trim().trim_start_matches('@');
             bot.send_message(msg.chat.id, "Начинаю выгрузку...").await?;
 
-            match reports::export_user_data(&state.pool, &state.conspects_dir, target).await {
-                Ok((excel, zip)) => {
-                    bot.send_document(msg.chat.id, InputFile::memory(excel).file_name("submissions.xlsx")).await?;
-                    if let Some(z) = zip {
-                        bot.send_document(msg.chat.id, InputFile::memory(z).file_name("files.zip")).await?;
+            let started = Instant::now();
+            let result = reports::export_user_data(&state.pool, &state.repos, &state.conspects_dir, target).await;
+            state.metrics.report_generated("user_export", started);
+            match result {
+                Ok(export) => {
+                    bot.send_document(msg.chat.id, InputFile::memory(export.excel).file_name("submissions.xlsx")).await?;
+                    send_zip_parts(&bot, msg.chat.id, export.zip_parts, "files").await?;
+                    for (name, doc) in export.pdfs {
+                        bot.send_document(msg.chat.id, InputFile::memory(doc).file_name(name)).await?;
                     }
                     bot.send_message(msg.chat.id, "Готово.").reply_markup(admin_kb()).await?;
                 }
@@ -239,8 +999,49 @@ pub async fn message_handler(
             dialogue.update(DialogueState::AdminPanel).await?;
         }
 
+        Some(DialogueState::AdminWaitingForGroups) => {
+            let mut missing = Vec::new();
+            let mut updated = 0;
+            for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+                let (who, group) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+                let who = who.trim_start_matches('@');
+                if state.repos.users.set_group(who, group.trim()).await? {
+                    updated += 1;
+                } else {
+                    missing.push(who.to_string());
+                }
+            }
+            let mut reply = format!("Группы обновлены: {}.", updated);
+            if !missing.is_empty() {
+                reply.push_str(&format!("\nНе найдены: {}", missing.join(", ")));
+            }
+            dialogue.update(DialogueState::AdminPanel).await?;
+            bot.send_message(msg.chat.id, reply).reply_markup(admin_kb()).await?;
+        }
+
+        Some(DialogueState::AdminWaitingForTeacherScores) => {
+            match mock::parse_teacher_scores(text) {
+                Ok(scores) => {
+                    let missing = mock::apply_teacher_scores(&state.pool, &state.mock_score_table, &scores).await?;
+                    let mut reply = format!("Оценок сохранено: {}.", scores.len() - missing.len());
+                    if !missing.is_empty() {
+                        let list: Vec<String> = missing.iter().map(|(a, n)| format!("{}/{}", a, n)).collect();
+                        reply.push_str(&format!("\nНет завершённой попытки с таким заданием: {}", list.join(", ")));
+                    }
+                    dialogue.update(DialogueState::AdminPanel).await?;
+                    bot.send_message(msg.chat.id, reply).reply_markup(admin_kb()).await?;
+                }
+                Err(e) => {
+                    bot.send_message(msg.chat.id, format!("Не удалось разобрать оценки: {}", e)).await?;
+                }
+            }
+        }
+
         Some(DialogueState::AdminWaitingForDeleteUser) => {
-            if db::delete_user_fully(&state.pool, &state.conspects_dir, text.trim()).await.is_ok() {
+            if let Err(e) = state.backups.snapshot_before("delete-user").await {
+                error!("Backup before user deletion failed: {:?}", e);
+                bot.send_message(msg.chat.id, "Не удалось сделать резервную копию, удаление отменено.").reply_markup(admin_kb()).await?;
+            } else if reports::delete_user_fully(&state.repos, &state.conspects_dir, text.trim()).await.is_ok() {
                 bot.send_message(msg.chat.id, "Пользователь удален.").reply_markup(admin_kb()).await?;
             } else {
                 bot.send_message(msg.chat.id, "Ошибка удаления.").reply_markup(admin_kb()).await?;
@@ -254,6 +1055,68 @@ pub async fn message_handler(
     Ok(())
 }
 
+pub async fn flush_media_groups(bot: &Bot, state: &AppState, now: i64) {
+    let mut keys_to_process = Vec::new();
+
+    for r in state.media_groups.iter() {
+        if now - r.value().2 > 2 {
+            keys_to_process.push(r.key().clone());
+        }
+    }
+
+    for key in keys_to_process {
+        if let Some((_, (file_ids, caption, _, context))) = state.media_groups.remove(&key) {
+            let uid_str = key.split('|').next().unwrap_or("0");
+            let uid = uid_str.parse::<i64>().unwrap_or(0);
+            let (kind, section, topic_id, topic_title) = context;
+
+            let date = Utc::now().format("%Y-%m-%d").to_string();
+            let ts = Utc::now().to_rfc3339();
+            let summary = if caption.len() > 200 { format!("{}...", &caption[..197]) } else { caption.clone() };
+            let joined_files = file_ids.join(";");
+
+            let res = state.repos.submissions.add(NewSubmission {
+                user_id: uid,
+                kind: kind.clone(),
+                section: section.clone(),
+                topic_id: topic_id.clone(),
+                topic_title: topic_title.clone(),
+                content_type: "photo_album".to_string(),
+                summary: summary.clone(),
+                file_id: joined_files,
+                message_id: 0,
+                date,
+                ts,
+            }).await;
+
+            if let Ok(submission_id) = res {
+                state.metrics.submission(&kind, &section);
+                state.events.submission_created(submission_id).await;
+                state.events.emit(events::ALBUM_FLUSHED, json!({
+                    "submission_id": submission_id,
+                    "user_id": uid,
+                    "photos": file_ids.len(),
+                })).await;
+                if matches!(kind, SubmissionType::Conspect) {
+                    for fid in file_ids.iter() {
+                        if let Ok(bytes) = reports::save_file_to_disk(bot, fid, &state.conspects_dir, uid, &section, &topic_id, submission_id).await {
+                            state.metrics.stored_bytes("conspect", bytes);
+                        }
+                    }
+                }
+
+                let type_str = match kind { SubmissionType::Dz => "ДЗ", SubmissionType::Conspect => "Конспект" };
+                let _ = bot.send_message(UserId(uid as u64), format!("Альбом принят! ({} фото)", file_ids.len())).await;
+
+                let _ = bot.send_message(UserId(state.admin_id as u64), format!(
+                    "📸 Новый {} (АЛЬБОМ) от user_{}: {} - {}",
+                    type_str, uid, topic_title, summary
+                )).reply_markup(admin_submission_kb(&kind, submission_id)).await;
+            }
+        }
+    }
+}
+
 pub async fn callback_handler(
     bot: Bot,
     q: CallbackQuery,
@@ -311,10 +1174,417 @@ pub async fn callback_handler(
                                   format!("Тема: {}\nОтправь {} (фото, файл или текст).", topic_title, type_str))
                 .reply_markup(teloxide::types::InlineKeyboardMarkup::default())
                 .await?;
+        } else if let Some(DialogueState::ChoosingTaskTopic) = dialogue.get().await? {
+            let ege_number = topic_id.trim_start_matches("ege").parse::<i64>().unwrap_or(0);
+            bot.edit_message_reply_markup(q.from.id, msg_id).await?;
+            send_task(&bot, ChatId(uid), &state, &dialogue, uid, ege_number).await?;
+        }
+        bot.answer_callback_query(q.id).await?;
+        return Ok(());
+    }
+
+    if data.starts_with("cdl|") {
+        let parts: Vec<&str> = data.split('|').collect();
+
+        let selection = match parts.as_slice() {
+            ["cdl", "all"] => Some(ConspectSelection::All),
+            ["cdl", "get", section] => Some(ConspectSelection::Section(section.to_string())),
+            ["cdl", "topic", section, topic_id] => Some(ConspectSelection::Topic(section.to_string(), topic_id.to_string())),
+            ["cdl", "sec", section] => {
+                bot.edit_message_text(q.from.id, msg_id, format!("Раздел: {}\nВыбери тему:", section))
+                    .reply_markup(keyboards::conspect_topics_kb(section))
+                    .await?;
+                None
+            }
+            ["cdl", "range"] => {
+                dialogue.update(DialogueState::WaitingForConspectRange).await?;
+                bot.edit_message_text(q.from.id, msg_id, "Пришли период в формате: 2025-01-01 2025-01-31").await?;
+                None
+            }
+            _ => None,
+        };
+
+        bot.answer_callback_query(q.id).await?;
+        if let Some(selection) = selection {
+            bot.edit_message_reply_markup(q.from.id, msg_id).await?;
+            send_conspects_archive(&bot, ChatId(uid), &state, uid, selection).await?;
+        }
+        return Ok(());
+    }
+
+    if data.starts_with("cpdf|") {
+        let parts: Vec<&str> = data.split('|').collect();
+        let (selection, title, file_name) = match parts.as_slice() {
+            ["cpdf", section] => (
+                ConspectSelection::Section(section.to_string()),
+                section.to_string(),
+                "conspects.pdf".to_string(),
+            ),
+            ["cpdf", section, topic_id] => (
+                ConspectSelection::Topic(section.to_string(), topic_id.to_string()),
+                keyboards::get_topic_title(section, topic_id).unwrap_or_else(|| "Конспект".to_string()),
+                format!("conspect_{}.pdf", topic_id),
+            ),
+            _ => return Ok(()),
+        };
+
+        bot.answer_callback_query(q.id).text("Собираю PDF...").await?;
+        bot.edit_message_reply_markup(q.from.id, msg_id).await?;
+
+        let started = Instant::now();
+        let result = reports::generate_conspects_pdf(&state.conspects_dir, uid, &selection, &title).await;
+        state.metrics.report_generated("conspects_pdf", started);
+        match result {
+            Ok(doc) if !doc.is_empty() => {
+                bot.send_document(q.from.id, InputFile::memory(doc).file_name(file_name)).await?;
+            }
+            Ok(_) => {
+                bot.send_message(q.from.id, "По этой теме нет сохранённых фото конспектов.").await?;
+            }
+            Err(e) => {
+                error!("PDF error: {:?}", e);
+                bot.send_message(q.from.id, "Не удалось собрать PDF. Если раздел большой, попробуй выбрать одну тему.").await?;
+            }
         }
         return Ok(());
     }
 
+    if data.starts_with("task|") {
+        let parts: Vec<&str> = data.split('|').collect();
+        match parts.as_slice() {
+            ["task", "next", n] => {
+                bot.edit_message_reply_markup(q.from.id, msg_id).await?;
+                send_task(&bot, ChatId(uid), &state, &dialogue, uid, n.parse().unwrap_or(0)).await?;
+            }
+            ["task", "topics"] => {
+                dialogue.update(DialogueState::ChoosingTaskTopic).await?;
+                bot.edit_message_reply_markup(q.from.id, msg_id).await?;
+                bot.send_message(q.from.id, "Выбери номер задания:").reply_markup(topics_kb("ЕГЭ 1-27")).await?;
+            }
+            _ => {}
+        }
+        bot.answer_callback_query(q.id).await?;
+        return Ok(());
+    }
+
+    if data == "daily|answer" {
+        let date = Utc::now().format("%Y-%m-%d").to_string();
+        match db::get_pending_daily_task(&state.pool, uid, &date).await? {
+            Some(task_id) => {
+                dialogue.update(DialogueState::WaitingForDailyAnswer { task_id }).await?;
+                bot.answer_callback_query(q.id).await?;
+                bot.edit_message_reply_markup(q.from.id, msg_id).await?;
+                bot.send_message(q.from.id, "Пришли ответ на задачу дня одним сообщением.").await?;
+            }
+            None => {
+                bot.answer_callback_query(q.id).text("Эта задача уже решена или устарела").await?;
+            }
+        }
+        return Ok(());
+    }
+
+    if data.starts_with("fc|") {
+        let parts: Vec<&str> = data.split('|').collect();
+        match parts.as_slice() {
+            ["fc", "topic", scope] => {
+                bot.answer_callback_query(q.id).await?;
+                bot.edit_message_reply_markup(q.from.id, msg_id).await?;
+                send_next_card(&bot, ChatId(uid), &state, uid, scope).await?;
+            }
+            ["fc", answer @ ("yes" | "no"), card_id, scope] => {
+                let card_id: i64 = card_id.parse().unwrap_or(0);
+                let Some((front, back)) = db::get_flashcard(&state.pool, card_id).await? else {
+                    bot.answer_callback_query(q.id).text("Карточка удалена").await?;
+                    return Ok(());
+                };
+                let knew = *answer == "yes";
+                let prev = db::get_card_state(&state.pool, uid, card_id).await?
+                    .map(|(ease, interval_days, repetitions)| CardState { ease, interval_days, repetitions })
+                    .unwrap_or_default();
+                let next = flashcards::schedule(prev, knew);
+                let due = flashcards::due_date(Utc::now().date_naive(), &next);
+                db::save_card_review(&state.pool, uid, card_id, &next, &due.format("%Y-%m-%d").to_string(), knew).await?;
+
+                bot.answer_callback_query(q.id).await?;
+                bot.edit_message_text(q.from.id, msg_id, format!(
+                    "🃏 {}\n\n💡 {}\n\n{} Следующее повторение: {}",
+                    front, back, if knew { "✅" } else { "❌" }, due.format("%d.%m")
+                )).await?;
+                send_next_card(&bot, ChatId(uid), &state, uid, scope).await?;
+            }
+            _ => {
+                bot.answer_callback_query(q.id).await?;
+            }
+        }
+        return Ok(());
+    }
+
+    if data.starts_with("mock|") {
+        let parts: Vec<&str> = data.split('|').collect();
+        let id = parts.get(2).and_then(|p| p.parse::<i64>().ok()).unwrap_or(0);
+
+        if parts.get(1) == Some(&"progress") {
+            bot.answer_callback_query(q.id).await?;
+            let started = Instant::now();
+            let result = reports::generate_mock_progress(&state.pool, uid, state.mock_target_score).await;
+            state.metrics.report_generated("mock_progress", started);
+            match result {
+                Ok((text, png)) => {
+                    bot.send_message(q.from.id, text).await?;
+                    if !png.is_empty() {
+                        bot.send_photo(q.from.id, InputFile::memory(png).file_name("mock_progress.png")).await?;
+                    }
+                }
+                Err(e) => {
+                    error!("Mock progress error: {:?}", e);
+                    bot.send_message(q.from.id, "Не удалось построить график, попробуй позже.").await?;
+                }
+            }
+            return Ok(());
+        }
+
+        if parts.get(1) == Some(&"start") {
+            if db::get_active_mock_attempt(&state.pool, uid).await?.is_some() {
+                bot.answer_callback_query(q.id).text("У тебя уже идёт пробник").await?;
+                return Ok(());
+            }
+            if !db::list_open_mock_variants(&state.pool, uid).await?.iter().any(|(v, _)| *v == id) {
+                bot.answer_callback_query(q.id).text("Этот вариант недоступен").await?;
+                return Ok(());
+            }
+
+            let attempt_id = db::start_mock_attempt(&state.pool, uid, id, mock::MOCK_DURATION_MIN).await?;
+            bot.answer_callback_query(q.id).await?;
+            bot.edit_message_text(q.from.id, msg_id, format!(
+                "🧪 Пробник начат! На решение {}. Удачи!", mock::format_left(mock::MOCK_DURATION_MIN)
+            )).await?;
+            send_mock_task(&bot, ChatId(uid), &state, &dialogue, attempt_id, 1).await?;
+            return Ok(());
+        }
+
+        let owned = db::get_mock_attempt(&state.pool, id).await?;
+        if !matches!(owned, Some((owner, _, _, false)) if owner == uid) {
+            bot.answer_callback_query(q.id).text("Пробник уже завершён").await?;
+            return Ok(());
+        }
+
+        match parts.as_slice() {
+            ["mock", "go", _, n] => {
+                bot.edit_message_reply_markup(q.from.id, msg_id).await?;
+                send_mock_task(&bot, ChatId(uid), &state, &dialogue, id, n.parse().unwrap_or(1)).await?;
+            }
+            ["mock", "grid", _] => {
+                let (text, kb) = mock_grid_text(&state, id).await?;
+                bot.edit_message_text(q.from.id, msg_id, text).reply_markup(kb).await?;
+            }
+            ["mock", "finish", _] => {
+                let answered = db::get_mock_answered(&state.pool, id).await?;
+                bot.edit_message_text(q.from.id, msg_id, format!(
+                    "Завершить пробник? Отвечено {} из 27, изменить ответы после завершения нельзя.", answered.len()
+                )).reply_markup(keyboards::mock_finish_kb(id)).await?;
+            }
+            ["mock", "end", _] => {
+                dialogue.exit().await?;
+                bot.edit_message_reply_markup(q.from.id, msg_id).await?;
+                finish_mock(&bot, &state, id).await?;
+            }
+            _ => {}
+        }
+        bot.answer_callback_query(q.id).await?;
+        return Ok(());
+    }
+
+    if data.starts_with("share|") {
+        let parts: Vec<&str> = data.split('|').collect();
+        let id = parts.get(2).and_then(|p| p.parse::<i64>().ok()).unwrap_or(0);
+
+        match parts.get(1).copied().unwrap_or("") {
+            "req" => {
+                let Some(Submission { topic_title, content_type, summary, file_id, .. }) = user_submission(&state, uid, id).await? else {
+                    bot.answer_callback_query(q.id).text("Работа не найдена").await?;
+                    return Ok(());
+                };
+
+                match db::share_conspect(&state.pool, id, false).await? {
+                    Some((shared_id, status)) if status == "pending" => {
+                        send_submission_content(&bot, ChatId(state.admin_id), &content_type, summary, &file_id).await?;
+                        bot.send_message(UserId(state.admin_id as u64), format!(
+                            "🤝 @{} предлагает конспект в библиотеку: {}",
+                            q.from.username.as_deref().unwrap_or("noname"), topic_title
+                        )).reply_markup(keyboards::share_review_kb(shared_id)).await?;
+                        bot.answer_callback_query(q.id).text("Отправлено учителю на одобрение").await?;
+                    }
+                    Some((_, status)) if status == "approved" => {
+                        bot.answer_callback_query(q.id).text("Этот конспект уже в библиотеке").await?;
+                    }
+                    Some(_) => {
+                        bot.answer_callback_query(q.id).text("Учитель уже отклонил этот конспект").await?;
+                    }
+                    None => {
+                        bot.answer_callback_query(q.id).text("Поделиться можно только конспектом").await?;
+                    }
+                }
+                return Ok(());
+            }
+            "admin" if uid == state.admin_id => {
+                let text = match db::share_conspect(&state.pool, id, true).await? {
+                    Some((shared_id, _)) => {
+                        state.events.review_decided(shared_id).await;
+                        "Конспект добавлен в библиотеку"
+                    }
+                    None => "Конспект не найден",
+                };
+                bot.answer_callback_query(q.id).text(text).await?;
+                bot.edit_message_reply_markup(q.from.id, msg_id).await?;
+                return Ok(());
+            }
+            action @ ("approve" | "reject") if uid == state.admin_id => {
+                let approved = action == "approve";
+                let status = if approved { "approved" } else { "rejected" };
+
+                if let Some((author_id, topic_title)) = db::set_shared_status(&state.pool, id, status).await? {
+                    state.events.review_decided(id).await;
+                    let note = if approved {
+                        format!("🎉 Твой конспект «{}» добавлен в библиотеку!", topic_title)
+                    } else {
+                        format!("Учитель не стал добавлять конспект «{}» в библиотеку.", topic_title)
+                    };
+                    let _ = bot.send_message(UserId(author_id as u64), note).await;
+                }
+
+                bot.answer_callback_query(q.id).text(if approved { "Одобрено" } else { "Отклонено" }).await?;
+                bot.edit_message_reply_markup(q.from.id, msg_id).await?;
+                return Ok(());
+            }
+            _ => {}
+        }
+        bot.answer_callback_query(q.id).await?;
+        return Ok(());
+    }
+
+    if data.starts_with("lib|") {
+        let parts: Vec<&str> = data.split('|').collect();
+
+        match parts.as_slice() {
+            ["lib", "root"] => {
+                bot.edit_message_text(q.from.id, msg_id, "📖 Библиотека конспектов одноклассников.\nВыбери раздел:")
+                    .reply_markup(keyboards::library_sections_kb())
+                    .await?;
+            }
+            ["lib", "sec", section] => {
+                let counts = db::count_shared_by_topic(&state.pool, section).await?;
+                let text = if counts.is_empty() {
+                    format!("Раздел: {}\nПока никто не поделился конспектами.", section)
+                } else {
+                    format!("Раздел: {}\nВыбери тему:", section)
+                };
+                bot.edit_message_text(q.from.id, msg_id, text)
+                    .reply_markup(keyboards::library_topics_kb(section, &counts))
+                    .await?;
+            }
+            ["lib", "topic", section, topic_id] => {
+                let items = db::list_shared_conspects(&state.pool, section, topic_id).await?;
+                let title = keyboards::get_topic_title(section, topic_id).unwrap_or_else(|| "Тема".to_string());
+                bot.edit_message_text(q.from.id, msg_id, format!("Тема: {}\nВыбери конспект:", title))
+                    .reply_markup(keyboards::library_items_kb(section, &items))
+                    .await?;
+            }
+            ["lib", "item", id] => {
+                let id = id.parse::<i64>().unwrap_or(0);
+                if let Some((author, topic_title, content_type, summary, file_id, likes)) = db::get_shared_conspect(&state.pool, id).await? {
+                    send_submission_content(&bot, ChatId(uid), &content_type, summary, &file_id).await?;
+                    bot.send_message(q.from.id, format!("📖 {}\n✍️ Автор: {}", topic_title, author))
+                        .reply_markup(keyboards::like_kb(id, likes))
+                        .await?;
+                } else {
+                    bot.answer_callback_query(q.id).text("Конспект больше недоступен").await?;
+                    return Ok(());
+                }
+            }
+            ["lib", "like", id] => {
+                let id = id.parse::<i64>().unwrap_or(0);
+                let likes = db::toggle_conspect_like(&state.pool, id, uid).await?;
+                bot.edit_message_reply_markup(q.from.id, msg_id)
+                    .reply_markup(keyboards::like_kb(id, likes))
+                    .await?;
+            }
+            _ => {}
+        }
+        bot.answer_callback_query(q.id).await?;
+        return Ok(());
+    }
+
+    if data.starts_with("hist|") {
+        let parts: Vec<&str> = data.split('|').collect();
+        let arg = |i: usize| parts.get(i).and_then(|p| p.parse::<i64>().ok()).unwrap_or(0);
+
+        match parts.get(1).copied().unwrap_or("") {
+            "page" => {
+                let (text, kb) = history_page(&state, uid, arg(2)).await?;
+                bot.edit_message_text(q.from.id, msg_id, text).reply_markup(kb).await?;
+            }
+            "item" => {
+                let (submission_id, page) = (arg(2), arg(3));
+                let Some(sub) = user_submission(&state, uid, submission_id).await? else {
+                    bot.answer_callback_query(q.id).text("Работа не найдена").await?;
+                    return Ok(());
+                };
+
+                send_submission_content(&bot, ChatId(uid), &sub.content_type, sub.summary, &sub.file_id).await?;
+
+                let type_str = match sub.kind { SubmissionType::Dz => "ДЗ", SubmissionType::Conspect => "Конспект" };
+                let can_withdraw = sub.status == SubmissionStatus::Submitted && within_grace(&sub.ts, state.withdraw_grace_minutes);
+                let mut details = format!(
+                    "{} · {}\nСдано: {}\nСтатус: {}",
+                    type_str, sub.topic_title, sub.ts.get(..16).unwrap_or(&sub.ts).replace('T', " "),
+                    match sub.status { SubmissionStatus::Withdrawn => "отозвано", SubmissionStatus::Submitted => "принято" }
+                );
+                if can_withdraw {
+                    details.push_str(&format!("\nОтозвать можно в течение {} мин. после сдачи.", state.withdraw_grace_minutes));
+                }
+                let can_share = sub.kind == SubmissionType::Conspect && sub.status != SubmissionStatus::Withdrawn;
+                bot.send_message(q.from.id, details)
+                    .reply_markup(keyboards::history_item_kb(submission_id, page, can_withdraw, can_share))
+                    .await?;
+            }
+            "withdraw" => {
+                let (submission_id, page) = (arg(2), arg(3));
+                let sub = user_submission(&state, uid, submission_id).await?;
+                let refusal = match &sub {
+                    None => Some("Работа не найдена."),
+                    Some(s) if s.status == SubmissionStatus::Withdrawn => Some("Уже отозвано."),
+                    Some(s) if !within_grace(&s.ts, state.withdraw_grace_minutes) => Some("Срок отзыва истёк, работу уже нельзя отозвать."),
+                    Some(_) if !state.repos.submissions.withdraw(uid, submission_id).await? => Some("Уже отозвано."),
+                    Some(_) => None,
+                };
+                if let Some(text) = refusal {
+                    bot.answer_callback_query(q.id).text(text).show_alert(true).await?;
+                    return Ok(());
+                }
+                let Some(sub) = sub else { return Ok(()) };
+
+                if sub.kind == SubmissionType::Conspect {
+                    if let Err(e) = reports::withdraw_conspect_files(&state.conspects_dir, uid, submission_id).await {
+                        error!("Cannot move files of withdrawn submission {}: {:?}", submission_id, e);
+                    }
+                }
+                let type_str = match sub.kind { SubmissionType::Dz => "ДЗ", SubmissionType::Conspect => "Конспект" };
+                bot.send_message(UserId(state.admin_id as u64), format!(
+                    "↩️ @{} отозвал(а) {}: {}",
+                    q.from.username.as_deref().unwrap_or("noname"), type_str, sub.topic_title
+                )).await?;
+
+                let (text, kb) = history_page(&state, uid, page).await?;
+                bot.edit_message_text(q.from.id, msg_id, text).reply_markup(kb).await?;
+                bot.answer_callback_query(q.id).text("Работа отозвана").await?;
+                return Ok(());
+            }
+            _ => {}
+        }
+        bot.answer_callback_query(q.id).await?;
+        return Ok(());
+    }
+
     if data.starts_with("admin|") && uid == state.admin_id {
         let action = data.split('|').nth(1).unwrap_or("");
 
@@ -322,7 +1592,10 @@ pub async fn callback_handler(
             "daily_full" | "send_daily_now" => {
                 bot.answer_callback_query(&q.id).text("Генерирую отчет...").await?;
                 let date = Utc::now().format("%Y-%m-%d").to_string();
-                match reports::generate_daily_report(&state.pool, &date).await {
+                let started = Instant::now();
+                let result = reports::generate_daily_report(&state.repos, &date).await;
+                state.metrics.report_generated("daily_report", started);
+                match result {
                     Ok(excel) => {
                         bot.send_document(q.from.id, InputFile::memory(excel).file_name(format!("report_{}.xlsx", date))).await?;
                     },
@@ -335,12 +1608,26 @@ pub async fn callback_handler(
 
             "full_history_manual" => {
                 bot.answer_callback_query(&q.id).text("Это может занять время...").await?;
-                if let Ok(files) = reports::generate_full_history_package(&state.pool).await {
+                let started = Instant::now();
+                let result = reports::generate_full_history_package(&state.pool, &state.repos, state.mock_target_score).await;
+                state.metrics.report_generated("full_history", started);
+                if let Ok(files) = result {
                     for file in files {
                         bot.send_document(q.from.id, file).await?;
                     }
                 }
             }
+            "task_errors" => {
+                bot.answer_callback_query(&q.id).text("Считаю статистику...").await?;
+                if let Err(e) = send_task_error_report(&bot, ChatId(uid), &state.pool, &state.repos, &state.metrics, None).await {
+                    error!("Task error report failed: {:?}", e);
+                    bot.send_message(q.from.id, "Ошибка генерации отчета").await?;
+                }
+            }
+            "daily_board" => {
+                let text = daily::leaderboard_text(&state.pool).await?;
+                bot.send_message(q.from.id, text).await?;
+            }
             "export_user" => {
                 dialogue.update(DialogueState::AdminWaitingForExportUser).await?;
                 bot.send_message(q.from.id, "Пришли ID или @username пользователя:").await?;
@@ -349,8 +1636,114 @@ pub async fn callback_handler(
                 dialogue.update(DialogueState::AdminWaitingForDeleteUser).await?;
                 bot.send_message(q.from.id, "Пришли ID или @username для УДАЛЕНИЯ:").await?;
             }
+            "task_add" => {
+                dialogue.update(DialogueState::AdminWaitingForTaskNumber).await?;
+                bot.send_message(q.from.id, "Пришли номер задания ЕГЭ (1–27):").await?;
+            }
+            "task_import" => {
+                dialogue.update(DialogueState::AdminWaitingForTaskImport).await?;
+                bot.send_message(q.from.id,
+                    "Пришли таблицу .xlsx или .csv. Первая строка — заголовки:\n\
+                     номер; вариант; условие; ответ; проверка; файлы\n\n\
+                     «проверка» — exact, set или seq (необязательно), «файлы» — имена вложений через «;».\n\
+                     Вложения пришли отдельными документами до таблицы. Повторный импорт обновляет задачи по паре номер+вариант."
+                ).await?;
+            }
+            "mock_publish" => {
+                dialogue.update(DialogueState::AdminWaitingForMockVariant).await?;
+                bot.send_message(q.from.id,
+                    "Пришли название варианта (значение колонки «вариант» из импорта).\n\
+                     В банке должны быть все 27 заданий этого варианта. Повторная публикация обновит состав."
+                ).await?;
+            }
+            "code_tests" => {
+                dialogue.update(DialogueState::AdminWaitingForCodeTests).await?;
+                bot.send_message(q.from.id,
+                    "Пришли тесты сообщением или .txt файлом. Первая строка — ID темы (op3, ege24 …), дальше блоки:\n\
+                     --- ввод\n5\n--- вывод\n25\n\n\
+                     Каждая пара «ввод/вывод» — один тест. Повторная отправка заменяет тесты темы."
+                ).await?;
+            }
+            "cards" => {
+                dialogue.update(DialogueState::AdminWaitingForCards).await?;
+                bot.send_message(q.from.id,
+                    "Пришли карточки одним сообщением. Первая строка — ID темы (op3, ege24 …), дальше по карточке на строку:\n\
+                     вопрос :: ответ\n\n\
+                     Карточки добавляются к уже существующим."
+                ).await?;
+            }
+            "quiz" => {
+                dialogue.update(DialogueState::AdminWaitingForQuiz).await?;
+                bot.send_message(q.from.id,
+                    "Пришли квиз одним сообщением. Первая строка — ID темы (op3, ege24 …), дальше вопросы:\n\
+                     ? Текст вопроса\n+ верный вариант\n- неверный вариант\n\n\
+                     У каждого вопроса 2–10 вариантов и ровно один «+». Квиз сразу уйдёт всем ученикам."
+                ).await?;
+            }
+            "groups" => {
+                dialogue.update(DialogueState::AdminWaitingForGroups).await?;
+                bot.send_message(q.from.id,
+                    "Пришли ученика и группу, по одному на строку:\n\
+                     @username 11А\n123456789 11Б\n\n\
+                     Без названия группы ученик из неё убирается."
+                ).await?;
+            }
+            "teacher_scores" => {
+                dialogue.update(DialogueState::AdminWaitingForTeacherScores).await?;
+                bot.send_message(q.from.id,
+                    "Пришли оценки за задания завершённых пробников, по одной на строку:\n\
+                     номер попытки, номер задания, баллы — например «12 27 1».\n\n\
+                     Оценка учителя заменяет автопроверку, первичный и тестовый балл пересчитываются."
+                ).await?;
+            }
+            "task_bank" => {
+                let counts = db::count_tasks_by_number(&state.pool).await?;
+                let text = if counts.is_empty() {
+                    "Банк задач пока пуст.".to_string()
+                } else {
+                    let lines: Vec<String> = counts.iter().map(|(n, c)| format!("Задание {}: {} шт.", n, c)).collect();
+                    format!("📚 Банк задач:\n{}", lines.join("\n"))
+                };
+                bot.send_message(q.from.id, text).await?;
+            }
+            "backups" => {
+                let backups = state.backups.list().await?;
+                let mut text = if !state.backups.enabled() {
+                    "Бэкапы доступны только для SQLite. Для Postgres используй pg_dump.".to_string()
+                } else if backups.is_empty() {
+                    "Бэкапов пока нет.".to_string()
+                } else {
+                    let lines: Vec<String> = backups.iter().take(15)
+                        .map(|b| format!("{} — {}", b.name, b.size_text()))
+                        .collect();
+                    format!("💾 Бэкапы ({} шт.):\n{}", backups.len(), lines.join("\n"))
+                };
+                if state.backups.enabled() {
+                    text.push_str("\n\nВосстановление: останови бота и выполни\nhomework_bot restore-backup <имя>");
+                    bot.send_message(q.from.id, text).reply_markup(keyboards::backups_kb()).await?;
+                } else {
+                    bot.send_message(q.from.id, text).await?;
+                }
+            }
+            "backup_now" => {
+                bot.answer_callback_query(&q.id).text("Делаю бэкап...").await?;
+                match state.backups.create("manual").await {
+                    Ok(backup) => {
+                        bot.send_message(q.from.id, format!("✅ Бэкап {} готов ({}).", backup.name, backup.size_text())).await?;
+                    }
+                    Err(e) => {
+                        error!("Manual backup failed: {:?}", e);
+                        bot.send_message(q.from.id, "Не удалось сделать бэкап.").await?;
+                    }
+                }
+            }
             "reset_all" => {
-                db::reset_database(&state.pool).await?;
+                if let Err(e) = state.backups.snapshot_before("reset").await {
+                    error!("Backup before reset failed: {:?}", e);
+                    bot.answer_callback_query(&q.id).text("Не удалось сделать резервную копию, сброс отменён.").show_alert(true).await?;
+                    return Ok(());
+                }
+                state.repos.users.remove_all().await?;
                 let _ = tokio::fs::remove_dir_all(&state.conspects_dir).await;
                 let _ = tokio::fs::create_dir_all(&state.conspects_dir).await;
                 bot.answer_callback_query(&q.id).text("База сброшена!").show_alert(true).await?;
@@ -361,4 +1754,15 @@ pub async fn callback_handler(
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+pub async fn poll_answer_handler(answer: PollAnswer, state: AppState) -> HandlerResult {
+    let Some(option_id) = answer.option_ids.first() else {
+        return Ok(());
+    };
+    let uid = answer.user.id.0 as i64;
+    if !db::answer_quiz_poll(&state.pool, &answer.poll_id, uid, *option_id as i64).await? {
+        info!("Poll answer {} from {} ignored", answer.poll_id, uid);
+    }
+    Ok(())
+}
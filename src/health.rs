@@ -0,0 +1,162 @@
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::{Duration, Instant};
+
+use chrono::Utc;
+use dashmap::DashMap;
+use serde_json::{json, Value};
+use warp::filters::BoxedFilter;
+use warp::http::StatusCode;
+use warp::reply::Response;
+use warp::{Filter, Reply};
+
+use crate::AppState;
+
+const DB_TIMEOUT: Duration = Duration::from_secs(2);
+const MEDIA_BACKLOG_MAX_AGE_SECS: i64 = 60;
+const OUTBOUND_MAX_DEPTH: i64 = 500;
+
+struct JobBeat {
+    interval_secs: i64,
+    last_tick: i64,
+}
+
+pub struct Health {
+    started: i64,
+    jobs: DashMap<&'static str, JobBeat>,
+    outbound: AtomicI64,
+}
+
+pub struct OutboundBatch<'a> {
+    health: &'a Health,
+    left: i64,
+}
+
+impl OutboundBatch<'_> {
+    pub fn sent(&mut self) {
+        if self.left > 0 {
+            self.left -= 1;
+            self.health.outbound.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Drop for OutboundBatch<'_> {
+    fn drop(&mut self) {
+        self.health.outbound.fetch_sub(self.left, Ordering::Relaxed);
+    }
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Health {
+    pub fn new() -> Self {
+        Health { started: Utc::now().timestamp(), jobs: DashMap::new(), outbound: AtomicI64::new(0) }
+    }
+
+    pub fn register_job(&self, name: &'static str, interval_secs: i64) {
+        self.jobs.insert(name, JobBeat { interval_secs, last_tick: Utc::now().timestamp() });
+    }
+
+    pub fn tick(&self, name: &'static str) {
+        if let Some(mut beat) = self.jobs.get_mut(name) {
+            beat.last_tick = Utc::now().timestamp();
+        }
+    }
+
+    pub fn outbound(&self, messages: usize) -> OutboundBatch<'_> {
+        self.outbound.fetch_add(messages as i64, Ordering::Relaxed);
+        OutboundBatch { health: self, left: messages as i64 }
+    }
+
+    pub fn outbound_depth(&self) -> i64 {
+        self.outbound.load(Ordering::Relaxed)
+    }
+
+    pub fn uptime_secs(&self) -> i64 {
+        Utc::now().timestamp() - self.started
+    }
+}
+
+fn status_str(ok: bool) -> &'static str {
+    if ok { "ok" } else { "degraded" }
+}
+
+async fn check_database(state: &AppState) -> (bool, Value) {
+    let started = Instant::now();
+    let probe = sqlx::query_scalar::<_, i64>("SELECT 1").fetch_one(&state.pool);
+    match tokio::time::timeout(DB_TIMEOUT, probe).await {
+        Ok(Ok(_)) => (true, json!({ "status": "ok", "latency_ms": started.elapsed().as_millis() as u64 })),
+        Ok(Err(e)) => (false, json!({ "status": "degraded", "error": e.to_string() })),
+        Err(_) => (false, json!({ "status": "degraded", "error": "timeout" })),
+    }
+}
+
+fn check_scheduler(state: &AppState) -> (bool, Value) {
+    let now = Utc::now().timestamp();
+    let mut all_ok = true;
+    let mut jobs = serde_json::Map::new();
+    for beat in state.health.jobs.iter() {
+        let age = now - beat.last_tick;
+        let ok = age <= beat.interval_secs + (beat.interval_secs / 10).max(60);
+        all_ok &= ok;
+        jobs.insert(beat.key().to_string(), json!({
+            "status": status_str(ok),
+            "last_tick": chrono::DateTime::from_timestamp(beat.last_tick, 0).map(|d| d.to_rfc3339()),
+            "age_secs": age,
+            "interval_secs": beat.interval_secs,
+        }));
+    }
+    (all_ok, json!({ "status": status_str(all_ok), "jobs": jobs }))
+}
+
+fn check_media_groups(state: &AppState) -> (bool, Value) {
+    let now = Utc::now().timestamp();
+    let oldest = state.media_groups.iter().map(|r| now - r.value().2).max().unwrap_or(0);
+    let ok = oldest <= MEDIA_BACKLOG_MAX_AGE_SECS;
+    (ok, json!({ "status": status_str(ok), "pending": state.media_groups.len(), "oldest_age_secs": oldest }))
+}
+
+fn check_outbound(state: &AppState) -> (bool, Value) {
+    let depth = state.health.outbound_depth();
+    let ok = depth <= OUTBOUND_MAX_DEPTH;
+    (ok, json!({ "status": status_str(ok), "depth": depth }))
+}
+
+async fn readiness(state: AppState) -> Result<Response, warp::Rejection> {
+    let (db_ok, database) = check_database(&state).await;
+    let (sched_ok, scheduler) = check_scheduler(&state);
+    let (media_ok, media_groups) = check_media_groups(&state);
+    let (out_ok, outbound) = check_outbound(&state);
+
+    let ok = db_ok && sched_ok && media_ok && out_ok;
+    let body = json!({
+        "status": status_str(ok),
+        "uptime_secs": state.health.uptime_secs(),
+        "components": {
+            "database": database,
+            "scheduler": scheduler,
+            "media_groups": media_groups,
+            "outbound_queue": outbound,
+        },
+    });
+    let code = if ok { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    Ok(warp::reply::with_status(warp::reply::json(&body), code).into_response())
+}
+
+pub fn routes(state: AppState) -> BoxedFilter<(Response,)> {
+    let health = state.health.clone();
+    let healthz = warp::path!("healthz")
+        .and(warp::get())
+        .map(move || warp::reply::json(&json!({ "status": "ok", "uptime_secs": health.uptime_secs() })).into_response());
+
+    let readyz = warp::path!("readyz")
+        .and(warp::get())
+        .and(warp::any().map(move || state.clone()))
+        .and_then(readiness);
+
+    healthz.or(readyz).unify().boxed()
+}
@@ -0,0 +1,266 @@
+use std::io::Cursor;
+use std::path::Path;
+
+use calamine::{open_workbook_from_rs, Reader, Xlsx};
+
+use crate::db::{self, DbPool};
+use crate::tasks::{self, AnswerKind};
+
+const MAX_REPORTED_ERRORS: usize = 30;
+
+pub struct ImportReport {
+    pub inserted: usize,
+    pub updated: usize,
+    pub errors: Vec<(usize, String)>,
+}
+
+impl ImportReport {
+    pub fn summary(&self) -> String {
+        let mut text = format!(
+            "Импорт завершён.\nДобавлено: {}\nОбновлено: {}\nОшибок: {}",
+            self.inserted, self.updated, self.errors.len()
+        );
+        for (row, err) in self.errors.iter().take(MAX_REPORTED_ERRORS) {
+            text.push_str(&format!("\nСтрока {}: {}", row, err));
+        }
+        if self.errors.len() > MAX_REPORTED_ERRORS {
+            text.push_str(&format!("\n…и ещё {} ошибок", self.errors.len() - MAX_REPORTED_ERRORS));
+        }
+        text
+    }
+}
+
+struct TaskRow {
+    ege_number: i64,
+    variant: String,
+    statement: String,
+    answer: String,
+    kind: AnswerKind,
+    attachments: Vec<String>,
+}
+
+#[derive(Default)]
+struct Columns {
+    number: Option<usize>,
+    variant: Option<usize>,
+    statement: Option<usize>,
+    answer: Option<usize>,
+    kind: Option<usize>,
+    files: Option<usize>,
+}
+
+impl Columns {
+    fn from_header(header: &[String]) -> anyhow::Result<Self> {
+        let mut cols = Columns::default();
+        for (i, name) in header.iter().enumerate() {
+            match name.trim().to_lowercase().as_str() {
+                "номер" | "задание" | "number" | "task" => cols.number = Some(i),
+                "вариант" | "variant" => cols.variant = Some(i),
+                "условие" | "текст" | "statement" | "text" => cols.statement = Some(i),
+                "ответ" | "answer" => cols.answer = Some(i),
+                "проверка" | "тип" | "kind" | "answer_kind" => cols.kind = Some(i),
+                "файлы" | "вложения" | "files" | "attachments" => cols.files = Some(i),
+                _ => {}
+            }
+        }
+
+        if cols.number.is_none() || cols.variant.is_none() || cols.answer.is_none() {
+            return Err(anyhow::anyhow!(
+                "В первой строке нужны колонки «номер», «вариант» и «ответ» (можно также «условие», «проверка», «файлы»)"
+            ));
+        }
+        Ok(cols)
+    }
+}
+
+fn read_rows(file_name: &str, data: &[u8]) -> anyhow::Result<Vec<Vec<String>>> {
+    let ext = Path::new(file_name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+
+    match ext.as_str() {
+        "xlsx" => {
+            let mut workbook: Xlsx<_> = open_workbook_from_rs(Cursor::new(data))?;
+            let range = workbook.worksheet_range_at(0)
+                .ok_or_else(|| anyhow::anyhow!("В файле нет листов"))??;
+            Ok(range.rows()
+                .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+                .collect())
+        }
+        "csv" => {
+            let text = String::from_utf8_lossy(data);
+            let text = text.trim_start_matches('\u{feff}');
+            let first_line = text.lines().next().unwrap_or("");
+            let delimiter = if first_line.matches(';').count() > first_line.matches(',').count() { b';' } else { b',' };
+
+            let mut reader = csv::ReaderBuilder::new()
+                .has_headers(false)
+                .flexible(true)
+                .delimiter(delimiter)
+                .from_reader(text.as_bytes());
+
+            let mut rows = Vec::new();
+            for record in reader.records() {
+                rows.push(record?.iter().map(|c| c.to_string()).collect());
+            }
+            Ok(rows)
+        }
+        _ => Err(anyhow::anyhow!("Поддерживаются только файлы .xlsx и .csv")),
+    }
+}
+
+fn parse_row(row: &[String], cols: &Columns, files_dir: &str) -> Result<TaskRow, String> {
+    let cell = |idx: Option<usize>| idx.and_then(|i| row.get(i)).map(|c| c.trim().to_string()).unwrap_or_default();
+
+    let number_raw = cell(cols.number);
+    let ege_number = number_raw.replace(',', ".").parse::<f64>()
+        .ok()
+        .filter(|n| n.fract() == 0.0 && (1.0..=27.0).contains(n))
+        .map(|n| n as i64)
+        .ok_or_else(|| format!("номер задания «{}» должен быть целым от 1 до 27", number_raw))?;
+
+    let variant = cell(cols.variant);
+    if variant.is_empty() {
+        return Err("не указан вариант".to_string());
+    }
+
+    let answer = cell(cols.answer);
+    let kind = match cell(cols.kind) {
+        k if k.is_empty() => AnswerKind::default_for(ege_number),
+        k => AnswerKind::parse(&k).ok_or_else(|| format!("неизвестный тип проверки «{}» (exact, set или seq)", k))?,
+    };
+    if tasks::normalize(&answer, kind).is_empty() {
+        return Err("пустой ответ".to_string());
+    }
+
+    let attachments: Vec<String> = cell(cols.files)
+        .split([';', ','])
+        .map(|f| f.trim())
+        .filter(|f| !f.is_empty())
+        .map(|f| f.to_string())
+        .collect();
+
+    for name in attachments.iter() {
+        let safe = Path::new(name).file_name().and_then(|n| n.to_str()) == Some(name.as_str());
+        if !safe || !Path::new(files_dir).join(name).is_file() {
+            return Err(format!("файл «{}» не найден в папке вложений", name));
+        }
+    }
+
+    let statement = cell(cols.statement);
+    if statement.is_empty() && attachments.is_empty() {
+        return Err("нет ни условия, ни файлов".to_string());
+    }
+
+    Ok(TaskRow { ege_number, variant, statement, answer, kind, attachments })
+}
+
+pub async fn import_tasks(pool: &DbPool, file_name: &str, data: &[u8], files_dir: &str) -> anyhow::Result<ImportReport> {
+    let rows = read_rows(file_name, data)?;
+    let header = rows.first().ok_or_else(|| anyhow::anyhow!("Файл пустой"))?;
+    let cols = Columns::from_header(header)?;
+
+    let mut report = ImportReport { inserted: 0, updated: 0, errors: Vec::new() };
+    let mut tx = pool.begin().await?;
+
+    for (i, row) in rows.iter().enumerate().skip(1) {
+        if row.iter().all(|c| c.trim().is_empty()) {
+            continue;
+        }
+
+        let line = i + 1;
+        match parse_row(row, &cols, files_dir) {
+            Ok(task) => {
+                let inserted = db::upsert_task(
+                    &mut tx, task.ege_number, &task.variant, &task.statement,
+                    &task.answer, task.kind, &task.attachments.join(";")
+                ).await
+                    .map_err(|e| anyhow::anyhow!("строка {}: ошибка БД ({}), ни одна задача не сохранена", line, e))?;
+                if inserted {
+                    report.inserted += 1;
+                } else {
+                    report.updated += 1;
+                }
+            }
+            Err(e) => report.errors.push((line, e)),
+        }
+    }
+
+    tx.commit().await?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(cells: &[&str]) -> Vec<String> {
+        cells.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn csv_delimiter_follows_the_header_line() {
+        let rows = read_rows("bank.CSV", "\u{feff}номер;вариант;условие;ответ\n5;A;\"x, y, z\";12\n".as_bytes()).unwrap();
+        assert_eq!(rows, vec![strings(&["номер", "вариант", "условие", "ответ"]), strings(&["5", "A", "x, y, z", "12"])]);
+
+        let rows = read_rows("bank.csv", b"number,variant,answer\n7,B,1;2\n").unwrap();
+        assert_eq!(rows[1], strings(&["7", "B", "1;2"]));
+
+        assert!(read_rows("bank.txt", b"number,variant,answer").is_err());
+    }
+
+    #[test]
+    fn header_accepts_russian_and_english_names() {
+        let cols = Columns::from_header(&strings(&["Задание", " Variant ", "Текст", "ANSWER", "тип", "вложения"])).unwrap();
+        assert_eq!(
+            (cols.number, cols.variant, cols.statement, cols.answer, cols.kind, cols.files),
+            (Some(0), Some(1), Some(2), Some(3), Some(4), Some(5))
+        );
+        assert!(Columns::from_header(&strings(&["номер", "вариант", "условие"])).is_err());
+    }
+
+    #[test]
+    fn rows_need_a_variant_and_a_valid_number() {
+        let cols = Columns::from_header(&strings(&["номер", "вариант", "условие", "ответ", "проверка"])).unwrap();
+
+        let task = parse_row(&strings(&["26.0", " A1 ", "Найди", "5 120", ""]), &cols, "").unwrap();
+        assert_eq!((task.ege_number, task.variant.as_str(), task.kind), (26, "A1", AnswerKind::Sequence));
+
+        assert_eq!(parse_row(&strings(&["3", " ", "Найди", "5"]), &cols, "").err().unwrap(), "не указан вариант");
+        assert!(parse_row(&strings(&["28", "A", "Найди", "5"]), &cols, "").err().unwrap().contains("от 1 до 27"));
+        assert!(parse_row(&strings(&["3", "A", "Найди", "5", "fuzzy"]), &cols, "").err().unwrap().contains("fuzzy"));
+        assert_eq!(parse_row(&strings(&["3", "A", "Найди", " "]), &cols, "").err().unwrap(), "пустой ответ");
+    }
+
+    #[test]
+    fn attachments_must_be_plain_names_inside_the_files_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.png"), b"x").unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        std::fs::write(dir.path().join("sub").join("b.png"), b"x").unwrap();
+        let files_dir = dir.path().join("sub").to_str().unwrap().to_string();
+        let cols = Columns::from_header(&strings(&["номер", "вариант", "ответ", "файлы"])).unwrap();
+        let row = |files: &str| parse_row(&strings(&["3", "A", "5", files]), &cols, &files_dir);
+
+        assert_eq!(row("b.png; b.png").unwrap().attachments, strings(&["b.png", "b.png"]));
+        for bad in ["../a.png", "/etc/passwd", "sub/b.png", "c.png"] {
+            assert!(row(bad).err().unwrap().contains("не найден"), "{} accepted", bad);
+        }
+        assert_eq!(row("").err().unwrap(), "нет ни условия, ни файлов");
+    }
+
+    #[tokio::test]
+    async fn second_import_updates_the_same_tasks() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = db::init_db(&format!("sqlite:{}", dir.path().join("bot.db").display())).await.unwrap();
+        let csv = "номер;вариант;условие;ответ\n1;A;Первое;5\n2;A;Второе;6\n30;A;Лишнее;7\n".as_bytes();
+
+        let first = import_tasks(&pool, "bank.csv", csv, "").await.unwrap();
+        assert_eq!((first.inserted, first.updated, first.errors.len()), (2, 0, 1));
+        let second = import_tasks(&pool, "bank.csv", csv, "").await.unwrap();
+        assert_eq!((second.inserted, second.updated), (0, 2));
+        assert_eq!(first.errors[0].0, 4);
+    }
+}
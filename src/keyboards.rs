@@ -1,9 +1,17 @@
 use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup, KeyboardButton, KeyboardMarkup};
 
+use crate::repo::{Submission, SubmissionStatus};
+use crate::states::SubmissionType;
+
+pub const SECTIONS: [&str; 2] = ["Основы Питона", "ЕГЭ 1-27"];
+
 pub fn main_kb(is_admin: bool) -> KeyboardMarkup {
     let mut rows = vec![
         vec![KeyboardButton::new("📚 Сдать ДЗ"), KeyboardButton::new("📘 Сдать конспект")],
-        vec![KeyboardButton::new("📁 Мои конспекты"), KeyboardButton::new("📌 Главное меню")],
+        vec![KeyboardButton::new("📁 Мои конспекты"), KeyboardButton::new("📈 Моя статистика")],
+        vec![KeyboardButton::new("🗂️ История сдач"), KeyboardButton::new("📖 Библиотека")],
+        vec![KeyboardButton::new("🎯 Задачи ЕГЭ"), KeyboardButton::new("🧪 Пробник")],
+        vec![KeyboardButton::new("🃏 Карточки"), KeyboardButton::new("📌 Главное меню")],
     ];
     if is_admin {
         rows.push(vec![KeyboardButton::new("🛠️ Админ-панель")]);
@@ -12,19 +20,16 @@ pub fn main_kb(is_admin: bool) -> KeyboardMarkup {
 }
 
 pub fn sections_kb() -> InlineKeyboardMarkup {
-    let sections = vec!["Основы Питона", "ЕГЭ 1-27"];
     let mut buttons = vec![];
-    for sec in sections {
+    for sec in SECTIONS {
         buttons.push(vec![InlineKeyboardButton::callback(sec, format!("sec|{}", sec))]);
     }
     buttons.push(vec![InlineKeyboardButton::callback("Отмена", "cancel")]);
     InlineKeyboardMarkup::new(buttons)
 }
 
-pub fn topics_kb(section: &str) -> InlineKeyboardMarkup {
-    let mut buttons = vec![];
-
-    let topics = match section {
+pub fn section_topics(section: &str) -> Vec<(String, String)> {
+    match section {
         "Основы Питона" => vec![
             ("op1", "Вводный урок"),
             ("op2", "Условия и операторы"),
@@ -33,63 +38,258 @@ pub fn topics_kb(section: &str) -> InlineKeyboardMarkup {
             ("op5", "Практика: циклы"),
             ("op6", "Строки и срезы"),
             ("op7", "Списки")
-        ],
-        "ЕГЭ 1-27" => {
-            let mut t = Vec::new();
-            for i in 1..=27 {
-                t.push((format!("ege{}", i), format!("Задание {}", i)));
-            }
-            return InlineKeyboardMarkup::new(
-                t.into_iter().map(|(id, title)|
-                    vec![InlineKeyboardButton::callback(title, format!("topic|{}|{}", section, id))]
-                ).chain(std::iter::once(vec![InlineKeyboardButton::callback("Отмена", "cancel")]))
-            );
-        },
+        ].into_iter().map(|(id, title)| (id.to_string(), title.to_string())).collect(),
+        "ЕГЭ 1-27" => (1..=27)
+            .map(|i| (format!("ege{}", i), format!("Задание {}", i)))
+            .collect(),
         _ => vec![]
-    };
+    }
+}
+
+pub fn topics_kb(section: &str) -> InlineKeyboardMarkup {
+    let mut buttons = vec![];
 
-    for (id, title) in topics {
+    for (id, title) in section_topics(section) {
         buttons.push(vec![InlineKeyboardButton::callback(title, format!("topic|{}|{}", section, id))]);
     }
     buttons.push(vec![InlineKeyboardButton::callback("Отмена", "cancel")]);
     InlineKeyboardMarkup::new(buttons)
 }
 
+pub fn conspects_kb() -> InlineKeyboardMarkup {
+    let mut buttons = vec![vec![InlineKeyboardButton::callback("📦 Все конспекты", "cdl|all")]];
+    for sec in SECTIONS {
+        buttons.push(vec![InlineKeyboardButton::callback(sec, format!("cdl|sec|{}", sec))]);
+    }
+    buttons.push(vec![InlineKeyboardButton::callback("📅 За период", "cdl|range")]);
+    buttons.push(vec![InlineKeyboardButton::callback("Отмена", "cancel")]);
+    InlineKeyboardMarkup::new(buttons)
+}
+
+pub fn conspect_topics_kb(section: &str) -> InlineKeyboardMarkup {
+    let mut buttons = vec![vec![
+        InlineKeyboardButton::callback("📦 Весь раздел", format!("cdl|get|{}", section)),
+        InlineKeyboardButton::callback("📄 PDF", format!("cpdf|{}", section)),
+    ]];
+    for (id, title) in section_topics(section) {
+        buttons.push(vec![
+            InlineKeyboardButton::callback(title, format!("cdl|topic|{}|{}", section, id)),
+            InlineKeyboardButton::callback("📄 PDF", format!("cpdf|{}|{}", section, id)),
+        ]);
+    }
+    buttons.push(vec![InlineKeyboardButton::callback("Отмена", "cancel")]);
+    InlineKeyboardMarkup::new(buttons)
+}
+
+pub fn history_kb(items: &[Submission], page: i64, total_pages: i64) -> InlineKeyboardMarkup {
+    let mut buttons = vec![];
+
+    for sub in items {
+        let type_str = match sub.kind { SubmissionType::Dz => "ДЗ", SubmissionType::Conspect => "Конспект" };
+        let status_str = match sub.status { SubmissionStatus::Withdrawn => "↩️", SubmissionStatus::Submitted => "✅" };
+        buttons.push(vec![InlineKeyboardButton::callback(
+            format!("{} {} · {} · {}", status_str, sub.date, type_str, sub.topic_title),
+            format!("hist|item|{}|{}", sub.id, page)
+        )]);
+    }
+
+    let mut nav = vec![];
+    if page > 0 {
+        nav.push(InlineKeyboardButton::callback("⬅️", format!("hist|page|{}", page - 1)));
+    }
+    if total_pages > 1 {
+        nav.push(InlineKeyboardButton::callback(format!("{}/{}", page + 1, total_pages), "hist|noop"));
+    }
+    if page + 1 < total_pages {
+        nav.push(InlineKeyboardButton::callback("➡️", format!("hist|page|{}", page + 1)));
+    }
+    if !nav.is_empty() {
+        buttons.push(nav);
+    }
+    buttons.push(vec![InlineKeyboardButton::callback("Закрыть", "cancel")]);
+    InlineKeyboardMarkup::new(buttons)
+}
+
+pub fn history_item_kb(submission_id: i64, page: i64, can_withdraw: bool, can_share: bool) -> InlineKeyboardMarkup {
+    let mut buttons = vec![];
+    if can_withdraw {
+        buttons.push(vec![InlineKeyboardButton::callback("↩️ Отозвать", format!("hist|withdraw|{}|{}", submission_id, page))]);
+    }
+    if can_share {
+        buttons.push(vec![InlineKeyboardButton::callback("🤝 Поделиться в библиотеке", format!("share|req|{}", submission_id))]);
+    }
+    buttons.push(vec![InlineKeyboardButton::callback("⬅️ К списку", format!("hist|page|{}", page))]);
+    InlineKeyboardMarkup::new(buttons)
+}
+
+pub fn library_sections_kb() -> InlineKeyboardMarkup {
+    let mut buttons = vec![];
+    for sec in SECTIONS {
+        buttons.push(vec![InlineKeyboardButton::callback(sec, format!("lib|sec|{}", sec))]);
+    }
+    buttons.push(vec![InlineKeyboardButton::callback("Отмена", "cancel")]);
+    InlineKeyboardMarkup::new(buttons)
+}
+
+pub fn library_topics_kb(section: &str, counts: &[(String, i64)]) -> InlineKeyboardMarkup {
+    let mut buttons = vec![];
+    for (id, title) in section_topics(section) {
+        if let Some((_, count)) = counts.iter().find(|(topic_id, _)| *topic_id == id) {
+            buttons.push(vec![InlineKeyboardButton::callback(
+                format!("{} ({})", title, count),
+                format!("lib|topic|{}|{}", section, id)
+            )]);
+        }
+    }
+    buttons.push(vec![InlineKeyboardButton::callback("⬅️ К разделам", "lib|root")]);
+    InlineKeyboardMarkup::new(buttons)
+}
+
+pub fn library_items_kb(section: &str, items: &[(i64, String, i64)]) -> InlineKeyboardMarkup {
+    let mut buttons = vec![];
+    for (id, author, likes) in items {
+        buttons.push(vec![InlineKeyboardButton::callback(
+            format!("✍️ {} · ❤️ {}", author, likes),
+            format!("lib|item|{}", id)
+        )]);
+    }
+    buttons.push(vec![InlineKeyboardButton::callback("⬅️ К темам", format!("lib|sec|{}", section))]);
+    InlineKeyboardMarkup::new(buttons)
+}
+
+pub fn like_kb(shared_id: i64, likes: i64) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback(format!("❤️ {}", likes), format!("lib|like|{}", shared_id))
+    ]])
+}
+
+pub fn share_review_kb(shared_id: i64) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback("✅ Одобрить", format!("share|approve|{}", shared_id)),
+        InlineKeyboardButton::callback("❌ Отклонить", format!("share|reject|{}", shared_id)),
+    ]])
+}
+
+pub fn admin_share_kb(submission_id: i64) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback("🤝 В библиотеку", format!("share|admin|{}", submission_id))
+    ]])
+}
+
+pub fn task_next_kb(ege_number: i64) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(vec![
+        vec![InlineKeyboardButton::callback("🎯 Ещё задачу", format!("task|next|{}", ege_number))],
+        vec![InlineKeyboardButton::callback("Другое задание", "task|topics")],
+    ])
+}
+
+pub fn daily_answer_kb() -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(vec![vec![InlineKeyboardButton::callback("✍️ Ответить", "daily|answer")]])
+}
+
+pub fn flashcard_topics_kb(topics: &[(String, String, i64, i64)]) -> InlineKeyboardMarkup {
+    let mut buttons: Vec<Vec<InlineKeyboardButton>> = topics.iter()
+        .map(|(section, topic_id, total, due)| {
+            let title = get_topic_title(section, topic_id).unwrap_or_else(|| topic_id.clone());
+            vec![InlineKeyboardButton::callback(
+                format!("{} — {} из {}", title, due, total),
+                format!("fc|topic|{}", topic_id)
+            )]
+        })
+        .collect();
+    buttons.push(vec![InlineKeyboardButton::callback("🔀 Все темы", "fc|topic|")]);
+    buttons.push(vec![InlineKeyboardButton::callback("Отмена", "cancel")]);
+    InlineKeyboardMarkup::new(buttons)
+}
+
+pub fn flashcard_kb(card_id: i64, scope: &str) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback("✅ Знаю", format!("fc|yes|{}|{}", card_id, scope)),
+        InlineKeyboardButton::callback("❌ Не знаю", format!("fc|no|{}|{}", card_id, scope)),
+    ]])
+}
+
+pub fn mock_variants_kb(variants: &[(i64, String)]) -> InlineKeyboardMarkup {
+    let mut buttons: Vec<Vec<InlineKeyboardButton>> = variants.iter()
+        .map(|(id, title)| vec![InlineKeyboardButton::callback(format!("🧪 {}", title), format!("mock|start|{}", id))])
+        .collect();
+    buttons.push(vec![InlineKeyboardButton::callback("📈 Мои результаты", "mock|progress")]);
+    buttons.push(vec![InlineKeyboardButton::callback("Отмена", "cancel")]);
+    InlineKeyboardMarkup::new(buttons)
+}
+
+pub fn mock_grid_kb(attempt_id: i64, answered: &[i64]) -> InlineKeyboardMarkup {
+    let cells: Vec<InlineKeyboardButton> = (1..=27)
+        .map(|n| {
+            let label = if answered.contains(&n) { format!("{}✓", n) } else { n.to_string() };
+            InlineKeyboardButton::callback(label, format!("mock|go|{}|{}", attempt_id, n))
+        })
+        .collect();
+
+    let mut buttons: Vec<Vec<InlineKeyboardButton>> = cells.chunks(6).map(|c| c.to_vec()).collect();
+    buttons.push(vec![InlineKeyboardButton::callback("🏁 Завершить", format!("mock|finish|{}", attempt_id))]);
+    InlineKeyboardMarkup::new(buttons)
+}
+
+pub fn mock_task_kb(attempt_id: i64) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback("🔢 Все задания", format!("mock|grid|{}", attempt_id)),
+        InlineKeyboardButton::callback("🏁 Завершить", format!("mock|finish|{}", attempt_id)),
+    ]])
+}
+
+pub fn mock_finish_kb(attempt_id: i64) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback("✅ Да, завершить", format!("mock|end|{}", attempt_id)),
+        InlineKeyboardButton::callback("↩️ Вернуться", format!("mock|grid|{}", attempt_id)),
+    ]])
+}
+
 pub fn admin_kb() -> InlineKeyboardMarkup {
     let buttons = vec![
         vec![InlineKeyboardButton::callback("📋 Дневной отчёт", "admin|daily_full")],
         vec![InlineKeyboardButton::callback("📤 Выслать сейчас", "admin|send_daily_now")],
         vec![InlineKeyboardButton::callback("📊 Полная история", "admin|full_history_manual")],
+        vec![
+            InlineKeyboardButton::callback("🔥 Ошибки по заданиям", "admin|task_errors"),
+            InlineKeyboardButton::callback("🏆 Задача дня", "admin|daily_board"),
+        ],
         vec![InlineKeyboardButton::callback("👤 Выгрузить ученика", "admin|export_user")],
         vec![InlineKeyboardButton::callback("🗑️ Удалить ученика", "admin|delete_user")],
-        vec![InlineKeyboardButton::callback("♻️ Сброс базы", "admin|reset_all")],
+        vec![
+            InlineKeyboardButton::callback("📝 Добавить задачу", "admin|task_add"),
+            InlineKeyboardButton::callback("📚 Банк задач", "admin|task_bank"),
+        ],
+        vec![
+            InlineKeyboardButton::callback("📥 Импорт задач", "admin|task_import"),
+            InlineKeyboardButton::callback("🧪 Опубликовать пробник", "admin|mock_publish"),
+        ],
+        vec![
+            InlineKeyboardButton::callback("🐍 Тесты для кода", "admin|code_tests"),
+            InlineKeyboardButton::callback("🃏 Карточки", "admin|cards"),
+        ],
+        vec![InlineKeyboardButton::callback("❓ Квиз по теме", "admin|quiz")],
+        vec![
+            InlineKeyboardButton::callback("👥 Группы", "admin|groups"),
+            InlineKeyboardButton::callback("✍️ Оценки за пробник", "admin|teacher_scores"),
+        ],
+        vec![
+            InlineKeyboardButton::callback("💾 Бэкапы", "admin|backups"),
+            InlineKeyboardButton::callback("♻️ Сброс базы", "admin|reset_all"),
+        ],
         vec![InlineKeyboardButton::callback("Отмена", "cancel")],
     ];
     InlineKeyboardMarkup::new(buttons)
 }
 
-pub fn get_topic_title(section: &str, topic_id: &str) -> Option<String> {
-    if section == "ЕГЭ 1-27" {
-        if topic_id.starts_with("ege") {
-            let num = topic_id.trim_start_matches("ege");
-            return Some(format!("Задание {}", num));
-        }
-    }
-
-    let topics = match section {
-        "Основы Питона" => vec![
-            ("op1", "Вводный урок"),
-            ("op2", "Условия и операторы"),
-            ("op3", "Цикл for"),
-            ("op4", "Цикл while"),
-            ("op5", "Практика: циклы"),
-            ("op6", "Строки и срезы"),
-            ("op7", "Списки")
-        ],
-        _ => vec![]
-    };
+pub fn backups_kb() -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback("➕ Сделать бэкап", "admin|backup_now"),
+    ]])
+}
 
-    topics.into_iter()
-        .find(|(id, _)| *id == topic_id)
-        .map(|(_, title)| title.to_string())
-}
\ No newline at end of file
+pub fn get_topic_title(section: &str, topic_id: &str) -> Option<String> {
+    section_topics(section).into_iter()
+        .find(|(id, _)| id == topic_id)
+        .map(|(_, title)| title)
+}
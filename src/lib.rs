@@ -0,0 +1,79 @@
+pub mod api;
+pub mod backup;
+pub mod code_check;
+pub mod code_preview;
+pub mod daily;
+pub mod dashboard;
+pub mod db;
+pub mod events;
+pub mod flashcards;
+pub mod handlers;
+pub mod health;
+pub mod import;
+pub mod keyboards;
+pub mod metrics;
+pub mod mock;
+pub mod pdf;
+pub mod quiz;
+pub mod repo;
+pub mod reports;
+pub mod states;
+pub mod tasks;
+pub mod webhook;
+
+use std::sync::Arc;
+use std::time::Instant;
+use teloxide::prelude::*;
+use teloxide::dispatching::UpdateHandler;
+use teloxide::dispatching::dialogue::InMemStorage;
+
+use crate::db::DbPool;
+use crate::states::{DialogueState, SubmissionType};
+
+#[derive(Clone)]
+pub struct AppState {
+    pub pool: DbPool,
+    pub repos: repo::Repos,
+    pub admin_id: i64,
+    pub conspects_dir: String,
+    pub task_files_dir: String,
+    pub withdraw_grace_minutes: i64,
+    pub mock_score_table: Arc<Vec<i64>>,
+    pub mock_target_score: i64,
+    pub code_checker: Arc<code_check::Checker>,
+    pub health: Arc<health::Health>,
+    pub metrics: Arc<metrics::Metrics>,
+    pub events: Arc<events::Events>,
+    pub backups: Arc<backup::Backups>,
+    pub media_groups: Arc<dashmap::DashMap<
+        String,
+        (Vec<String>, String, i64, (SubmissionType, String, String, String))
+    >>,
+}
+
+pub fn schema() -> UpdateHandler<Box<dyn std::error::Error + Send + Sync + 'static>> {
+    dptree::entry()
+        .branch(Update::filter_message()
+            .enter_dialogue::<Message, InMemStorage<DialogueState>, DialogueState>()
+            .endpoint(|bot: Bot, msg: Message, dialogue: handlers::MyDialogue, state: AppState| async move {
+                let started = Instant::now();
+                let res = handlers::message_handler(bot, msg, dialogue, state.clone()).await;
+                state.metrics.handler_finished("message", started, res.as_ref().err().map(|e| e.as_ref()));
+                res
+            }))
+        .branch(Update::filter_callback_query()
+            .enter_dialogue::<CallbackQuery, InMemStorage<DialogueState>, DialogueState>()
+            .endpoint(|bot: Bot, q: CallbackQuery, dialogue: handlers::MyDialogue, state: AppState| async move {
+                let started = Instant::now();
+                let res = handlers::callback_handler(bot, q, dialogue, state.clone()).await;
+                state.metrics.handler_finished("callback_query", started, res.as_ref().err().map(|e| e.as_ref()));
+                res
+            }))
+        .branch(Update::filter_poll_answer()
+            .endpoint(|answer: PollAnswer, state: AppState| async move {
+                let started = Instant::now();
+                let res = handlers::poll_answer_handler(answer, state.clone()).await;
+                state.metrics.handler_finished("poll_answer", started, res.as_ref().err().map(|e| e.as_ref()));
+                res
+            }))
+}
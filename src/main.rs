@@ -1,32 +1,25 @@
-mod db;
-mod handlers;
-mod keyboards;
-mod reports;
-mod states;
-
 use std::sync::Arc;
+use std::time::Instant;
 use dotenvy::dotenv;
 use teloxide::prelude::*;
 use teloxide::dispatching::dialogue::InMemStorage;
+use teloxide::error_handlers::LoggingErrorHandler;
 use teloxide::RequestError;
 use teloxide::ApiError;
 use tokio_cron_scheduler::{Job, JobScheduler};
 use chrono::Utc;
-use sqlx::Row;
 use warp::Filter;
 
-use crate::db::{init_db, DbPool};
-use crate::states::{DialogueState, SubmissionType};
-
-#[derive(Clone)]
-pub struct AppState {
-    pub pool: DbPool,
-    pub admin_id: i64,
-    pub conspects_dir: String,
-    pub media_groups: Arc<dashmap::DashMap<
-        String,
-        (Vec<String>, String, i64, (SubmissionType, String, String, String))
-    >>,
+use homework_bot::db::{self, init_db};
+use homework_bot::states::{DialogueState, SubmissionType};
+use homework_bot::{
+    api, backup, code_check, daily, dashboard, events, handlers, health, import, metrics, mock, repo, reports, schema, tasks,
+    webhook, AppState,
+};
+
+async fn forget_blocked_user(repos: &repo::Repos, events: &Arc<events::Events>, uid: i64) {
+    let _ = repos.users.remove(uid).await;
+    events.emit(events::USER_BLOCKED, serde_json::json!({ "user_id": uid })).await;
 }
 
 #[tokio::main]
@@ -34,205 +27,431 @@ async fn main() -> anyhow::Result<()> {
     dotenv().ok();
     pretty_env_logger::init();
 
+    let db_url = std::env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite:bot.db".into());
+    let task_files_dir = std::env::var("TASK_FILES_DIR").unwrap_or_else(|_| "task_files".into());
+
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("import-tasks") {
+        let path = args.get(2).ok_or_else(|| anyhow::anyhow!("Usage: homework_bot import-tasks <file.xlsx|file.csv>"))?;
+        let pool = init_db(&db_url).await?;
+        let data = tokio::fs::read(path).await?;
+        let report = import::import_tasks(&pool, path, &data, &task_files_dir).await?;
+        println!("{}", report.summary());
+        return Ok(());
+    }
+    if args.get(1).map(String::as_str) == Some("migrate-to-postgres") {
+        if db::Backend::from_url(&db_url)? != db::Backend::Postgres {
+            return Err(anyhow::anyhow!("DATABASE_URL must point to the target Postgres database"));
+        }
+        let source_path = args.get(2).map(String::as_str).unwrap_or("bot.db");
+        if !std::path::Path::new(source_path).exists() {
+            return Err(anyhow::anyhow!("Usage: homework_bot migrate-to-postgres [bot.db]"));
+        }
+        let source = init_db(&format!("sqlite:{}", source_path)).await?;
+        let target = init_db(&db_url).await?;
+        for t in db::copy_database(&source, &target).await? {
+            if t.orphaned > 0 {
+                println!("{}: {} (skipped {} orphaned rows)", t.table, t.copied, t.orphaned);
+            } else {
+                println!("{}: {}", t.table, t.copied);
+            }
+        }
+        return Ok(());
+    }
+
+    if args.get(1).map(String::as_str) == Some("restore-backup") {
+        let database_path = backup::sqlite_path(&db_url)
+            .ok_or_else(|| anyhow::anyhow!("restore-backup works only with a SQLite file DATABASE_URL"))?;
+        let conspects_dir = std::env::var("CONSPECTS_DIR").unwrap_or_else(|_| "conspects".into());
+        let _lock = backup::lock_database(&database_path)?;
+        let pool = init_db(&db_url).await?;
+        let backups = backup::Backups::new(pool.clone(), &conspects_dir, backup::BackupConfig::from_env()?);
+        let Some(name) = args.get(2) else {
+            for b in backups.list().await? {
+                println!("{}  {}", b.name, b.size_text());
+            }
+            return Err(anyhow::anyhow!("Usage: homework_bot restore-backup <name>"));
+        };
+        let source = backups.dir().join(name);
+        if !source.is_dir() {
+            return Err(anyhow::anyhow!("Backup {} not found in {}", name, backups.dir().display()));
+        }
+        let current = backups.create("before-restore").await?;
+        println!("Current state saved as {}", current.name);
+        pool.close().await;
+        backup::restore(&source, &database_path, std::path::Path::new(&conspects_dir)).await?;
+        println!("Restored {}", name);
+        return Ok(());
+    }
+
     let token = std::env::var("API_TOKEN").expect("API_TOKEN required");
     let admin_id = std::env::var("ADMIN_ID").unwrap_or_else(|_| "0".into()).parse::<i64>()?;
-    let db_url = std::env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite:bot.db".into());
     let conspects_dir = std::env::var("CONSPECTS_DIR").unwrap_or_else(|_| "conspects".into());
-
+    let withdraw_grace_minutes = std::env::var("WITHDRAW_GRACE_MINUTES").unwrap_or_else(|_| "30".into()).parse::<i64>()?;
+    let mock_score_table = match std::env::var("MOCK_SCORE_TABLE") {
+        Ok(spec) => tasks::parse_score_table(&spec)?,
+        Err(_) => tasks::DEFAULT_SCORE_TABLE.to_vec(),
+    };
+    let mock_target_score = std::env::var("MOCK_TARGET_SCORE").unwrap_or_else(|_| "80".into()).parse::<i64>()?;
+    let code_checker = code_check::Checker::new(
+        std::env::var("CODE_CHECK_PYTHON").unwrap_or_else(|_| "python3".into()),
+        std::env::var("CODE_CHECK_TIME_LIMIT").unwrap_or_else(|_| "5".into()).parse::<u64>()?,
+        std::env::var("CODE_CHECK_MEMORY_MB").unwrap_or_else(|_| "256".into()).parse::<u64>()?,
+    );
+    if code_checker.probe().await {
+        log::info!("Code check sandbox is available");
+    }
+
+    let _db_lock = backup::sqlite_path(&db_url).map(|path| backup::lock_database(&path)).transpose()?;
     let pool = init_db(&db_url).await?;
     tokio::fs::create_dir_all(&conspects_dir).await?;
 
+    let repos = repo::Repos::sql(pool.clone());
     let app_state = AppState {
         pool: pool.clone(),
+        repos: repos.clone(),
         admin_id,
         conspects_dir: conspects_dir.clone(),
+        task_files_dir,
+        withdraw_grace_minutes,
+        mock_score_table: Arc::new(mock_score_table),
+        mock_target_score,
+        code_checker: Arc::new(code_checker),
+        health: Arc::new(health::Health::new()),
+        metrics: Arc::new(metrics::Metrics::default()),
+        events: Arc::new(events::Events::new(pool.clone(), repos.submissions.clone(), events::EventConfig::from_env()?)?),
+        backups: Arc::new(backup::Backups::new(pool.clone(), &conspects_dir, backup::BackupConfig::from_env()?)),
         media_groups: Arc::new(dashmap::DashMap::new()),
     };
 
+    let resumed = app_state.events.resume_pending().await?;
+    if resumed > 0 {
+        log::info!("Resumed {} pending webhook deliveries", resumed);
+    }
+
+    let webhook_config = webhook::WebhookConfig::from_env()?;
+    let (webhook_listener, webhook_route) = match &webhook_config {
+        Some(config) => {
+            let (listener, tx) = webhook::listener();
+            (Some(listener), Some(webhook::route(config.clone(), tx)))
+        }
+        None => (None, None),
+    };
+
+    let bot = Bot::new(token.clone());
+    let me = bot.get_me().await?;
+    let dashboard = Arc::new(dashboard::Dashboard::new(&token, me.username(), admin_id)?);
+
+    let mut health_routes = health::routes(app_state.clone())
+        .or(metrics::routes(app_state.metrics.clone()))
+        .unify()
+        .or(dashboard::routes(app_state.clone(), dashboard))
+        .unify()
+        .boxed();
+    if let Some(api_config) = api::ApiConfig::from_env()? {
+        health_routes = health_routes.or(api::routes(app_state.clone(), api_config)).unify().boxed();
+    }
     tokio::spawn(async move {
         let port_str = std::env::var("PORT").unwrap_or_else(|_| "8080".to_string());
         let port = port_str.parse::<u16>().unwrap_or(8080);
-        let routes = warp::any().map(|| "I'm alive! Bot is running.");
-        warp::serve(routes).run(([0, 0, 0, 0], port)).await;
+        match webhook_route {
+            Some(webhook) => warp::serve(health_routes.or(webhook)).run(([0, 0, 0, 0], port)).await,
+            None => warp::serve(health_routes).run(([0, 0, 0, 0], port)).await,
+        }
     });
 
-    let bot = Bot::new(token);
-
     let sched = JobScheduler::new().await?;
 
-    let pool_remind = pool.clone();
+    let repos_remind = app_state.repos.clone();
+    let health_remind = app_state.health.clone();
+    let metrics_remind = app_state.metrics.clone();
+    let events_remind = app_state.events.clone();
+    health_remind.register_job("reminder", 86400);
     let bot_remind = bot.clone();
     sched.add(Job::new_async("0 0 18 * * *", move |_uuid, _l| {
-        let pool = pool_remind.clone();
+        health_remind.tick("reminder");
+        let metrics = metrics_remind.clone();
+        let events = events_remind.clone();
+        let repos = repos_remind.clone();
         let bot = bot_remind.clone();
+        let health = health_remind.clone();
         Box::pin(async move {
-            let rows = sqlx::query("SELECT id FROM users").fetch_all(&pool).await;
-            if let Ok(users) = rows {
-                for row in users {
-                    let uid: i64 = row.get("id");
+            let started = Instant::now();
+            if let Ok(users) = repos.users.list().await {
+                let mut batch = health.outbound(users.len());
+                for user in users {
+                    batch.sent();
+                    let uid = user.id;
                     match bot.send_message(
                         UserId(uid as u64),
                         "⏰ Напоминание: не забудьте сегодня сдать ДЗ и/или конспект."
                     ).await {
                         Ok(_) => {},
-                        Err(RequestError::Api(ApiError::BotBlocked)) => {
-                            let _ = sqlx::query("DELETE FROM users WHERE id = ?").bind(uid).execute(&pool).await;
-                        },
-                        Err(_) => {}
+                        Err(e) => {
+                            metrics.telegram_error(&e);
+                            if matches!(e, RequestError::Api(ApiError::BotBlocked)) {
+                                forget_blocked_user(&repos, &events, uid).await;
+                            }
+                        }
                     }
                 }
             }
+            metrics.job_finished("reminder", started, true);
         })
     })?).await?;
 
-    let pool_report = pool.clone();
+    let repos_report = app_state.repos.clone();
+    let health_report = app_state.health.clone();
+    let metrics_report = app_state.metrics.clone();
+    health_report.register_job("daily_report", 86400);
     let bot_report = bot.clone();
     sched.add(Job::new_async("0 55 23 * * *", move |_uuid, _l| {
-        let pool = pool_report.clone();
+        health_report.tick("daily_report");
+        let metrics = metrics_report.clone();
+        let repos = repos_report.clone();
         let bot = bot_report.clone();
         Box::pin(async move {
+            let started = Instant::now();
             let date = Utc::now().format("%Y-%m-%d").to_string();
 
-            let dz_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM submissions WHERE date = ? AND type = 'dz'")
-                .bind(&date).fetch_one(&pool).await.unwrap_or(0);
+            let dz_filter = repo::SubmissionFilter { kind: Some(SubmissionType::Dz), ..repo::SubmissionFilter::on_date(&date) };
+            let dz_count = repos.submissions.count(&dz_filter).await.unwrap_or(0);
 
-            let conspect_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM submissions WHERE date = ? AND type = 'conspect'")
-                .bind(&date).fetch_one(&pool).await.unwrap_or(0);
+            let conspect_filter = repo::SubmissionFilter { kind: Some(SubmissionType::Conspect), ..repo::SubmissionFilter::on_date(&date) };
+            let conspect_count = repos.submissions.count(&conspect_filter).await.unwrap_or(0);
 
             let msg = format!("Ежедневный отчёт за {}:\nДЗ: {}\nКонспект: {}", date, dz_count, conspect_count);
             let _ = bot.send_message(UserId(admin_id as u64), msg).await;
+            metrics.job_finished("daily_report", started, true);
         })
     })?).await?;
 
-    let pool_reason = pool.clone();
+    let repos_reason = app_state.repos.clone();
+    let health_reason = app_state.health.clone();
+    let metrics_reason = app_state.metrics.clone();
+    let events_reason = app_state.events.clone();
+    health_reason.register_job("miss_reasons", 86400);
     let bot_reason = bot.clone();
     sched.add(Job::new_async("0 57 23 * * *", move |_uuid, _l| {
-        let pool = pool_reason.clone();
+        health_reason.tick("miss_reasons");
+        let metrics = metrics_reason.clone();
+        let events = events_reason.clone();
+        let repos = repos_reason.clone();
         let bot = bot_reason.clone();
+        let health = health_reason.clone();
         Box::pin(async move {
+            let started = Instant::now();
             let date = Utc::now().format("%Y-%m-%d").to_string();
 
-            let query = "
-                SELECT id FROM users
-                WHERE id NOT IN (SELECT user_id FROM submissions WHERE date = ?)
-            ";
-
-            if let Ok(rows) = sqlx::query(query).bind(&date).fetch_all(&pool).await {
-                for row in rows {
-                    let uid: i64 = row.get("id");
-                    let _ = sqlx::query("INSERT OR IGNORE INTO miss_reasons (user_id, date, reason) VALUES (?, ?, '')")
-                        .bind(uid).bind(&date).execute(&pool).await;
+            if let Ok(uids) = reports::users_without_submissions(&repos, &date).await {
+                let mut batch = health.outbound(uids.len());
+                for uid in uids {
+                    batch.sent();
+                    let _ = repos.miss_reasons.request(uid, &date).await;
 
                     match bot.send_message(
                         UserId(uid as u64),
                         format!("Сегодня ({}) ты ничего не сдал(а). Укажи причину пропуска (отправь текст).", date)
                     ).await {
                         Ok(_) => {},
-                        Err(RequestError::Api(ApiError::BotBlocked)) => {
-                            let _ = sqlx::query("DELETE FROM users WHERE id = ?").bind(uid).execute(&pool).await;
-                        },
-                        Err(_) => {}
+                        Err(e) => {
+                            metrics.telegram_error(&e);
+                            if matches!(e, RequestError::Api(ApiError::BotBlocked)) {
+                                forget_blocked_user(&repos, &events, uid).await;
+                            }
+                        }
                     }
                 }
             }
+            metrics.job_finished("miss_reasons", started, true);
+        })
+    })?).await?;
+
+    let pool_errors = pool.clone();
+    let repos_errors = app_state.repos.clone();
+    let health_errors = app_state.health.clone();
+    let metrics_errors = app_state.metrics.clone();
+    health_errors.register_job("task_error_report", 604800);
+    let bot_errors = bot.clone();
+    sched.add(Job::new_async("0 0 20 * * Sun", move |_uuid, _l| {
+        health_errors.tick("task_error_report");
+        let metrics = metrics_errors.clone();
+        let pool = pool_errors.clone();
+        let repos = repos_errors.clone();
+        let bot = bot_errors.clone();
+        Box::pin(async move {
+            let started = Instant::now();
+            let since = Utc::now().date_naive() - chrono::Duration::days(7);
+            let res = handlers::send_task_error_report(&bot, ChatId(admin_id), &pool, &repos, &metrics, Some(since)).await;
+            if let Err(e) = &res {
+                log::error!("Weekly task error report failed: {:?}", e);
+            }
+            metrics.job_finished("task_error_report", started, res.is_ok());
+        })
+    })?).await?;
+
+    if app_state.backups.enabled() {
+        let backups_job = app_state.backups.clone();
+        let health_backup = app_state.health.clone();
+        let metrics_backup = app_state.metrics.clone();
+        health_backup.register_job("backup", 86400);
+        sched.add(Job::new_async("0 0 3 * * *", move |_uuid, _l| {
+            health_backup.tick("backup");
+            let metrics = metrics_backup.clone();
+            let backups = backups_job.clone();
+            Box::pin(async move {
+                let started = Instant::now();
+                let res = match backups.create("daily").await {
+                    Ok(_) => backups.prune().await.map(|_| ()),
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = &res {
+                    log::error!("Scheduled backup failed: {:?}", e);
+                }
+                metrics.job_finished("backup", started, res.is_ok());
+            })
+        })?).await?;
+    } else {
+        log::info!("Scheduled backups are disabled for Postgres, use pg_dump");
+    }
+
+    let class_chat_id = std::env::var("CLASS_CHAT_ID").ok().and_then(|v| v.parse::<i64>().ok());
+
+    let state_daily = app_state.clone();
+    state_daily.health.register_job("daily_tasks", 86400);
+    let bot_daily = bot.clone();
+    sched.add(Job::new_async("0 0 6 * * *", move |_uuid, _l| {
+        state_daily.health.tick("daily_tasks");
+        let state = state_daily.clone();
+        let bot = bot_daily.clone();
+        Box::pin(async move {
+            let started = Instant::now();
+            let res = daily::push_daily_tasks(&bot, &state).await;
+            if let Err(e) = &res {
+                log::error!("Daily tasks push failed: {:?}", e);
+            }
+            state.metrics.job_finished("daily_tasks", started, res.is_ok());
+        })
+    })?).await?;
+
+    let pool_board = pool.clone();
+    let health_board = app_state.health.clone();
+    let metrics_board = app_state.metrics.clone();
+    health_board.register_job("daily_leaderboard", 604800);
+    let bot_board = bot.clone();
+    sched.add(Job::new_async("0 30 19 * * Sun", move |_uuid, _l| {
+        health_board.tick("daily_leaderboard");
+        let metrics = metrics_board.clone();
+        let pool = pool_board.clone();
+        let bot = bot_board.clone();
+        Box::pin(async move {
+            let started = Instant::now();
+            let ok = match daily::leaderboard_text(&pool).await {
+                Ok(text) => {
+                    let _ = bot.send_message(ChatId(admin_id), &text).await;
+                    if let Some(chat_id) = class_chat_id {
+                        let _ = bot.send_message(ChatId(chat_id), &text).await;
+                    }
+                    true
+                }
+                Err(e) => {
+                    log::error!("Daily leaderboard failed: {:?}", e);
+                    false
+                }
+            };
+            metrics.job_finished("daily_leaderboard", started, ok);
+        })
+    })?).await?;
+
+    let state_mock = app_state.clone();
+    state_mock.health.register_job("mock_tick", 60);
+    let bot_mock = bot.clone();
+    sched.add(Job::new_async("0 * * * * *", move |_uuid, _l| {
+        state_mock.health.tick("mock_tick");
+        let state = state_mock.clone();
+        let bot = bot_mock.clone();
+        Box::pin(async move {
+            let started = Instant::now();
+            let res = mock::tick(&bot, &state.pool, state.admin_id, &state.mock_score_table).await;
+            if let Err(e) = &res {
+                log::error!("Mock exam tick failed: {:?}", e);
+            }
+            state.metrics.job_finished("mock_tick", started, res.is_ok());
         })
     })?).await?;
 
     let state_media = app_state.clone();
+    state_media.health.register_job("media_groups", 2);
     let bot_media = bot.clone();
 
-    let pool_danya = pool.clone();
+    let repos_danya = app_state.repos.clone();
+    let health_danya = app_state.health.clone();
+    let metrics_danya = app_state.metrics.clone();
+    let events_danya = app_state.events.clone();
+    health_danya.register_job("broadcast", 7200);
     let bot_danya = bot.clone();
 
     sched.add(Job::new_async("0 0 */2 * * *", move |_uuid, _l| {
-        let pool = pool_danya.clone();
+        health_danya.tick("broadcast");
+        let metrics = metrics_danya.clone();
+        let events = events_danya.clone();
+        let repos = repos_danya.clone();
         let bot = bot_danya.clone();
+        let health = health_danya.clone();
         Box::pin(async move {
-            let rows = sqlx::query("SELECT id FROM users").fetch_all(&pool).await;
-
-            if let Ok(users) = rows {
+            let started = Instant::now();
+            if let Ok(users) = repos.users.list().await {
                 let message_text = "Привет от Дани) Желаю удачкиии!!\n\nУ меня все хорошо, просто очень много прогаю и занят стартапом(((";
 
-                for row in users {
-                    let uid: i64 = row.get("id");
+                let mut batch = health.outbound(users.len());
+                for user in users {
+                    batch.sent();
+                    let uid = user.id;
                     match bot.send_message(UserId(uid as u64), message_text).await {
                         Ok(_) => {},
-                        Err(RequestError::Api(ApiError::BotBlocked)) => {
-                            let _ = sqlx::query("DELETE FROM users WHERE id = ?").bind(uid).execute(&pool).await;
-                        },
-                        Err(_) => {}
+                        Err(e) => {
+                            metrics.telegram_error(&e);
+                            if matches!(e, RequestError::Api(ApiError::BotBlocked)) {
+                                forget_blocked_user(&repos, &events, uid).await;
+                            }
+                        }
                     }
                     tokio::time::sleep(std::time::Duration::from_millis(50)).await;
                 }
             }
+            metrics.job_finished("broadcast", started, true);
         })
     })?).await?;
 
     sched.add(Job::new_async("1/2 * * * * *", move |_uuid, _l| {
+        state_media.health.tick("media_groups");
         let state = state_media.clone();
         let bot = bot_media.clone();
         Box::pin(async move {
-            let now = Utc::now().timestamp();
-            let mut keys_to_process = Vec::new();
-
-            for r in state.media_groups.iter() {
-                if now - r.value().2 > 2 {
-                    keys_to_process.push(r.key().clone());
-                }
-            }
-
-            for key in keys_to_process {
-                if let Some((_, (file_ids, caption, _, context))) = state.media_groups.remove(&key) {
-                    let uid_str = key.split('|').next().unwrap_or("0");
-                    let uid = uid_str.parse::<i64>().unwrap_or(0);
-                    let (kind, section, topic_id, topic_title) = context;
-
-                    let date = Utc::now().format("%Y-%m-%d").to_string();
-                    let ts = Utc::now().to_rfc3339();
-                    let summary = if caption.len() > 200 { format!("{}...", &caption[..197]) } else { caption.clone() };
-                    let joined_files = file_ids.join(";");
-
-                    let res = db::add_submission(
-                        &state.pool, uid, &kind, &section, &topic_id, &topic_title,
-                        "photo_album", &summary, &joined_files, 0, &date, &ts
-                    ).await;
-
-                    if res.is_ok() {
-                        if matches!(kind, SubmissionType::Conspect) {
-                            for fid in file_ids.iter() {
-                                let _ = reports::save_file_to_disk(&bot, fid, &state.conspects_dir, uid, &section, &topic_id).await;
-                            }
-                        }
-
-                        let type_str = match kind { SubmissionType::Dz => "ДЗ", SubmissionType::Conspect => "Конспект" };
-                        let _ = bot.send_message(UserId(uid as u64), format!("Альбом принят! ({} фото)", file_ids.len())).await;
-
-                        let _ = bot.send_message(UserId(state.admin_id as u64), format!(
-                            "📸 Новый {} (АЛЬБОМ) от user_{}: {} - {}",
-                            type_str, uid, topic_title, summary
-                        )).await;
-                    }
-                }
-            }
+            let started = Instant::now();
+            handlers::flush_media_groups(&bot, &state, Utc::now().timestamp()).await;
+            state.metrics.job_finished("media_groups", started, true);
         })
     })?).await?;
 
     sched.start().await?;
 
-    let handler = dptree::entry()
-        .branch(Update::filter_message()
-            .enter_dialogue::<Message, InMemStorage<DialogueState>, DialogueState>()
-            .endpoint(handlers::message_handler))
-        .branch(Update::filter_callback_query()
-            .enter_dialogue::<CallbackQuery, InMemStorage<DialogueState>, DialogueState>()
-            .endpoint(handlers::callback_handler));
-
-    Dispatcher::builder(bot, handler)
+    let mut dispatcher = Dispatcher::builder(bot.clone(), schema())
         .dependencies(dptree::deps![InMemStorage::<DialogueState>::new(), app_state])
         .enable_ctrlc_handler()
-        .build()
-        .dispatch()
-        .await;
+        .build();
+
+    match (webhook_config, webhook_listener) {
+        (Some(config), Some(listener)) => {
+            webhook::set_webhook(&bot, &config).await?;
+            log::info!("Webhook mode: listening on {}", config.url.path());
+            dispatcher.dispatch_with_listener(
+                listener,
+                LoggingErrorHandler::with_custom_text("An error from the webhook listener")
+            ).await;
+        }
+        _ => dispatcher.dispatch().await,
+    }
 
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,156 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use teloxide::{ApiError, RequestError};
+use warp::filters::BoxedFilter;
+use warp::reply::Response;
+use warp::{Filter, Reply};
+
+use crate::states::SubmissionType;
+
+const BUCKETS: [f64; 14] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0];
+
+const COUNTERS: [(&str, &str); 7] = [
+    ("bot_updates_total", "Updates processed, by update kind"),
+    ("bot_handler_errors_total", "Handler invocations that returned an error"),
+    ("bot_submissions_total", "Accepted submissions, by type and section"),
+    ("bot_telegram_errors_total", "Telegram Bot API errors, by kind"),
+    ("bot_cron_runs_total", "Scheduled job runs"),
+    ("bot_cron_failures_total", "Scheduled job runs that failed"),
+    ("bot_stored_file_bytes_total", "Bytes written to disk, by file kind"),
+];
+
+const HISTOGRAMS: [(&str, &str); 3] = [
+    ("bot_handler_duration_seconds", "Handler latency"),
+    ("bot_cron_duration_seconds", "Scheduled job duration"),
+    ("bot_report_duration_seconds", "Report generation time"),
+];
+
+#[derive(Default)]
+struct Histogram {
+    buckets: [u64; BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+type Series = (&'static str, Vec<(&'static str, String)>);
+
+#[derive(Default)]
+pub struct Metrics {
+    counters: Mutex<BTreeMap<Series, u64>>,
+    histograms: Mutex<BTreeMap<Series, Histogram>>,
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn render_labels(labels: &[(&'static str, String)], extra: Option<(&str, String)>) -> String {
+    let mut parts: Vec<String> = labels.iter().map(|(k, v)| format!("{}=\"{}\"", k, escape(v))).collect();
+    if let Some((k, v)) = extra {
+        parts.push(format!("{}=\"{}\"", k, v));
+    }
+    if parts.is_empty() { String::new() } else { format!("{{{}}}", parts.join(",")) }
+}
+
+pub fn telegram_error_kind(err: &RequestError) -> &'static str {
+    match err {
+        RequestError::Api(ApiError::BotBlocked) => "bot_blocked",
+        RequestError::Api(_) => "api",
+        RequestError::MigrateToChatId(_) => "migrate_to_chat",
+        RequestError::RetryAfter(_) => "retry_after",
+        RequestError::Network(_) => "network",
+        RequestError::InvalidJson { .. } => "invalid_json",
+        RequestError::Io(_) => "io",
+    }
+}
+
+impl Metrics {
+    fn inc_by(&self, name: &'static str, labels: Vec<(&'static str, String)>, value: u64) {
+        *self.counters.lock().unwrap().entry((name, labels)).or_insert(0) += value;
+    }
+
+    fn observe(&self, name: &'static str, labels: Vec<(&'static str, String)>, seconds: f64) {
+        let mut histograms = self.histograms.lock().unwrap();
+        let h = histograms.entry((name, labels)).or_default();
+        for (i, bound) in BUCKETS.iter().enumerate() {
+            if seconds <= *bound {
+                h.buckets[i] += 1;
+            }
+        }
+        h.sum += seconds;
+        h.count += 1;
+    }
+
+    pub fn handler_finished(&self, kind: &'static str, started: Instant, error: Option<&(dyn std::error::Error + Send + Sync + 'static)>) {
+        self.inc_by("bot_updates_total", vec![("kind", kind.to_string())], 1);
+        self.observe("bot_handler_duration_seconds", vec![("kind", kind.to_string())], started.elapsed().as_secs_f64());
+        if let Some(err) = error {
+            self.inc_by("bot_handler_errors_total", vec![("kind", kind.to_string())], 1);
+            if let Some(err) = err.downcast_ref::<RequestError>() {
+                self.telegram_error(err);
+            }
+        }
+    }
+
+    pub fn telegram_error(&self, err: &RequestError) {
+        self.inc_by("bot_telegram_errors_total", vec![("kind", telegram_error_kind(err).to_string())], 1);
+    }
+
+    pub fn submission(&self, kind: &SubmissionType, section: &str) {
+        self.inc_by("bot_submissions_total", vec![("type", kind.as_str().to_string()), ("section", section.to_string())], 1);
+    }
+
+    pub fn job_finished(&self, job: &'static str, started: Instant, ok: bool) {
+        self.inc_by("bot_cron_runs_total", vec![("job", job.to_string())], 1);
+        if !ok {
+            self.inc_by("bot_cron_failures_total", vec![("job", job.to_string())], 1);
+        }
+        self.observe("bot_cron_duration_seconds", vec![("job", job.to_string())], started.elapsed().as_secs_f64());
+    }
+
+    pub fn report_generated(&self, report: &'static str, started: Instant) {
+        self.observe("bot_report_duration_seconds", vec![("report", report.to_string())], started.elapsed().as_secs_f64());
+    }
+
+    pub fn stored_bytes(&self, kind: &'static str, bytes: u64) {
+        self.inc_by("bot_stored_file_bytes_total", vec![("kind", kind.to_string())], bytes);
+    }
+
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let counters = self.counters.lock().unwrap();
+        for (name, help) in COUNTERS {
+            let _ = writeln!(out, "# HELP {} {}\n# TYPE {} counter", name, help, name);
+            for ((_, labels), value) in counters.iter().filter(|((n, _), _)| *n == name) {
+                let _ = writeln!(out, "{}{} {}", name, render_labels(labels, None), value);
+            }
+        }
+        drop(counters);
+
+        let histograms = self.histograms.lock().unwrap();
+        for (name, help) in HISTOGRAMS {
+            let _ = writeln!(out, "# HELP {} {}\n# TYPE {} histogram", name, help, name);
+            for ((_, labels), h) in histograms.iter().filter(|((n, _), _)| *n == name) {
+                for (bound, count) in BUCKETS.iter().zip(h.buckets.iter()) {
+                    let _ = writeln!(out, "{}_bucket{} {}", name, render_labels(labels, Some(("le", bound.to_string()))), count);
+                }
+                let _ = writeln!(out, "{}_bucket{} {}", name, render_labels(labels, Some(("le", "+Inf".to_string()))), h.count);
+                let _ = writeln!(out, "{}_sum{} {}", name, render_labels(labels, None), h.sum);
+                let _ = writeln!(out, "{}_count{} {}", name, render_labels(labels, None), h.count);
+            }
+        }
+        out
+    }
+}
+
+pub fn routes(metrics: Arc<Metrics>) -> BoxedFilter<(Response,)> {
+    warp::path!("metrics")
+        .and(warp::get())
+        .map(move || {
+            warp::reply::with_header(metrics.render(), "content-type", "text/plain; version=0.0.4").into_response()
+        })
+        .boxed()
+}
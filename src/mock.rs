@@ -0,0 +1,133 @@
+use chrono::{DateTime, Utc};
+use log::error;
+use teloxide::prelude::*;
+
+use crate::db::{self, DbPool};
+use crate::tasks::{self, AnswerKind};
+
+pub const MOCK_DURATION_MIN: i64 = 235;
+const NOTICE_THRESHOLDS: [i64; 6] = [180, 120, 60, 30, 15, 5];
+
+pub fn minutes_left(deadline_ts: &str) -> i64 {
+    DateTime::parse_from_rfc3339(deadline_ts)
+        .map(|d| (d.with_timezone(&Utc) - Utc::now()).num_seconds().div_euclid(60))
+        .unwrap_or(-1)
+}
+
+pub fn format_left(minutes: i64) -> String {
+    let minutes = minutes.max(0);
+    format!("{} ч {:02} мин", minutes / 60, minutes % 60)
+}
+
+pub async fn finish_attempt(bot: &Bot, pool: &DbPool, admin_id: i64, score_table: &[i64], attempt_id: i64) -> anyhow::Result<()> {
+    let Some((user_id, _, _, false)) = db::get_mock_attempt(pool, attempt_id).await? else {
+        return Ok(());
+    };
+
+    let mut points = Vec::new();
+    let mut lines = Vec::new();
+    for (ege_number, canonical, kind, given) in db::mock_grading_rows(pool, attempt_id).await? {
+        let kind = AnswerKind::parse(&kind).unwrap_or(AnswerKind::default_for(ege_number));
+        let given = given.unwrap_or_default();
+        let pts = if given.is_empty() { 0 } else { tasks::score_answer(ege_number, &given, &canonical, kind) };
+        let mark = if pts == tasks::max_points(ege_number) { "✅" } else if pts > 0 { "➗" } else { "❌" };
+        lines.push(format!("{} {}: {} (ответ: {})", mark, ege_number, if given.is_empty() { "—" } else { &given }, canonical));
+        points.push((ege_number, pts));
+    }
+
+    let primary: i64 = points.iter().map(|(_, p)| p).sum();
+    let test = tasks::primary_to_test(primary, score_table);
+    if !db::finish_mock_attempt(pool, attempt_id, &points, primary, test).await? {
+        return Ok(());
+    }
+
+    let text = format!(
+        "🧪 Пробник завершён!\nПервичный балл: {}\nТестовый балл: {}\n\n{}",
+        primary, test, lines.join("\n")
+    );
+    bot.send_message(UserId(user_id as u64), text).await?;
+    bot.send_message(UserId(admin_id as u64), format!(
+        "🧪 user_{} завершил пробник (попытка {}): {} перв. / {} тест.", user_id, attempt_id, primary, test
+    )).await?;
+    Ok(())
+}
+
+pub fn parse_teacher_scores(spec: &str) -> Result<Vec<(i64, i64, i64)>, String> {
+    let mut scores = Vec::new();
+    for (i, line) in spec.lines().map(str::trim).enumerate().filter(|(_, l)| !l.is_empty()) {
+        let fields: Vec<i64> = line.split_whitespace().map(str::parse).collect::<Result<_, _>>()
+            .map_err(|_| format!("в строке {} должны быть только числа", i + 1))?;
+        let [attempt_id, ege_number, points] = fields[..] else {
+            return Err(format!("в строке {} нужно три числа: попытка, задание, баллы", i + 1));
+        };
+        if !(1..=27).contains(&ege_number) {
+            return Err(format!("в строке {} нет задания {}", i + 1, ege_number));
+        }
+        if !(0..=tasks::max_points(ege_number)).contains(&points) {
+            return Err(format!("в строке {}: за задание {} можно дать от 0 до {} баллов", i + 1, ege_number, tasks::max_points(ege_number)));
+        }
+        scores.push((attempt_id, ege_number, points));
+    }
+    if scores.is_empty() {
+        return Err("нет ни одной оценки".to_string());
+    }
+    Ok(scores)
+}
+
+pub async fn apply_teacher_scores(pool: &DbPool, score_table: &[i64], scores: &[(i64, i64, i64)]) -> anyhow::Result<Vec<(i64, i64)>> {
+    let mut missing = Vec::new();
+    let mut attempts = Vec::new();
+    for &(attempt_id, ege_number, points) in scores {
+        if !db::set_teacher_points(pool, attempt_id, ege_number, points).await? {
+            missing.push((attempt_id, ege_number));
+        } else if !attempts.contains(&attempt_id) {
+            attempts.push(attempt_id);
+        }
+    }
+    for attempt_id in attempts {
+        let primary = db::mock_primary_score(pool, attempt_id).await?;
+        db::set_mock_scores(pool, attempt_id, primary, tasks::primary_to_test(primary, score_table)).await?;
+    }
+    Ok(missing)
+}
+
+pub async fn tick(bot: &Bot, pool: &DbPool, admin_id: i64, score_table: &[i64]) -> anyhow::Result<()> {
+    for (attempt_id, user_id, deadline_ts, last_notice) in db::list_running_mock_attempts(pool).await? {
+        let left = minutes_left(&deadline_ts);
+        if left < 0 {
+            if let Err(e) = finish_attempt(bot, pool, admin_id, score_table, attempt_id).await {
+                error!("Mock attempt {} not finished: {:?}", attempt_id, e);
+            }
+            continue;
+        }
+
+        if let Some(threshold) = NOTICE_THRESHOLDS.iter().copied().rev().find(|t| left < *t && *t < last_notice) {
+            db::set_mock_notice(pool, attempt_id, threshold).await?;
+            let _ = bot.send_message(UserId(user_id as u64), format!(
+                "⏳ До конца пробника осталось {}.", format_left(left + 1)
+            )).await;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn teacher_scores_are_read_line_by_line() {
+        let scores = parse_teacher_scores("12 27 2\n\n  12 26 1  \n13 25 1\n").unwrap();
+        assert_eq!(scores, vec![(12, 27, 2), (12, 26, 1), (13, 25, 1)]);
+    }
+
+    #[test]
+    fn teacher_scores_reject_bad_lines() {
+        assert_eq!(parse_teacher_scores("12 27 2\n12 x 1"), Err("в строке 2 должны быть только числа".to_string()));
+        assert_eq!(parse_teacher_scores("12 27"), Err("в строке 1 нужно три числа: попытка, задание, баллы".to_string()));
+        assert_eq!(parse_teacher_scores("12 28 1"), Err("в строке 1 нет задания 28".to_string()));
+        assert_eq!(parse_teacher_scores("12 27 3"), Err("в строке 1: за задание 27 можно дать от 0 до 2 баллов".to_string()));
+        assert_eq!(parse_teacher_scores("12 5 2"), Err("в строке 1: за задание 5 можно дать от 0 до 1 баллов".to_string()));
+        assert_eq!(parse_teacher_scores(" \n"), Err("нет ни одной оценки".to_string()));
+    }
+}
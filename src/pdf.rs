@@ -0,0 +1,143 @@
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+
+use image::{imageops::FilterType, RgbImage};
+use image::codecs::jpeg::JpegEncoder;
+use log::warn;
+use pdf_writer::{Content, Filter, Finish, Name, Pdf, Rect, Ref};
+use plotters::prelude::*;
+use plotters::style::text_anchor::{HPos, Pos, VPos};
+
+const A4: (f32, f32) = (595.0, 842.0);
+const PAGE_MARGIN: f32 = 28.0;
+const MAX_IMAGE_SIDE: u32 = 1600;
+const JPEG_QUALITY: u8 = 80;
+const TITLE_PAGE_SIZE: (u32, u32) = (1240, 1754);
+
+pub fn is_image_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| matches!(e.to_lowercase().as_str(), "jpg" | "jpeg" | "png"))
+        .unwrap_or(false)
+}
+
+fn exif_orientation(bytes: &[u8]) -> u32 {
+    exif::Reader::new()
+        .read_from_container(&mut Cursor::new(bytes))
+        .ok()
+        .and_then(|exif| {
+            exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)
+                .and_then(|f| f.value.get_uint(0))
+        })
+        .unwrap_or(1)
+}
+
+fn load_normalized(path: &Path) -> anyhow::Result<RgbImage> {
+    let bytes = std::fs::read(path)?;
+    let img = image::load_from_memory(&bytes)?;
+
+    let img = match exif_orientation(&bytes) {
+        2 => img.fliph(),
+        3 => img.rotate180(),
+        4 => img.flipv(),
+        5 => img.rotate90().fliph(),
+        6 => img.rotate90(),
+        7 => img.rotate270().fliph(),
+        8 => img.rotate270(),
+        _ => img,
+    };
+
+    let img = if img.width().max(img.height()) > MAX_IMAGE_SIDE {
+        img.resize(MAX_IMAGE_SIDE, MAX_IMAGE_SIDE, FilterType::Triangle)
+    } else {
+        img
+    };
+
+    Ok(img.to_rgb8())
+}
+
+fn render_title_page(lines: &[String]) -> anyhow::Result<RgbImage> {
+    let (w, h) = TITLE_PAGE_SIZE;
+    let mut buffer = vec![0; (w * h * 3) as usize];
+    {
+        let root = BitMapBackend::with_buffer(&mut buffer, TITLE_PAGE_SIZE).into_drawing_area();
+        root.fill(&WHITE)?;
+
+        let center = Pos::new(HPos::Center, VPos::Center);
+        for (i, line) in lines.iter().enumerate() {
+            let size = if i == 0 { 80 } else { 48 };
+            let style = TextStyle::from(("sans-serif", size).into_font()).color(&BLACK).pos(center);
+            root.draw_text(line, &style, ((w / 2) as i32, (h / 3) as i32 + i as i32 * 110))?;
+        }
+        root.present()?;
+    }
+
+    RgbImage::from_raw(w, h, buffer).ok_or_else(|| anyhow::anyhow!("Title page buffer size mismatch"))
+}
+
+fn encode_jpeg(img: &RgbImage) -> anyhow::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    JpegEncoder::new_with_quality(&mut out, JPEG_QUALITY).encode_image(img)?;
+    Ok(out)
+}
+
+pub fn build_conspect_pdf(images: &[PathBuf], title_lines: impl FnOnce(&[&PathBuf]) -> Vec<String>) -> anyhow::Result<Vec<u8>> {
+    let mut written = Vec::new();
+    let mut photos = Vec::new();
+    for path in images {
+        match load_normalized(path) {
+            Ok(img) => {
+                written.push(path);
+                photos.push(img);
+            }
+            Err(e) => warn!("Skipping {:?} in PDF: {:?}", path, e),
+        }
+    }
+
+    let mut pages = vec![render_title_page(&title_lines(&written))?];
+    pages.extend(photos);
+
+    let mut pdf = Pdf::new();
+    let catalog_id = Ref::new(1);
+    let page_tree_id = Ref::new(2);
+    let page_ids: Vec<Ref> = (0..pages.len()).map(|i| Ref::new(3 + i as i32 * 3)).collect();
+    let image_name = Name(b"Im1");
+
+    pdf.catalog(catalog_id).pages(page_tree_id);
+    pdf.pages(page_tree_id).kids(page_ids.iter().copied()).count(pages.len() as i32);
+
+    for (img, page_id) in pages.iter().zip(page_ids) {
+        let content_id = Ref::new(page_id.get() + 1);
+        let image_id = Ref::new(page_id.get() + 2);
+
+        let (img_w, img_h) = (img.width() as f32, img.height() as f32);
+        let (page_w, page_h) = if img_w > img_h { (A4.1, A4.0) } else { A4 };
+        let scale = ((page_w - 2.0 * PAGE_MARGIN) / img_w).min((page_h - 2.0 * PAGE_MARGIN) / img_h);
+        let (w, h) = (img_w * scale, img_h * scale);
+
+        let mut page = pdf.page(page_id);
+        page.media_box(Rect::new(0.0, 0.0, page_w, page_h));
+        page.parent(page_tree_id);
+        page.contents(content_id);
+        page.resources().x_objects().pair(image_name, image_id);
+        page.finish();
+
+        let encoded = encode_jpeg(img)?;
+        let mut xobject = pdf.image_xobject(image_id, &encoded);
+        xobject.filter(Filter::DctDecode);
+        xobject.width(img.width() as i32);
+        xobject.height(img.height() as i32);
+        xobject.color_space().device_rgb();
+        xobject.bits_per_component(8);
+        xobject.finish();
+
+        let mut content = Content::new();
+        content.save_state();
+        content.transform([w, 0.0, 0.0, h, (page_w - w) / 2.0, (page_h - h) / 2.0]);
+        content.x_object(image_name);
+        content.restore_state();
+        pdf.stream(content_id, &content.finish());
+    }
+
+    Ok(pdf.finish())
+}
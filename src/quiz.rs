@@ -0,0 +1,177 @@
+use log::error;
+use teloxide::prelude::*;
+use teloxide::types::PollType;
+
+use crate::db::{self, DbPool};
+use crate::health::Health;
+use crate::keyboards;
+use crate::repo::UserRepo;
+
+const MAX_QUESTION_CHARS: usize = 300;
+const MAX_OPTION_CHARS: usize = 100;
+const MAX_OPTIONS: usize = 10;
+
+pub struct QuizQuestion {
+    pub question: String,
+    pub options: Vec<String>,
+    pub correct: usize,
+}
+
+pub struct QuizSpec {
+    pub section: String,
+    pub topic_id: String,
+    pub questions: Vec<QuizQuestion>,
+}
+
+fn check_question(n: usize, q: &QuizQuestion, correct: &[usize]) -> Result<(), String> {
+    if q.options.len() < 2 || q.options.len() > MAX_OPTIONS {
+        return Err(format!("в вопросе {} должно быть от 2 до {} вариантов", n, MAX_OPTIONS));
+    }
+    if correct.len() != 1 {
+        return Err(format!("в вопросе {} должен быть ровно один вариант с «+»", n));
+    }
+    if let Some(opt) = q.options.iter().find(|o| o.chars().count() > MAX_OPTION_CHARS) {
+        return Err(format!("в вопросе {} слишком длинный вариант «{}»", n, opt));
+    }
+    Ok(())
+}
+
+pub fn parse_quiz(spec: &str) -> Result<QuizSpec, String> {
+    let mut lines = spec.lines().map(str::trim).filter(|l| !l.is_empty());
+    let topic_id = lines.next().unwrap_or("").to_string();
+    let section = keyboards::SECTIONS.iter()
+        .find(|s| keyboards::get_topic_title(s, &topic_id).is_some())
+        .ok_or_else(|| format!("неизвестная тема «{}»", topic_id))?;
+
+    let mut questions: Vec<QuizQuestion> = Vec::new();
+    let mut correct: Vec<usize> = Vec::new();
+    for line in lines {
+        if let Some(text) = line.strip_prefix('?') {
+            if let Some(last) = questions.last() {
+                check_question(questions.len(), last, &correct)?;
+            }
+            let text = text.trim();
+            if text.is_empty() || text.chars().count() > MAX_QUESTION_CHARS {
+                return Err(format!("вопрос {} пустой или длиннее {} символов", questions.len() + 1, MAX_QUESTION_CHARS));
+            }
+            questions.push(QuizQuestion { question: text.to_string(), options: Vec::new(), correct: 0 });
+            correct.clear();
+            continue;
+        }
+
+        let (is_correct, option) = match (line.strip_prefix('+'), line.strip_prefix('-')) {
+            (Some(o), _) => (true, o.trim()),
+            (_, Some(o)) => (false, o.trim()),
+            _ => return Err(format!("непонятная строка «{}»", line)),
+        };
+        let Some(question) = questions.last_mut() else {
+            return Err("варианты ответа до первого вопроса".to_string());
+        };
+        if option.is_empty() {
+            return Err(format!("пустой вариант в вопросе {}", questions.len()));
+        }
+        if is_correct {
+            correct.push(question.options.len());
+            question.correct = question.options.len();
+        }
+        question.options.push(option.to_string());
+    }
+
+    match questions.last() {
+        Some(last) => check_question(questions.len(), last, &correct)?,
+        None => return Err("нет ни одного вопроса".to_string()),
+    }
+    Ok(QuizSpec { section: section.to_string(), topic_id, questions })
+}
+
+pub async fn deliver_quiz(bot: &Bot, pool: &DbPool, users: &dyn UserRepo, health: &Health, admin_id: i64, quiz_id: i64) -> anyhow::Result<usize> {
+    let Some((section, topic_id)) = db::get_quiz(pool, quiz_id).await? else {
+        return Ok(0);
+    };
+    let questions = db::get_quiz_questions(pool, quiz_id).await?;
+    let title = keyboards::get_topic_title(&section, &topic_id).unwrap_or(topic_id);
+
+    let students: Vec<i64> = users.list().await?.into_iter().map(|u| u.id).filter(|id| *id != admin_id).collect();
+    let mut batch = health.outbound(students.len());
+    let mut reached = 0;
+    for uid in students {
+        batch.sent();
+        let chat_id = ChatId(uid);
+        if let Err(e) = bot.send_message(chat_id, format!("❓ Проверочный квиз по теме «{}»: {} вопр.", title, questions.len())).await {
+            error!("Quiz for {} not sent: {:?}", uid, e);
+            continue;
+        }
+        for (question_id, question, options, correct) in questions.iter() {
+            let sent = bot.send_poll(chat_id, question, options.split('\n').map(String::from))
+                .type_(PollType::Quiz)
+                .is_anonymous(false)
+                .correct_option_id(*correct as u8)
+                .await;
+            match sent {
+                Ok(msg) => {
+                    if let Some(poll) = msg.poll() {
+                        if let Err(e) = db::record_quiz_poll(pool, &poll.id, *question_id, uid).await {
+                            error!("Quiz poll {} for {} not recorded: {:?}", poll.id, uid, e);
+                        }
+                    }
+                }
+                Err(e) => error!("Quiz poll for {} not sent: {:?}", uid, e),
+            }
+        }
+        reached += 1;
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    Ok(reached)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quiz_spec_is_split_into_questions_with_one_correct_option() {
+        let spec = parse_quiz("op3\n? Сколько раз выполнится range(3)?\n- 2\n+ 3\n-4\n\n?Что делает break\n+выходит из цикла\n- ничего").unwrap();
+        assert_eq!((spec.section.as_str(), spec.topic_id.as_str()), ("Основы Питона", "op3"));
+        let questions: Vec<_> = spec.questions.iter().map(|q| (q.question.as_str(), q.options.len(), q.correct)).collect();
+        assert_eq!(questions, vec![("Сколько раз выполнится range(3)?", 3, 1), ("Что делает break", 2, 0)]);
+        assert_eq!(spec.questions[0].options, ["2", "3", "4"]);
+    }
+
+    #[test]
+    fn quiz_spec_rejects_malformed_questions() {
+        let err = |spec: &str| parse_quiz(spec).err().unwrap();
+        assert_eq!(err("op99\n? a\n+ b\n- c"), "неизвестная тема «op99»");
+        assert_eq!(err("op3"), "нет ни одного вопроса");
+        assert_eq!(err("op3\n+ b\n? a"), "варианты ответа до первого вопроса");
+        assert_eq!(err("op3\n? a\n* b"), "непонятная строка «* b»");
+        assert_eq!(err("op3\n? a\n+ b\n-"), "пустой вариант в вопросе 1");
+        assert_eq!(err("op3\n?\n+ b\n- c"), "вопрос 1 пустой или длиннее 300 символов");
+        assert_eq!(err("op3\n? a\n+ b\n- x\n? c\n+ d\n+ e"), "в вопросе 2 должен быть ровно один вариант с «+»");
+        assert_eq!(err("op3\n? a\n- b\n- c\n? d\n+ e\n- f"), "в вопросе 1 должен быть ровно один вариант с «+»");
+        assert_eq!(err("op3\n? a\n+ b"), "в вопросе 1 должно быть от 2 до 10 вариантов");
+        assert!(err(&format!("op3\n? a\n+ b\n- {}", "x".repeat(101))).starts_with("в вопросе 1 слишком длинный вариант"));
+    }
+
+    #[tokio::test]
+    async fn answer_that_beats_the_poll_record_is_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = db::init_db(&format!("sqlite:{}", dir.path().join("bot.db").display())).await.unwrap();
+        sqlx::query("INSERT INTO users (id, username, first_name) VALUES (1, 'anna', 'Anna'), (2, 'oleg', 'Oleg')")
+            .execute(&pool).await.unwrap();
+        let spec = parse_quiz("op3\n? a\n- b\n+ c").unwrap();
+        let quiz_id = db::create_quiz(&pool, &spec.section, &spec.topic_id, &spec.questions).await.unwrap();
+        let question_id = db::get_quiz_questions(&pool, quiz_id).await.unwrap()[0].0;
+
+        assert!(db::answer_quiz_poll(&pool, "early", 1, 1).await.unwrap());
+        db::record_quiz_poll(&pool, "early", question_id, 1).await.unwrap();
+        db::record_quiz_poll(&pool, "late", question_id, 2).await.unwrap();
+        assert!(db::answer_quiz_poll(&pool, "late", 2, 0).await.unwrap());
+        assert!(!db::answer_quiz_poll(&pool, "late", 2, 1).await.unwrap());
+
+        let rows = sqlx::query_as::<_, (String, i64, i64)>("SELECT poll_id, option_id, is_correct FROM quiz_polls ORDER BY poll_id")
+            .fetch_all(&pool).await.unwrap();
+        assert_eq!(rows, vec![("early".to_string(), 1, 1), ("late".to_string(), 0, 0)]);
+        let left: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM quiz_early_answers").fetch_one(&pool).await.unwrap();
+        assert_eq!(left, 0);
+    }
+}
@@ -0,0 +1,190 @@
+#[cfg(test)]
+pub mod memory;
+pub mod sql;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+
+use crate::db::DbPool;
+use crate::states::SubmissionType;
+
+pub type RepoFuture<'a, T> = BoxFuture<'a, anyhow::Result<T>>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct User {
+    pub id: i64,
+    pub username: Option<String>,
+    pub first_name: String,
+}
+
+impl User {
+    pub fn display_name(&self) -> String {
+        self.username.clone().unwrap_or_else(|| self.first_name.clone())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SubmissionStatus {
+    Submitted,
+    Withdrawn,
+}
+
+impl SubmissionStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SubmissionStatus::Submitted => "submitted",
+            SubmissionStatus::Withdrawn => "withdrawn",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "submitted" => Some(SubmissionStatus::Submitted),
+            "withdrawn" => Some(SubmissionStatus::Withdrawn),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Submission {
+    pub id: i64,
+    pub user_id: i64,
+    pub kind: SubmissionType,
+    pub section: String,
+    pub topic_id: String,
+    pub topic_title: String,
+    pub content_type: String,
+    pub summary: String,
+    pub file_id: String,
+    pub message_id: i64,
+    pub date: String,
+    pub ts: String,
+    pub status: SubmissionStatus,
+}
+
+#[derive(Clone, Debug)]
+pub struct NewSubmission {
+    pub user_id: i64,
+    pub kind: SubmissionType,
+    pub section: String,
+    pub topic_id: String,
+    pub topic_title: String,
+    pub content_type: String,
+    pub summary: String,
+    pub file_id: String,
+    pub message_id: i32,
+    pub date: String,
+    pub ts: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SubmissionTally {
+    pub user_id: i64,
+    pub kind: SubmissionType,
+    pub section: String,
+    pub topic_id: String,
+    pub date: String,
+    pub count: i64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MissReason {
+    pub user_id: i64,
+    pub date: String,
+    pub reason: String,
+}
+
+#[derive(Clone, Default)]
+pub struct SubmissionFilter {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub kind: Option<SubmissionType>,
+    pub section: Option<String>,
+    pub topic_id: Option<String>,
+    pub status: Option<SubmissionStatus>,
+    pub user_id: Option<i64>,
+    pub active_only: bool,
+}
+
+impl SubmissionFilter {
+    pub fn on_date(date: &str) -> Self {
+        SubmissionFilter { from: Some(date.to_string()), to: Some(date.to_string()), active_only: true, ..Default::default() }
+    }
+
+    pub fn matches(&self, s: &Submission) -> bool {
+        self.from.as_ref().is_none_or(|d| s.date >= *d)
+            && self.to.as_ref().is_none_or(|d| s.date <= *d)
+            && self.kind.as_ref().is_none_or(|k| s.kind == *k)
+            && self.section.as_ref().is_none_or(|v| s.section == *v)
+            && self.topic_id.as_ref().is_none_or(|v| s.topic_id == *v)
+            && self.status.is_none_or(|v| s.status == v)
+            && self.user_id.is_none_or(|v| s.user_id == v)
+            && !(self.active_only && s.status == SubmissionStatus::Withdrawn)
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct MissReasonFilter {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub user_id: Option<i64>,
+}
+
+impl MissReasonFilter {
+    pub fn matches(&self, m: &MissReason) -> bool {
+        self.from.as_ref().is_none_or(|d| m.date >= *d)
+            && self.to.as_ref().is_none_or(|d| m.date <= *d)
+            && self.user_id.is_none_or(|v| m.user_id == v)
+    }
+}
+
+pub trait UserRepo: Send + Sync {
+    fn upsert(&self, user: User) -> RepoFuture<'_, bool>;
+    fn get(&self, id: i64) -> RepoFuture<'_, Option<User>>;
+    fn find<'a>(&'a self, identifier: &'a str) -> RepoFuture<'a, Option<User>>;
+    fn list(&self) -> RepoFuture<'_, Vec<User>>;
+    fn remove(&self, id: i64) -> RepoFuture<'_, ()>;
+    fn remove_all(&self) -> RepoFuture<'_, ()>;
+    fn groups(&self) -> RepoFuture<'_, HashMap<i64, String>>;
+    fn set_group<'a>(&'a self, identifier: &'a str, group: &'a str) -> RepoFuture<'a, bool>;
+}
+
+pub trait SubmissionRepo: Send + Sync {
+    fn add(&self, new: NewSubmission) -> RepoFuture<'_, i64>;
+    fn get(&self, id: i64) -> RepoFuture<'_, Option<Submission>>;
+    fn list<'a>(&'a self, filter: &'a SubmissionFilter, limit: Option<i64>, offset: i64) -> RepoFuture<'a, Vec<Submission>>;
+    fn count<'a>(&'a self, filter: &'a SubmissionFilter) -> RepoFuture<'a, i64>;
+    fn withdraw(&self, user_id: i64, id: i64) -> RepoFuture<'_, bool>;
+    fn tally<'a>(&'a self, filter: &'a SubmissionFilter) -> RepoFuture<'a, Vec<SubmissionTally>>;
+}
+
+pub trait MissReasonRepo: Send + Sync {
+    fn request<'a>(&'a self, user_id: i64, date: &'a str) -> RepoFuture<'a, ()>;
+    fn is_waiting(&self, user_id: i64) -> RepoFuture<'_, bool>;
+    fn answer<'a>(&'a self, user_id: i64, reason: &'a str) -> RepoFuture<'a, ()>;
+    fn list<'a>(&'a self, filter: &'a MissReasonFilter) -> RepoFuture<'a, Vec<MissReason>>;
+    fn count_by_user<'a>(&'a self, filter: &'a MissReasonFilter) -> RepoFuture<'a, HashMap<i64, i64>>;
+}
+
+#[derive(Clone)]
+pub struct Repos {
+    pub users: Arc<dyn UserRepo>,
+    pub submissions: Arc<dyn SubmissionRepo>,
+    pub miss_reasons: Arc<dyn MissReasonRepo>,
+}
+
+impl Repos {
+    pub fn sql(pool: DbPool) -> Self {
+        let repo = Arc::new(sql::SqlRepo::new(pool));
+        Repos { users: repo.clone(), submissions: repo.clone(), miss_reasons: repo }
+    }
+
+    #[cfg(test)]
+    pub fn in_memory() -> Self {
+        let repo = Arc::new(memory::MemoryRepo::default());
+        Repos { users: repo.clone(), submissions: repo.clone(), miss_reasons: repo }
+    }
+}
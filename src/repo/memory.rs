@@ -0,0 +1,213 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+use super::{
+    MissReason, MissReasonFilter, MissReasonRepo, NewSubmission, RepoFuture, Submission, SubmissionFilter,
+    SubmissionRepo, SubmissionStatus, SubmissionTally, User, UserRepo,
+};
+
+#[derive(Default)]
+struct Data {
+    users: BTreeMap<i64, User>,
+    groups: HashMap<i64, String>,
+    submissions: Vec<Submission>,
+    miss_reasons: BTreeMap<(i64, String), String>,
+}
+
+#[derive(Default)]
+pub struct MemoryRepo {
+    data: Mutex<Data>,
+}
+
+impl MemoryRepo {
+    fn with<T>(&self, f: impl FnOnce(&mut Data) -> T) -> T {
+        f(&mut self.data.lock().unwrap())
+    }
+}
+
+impl UserRepo for MemoryRepo {
+    fn upsert(&self, user: User) -> RepoFuture<'_, bool> {
+        let is_new = self.with(|d| d.users.insert(user.id, user).is_none());
+        Box::pin(async move { Ok(is_new) })
+    }
+
+    fn get(&self, id: i64) -> RepoFuture<'_, Option<User>> {
+        let user = self.with(|d| d.users.get(&id).cloned());
+        Box::pin(async move { Ok(user) })
+    }
+
+    fn find<'a>(&'a self, identifier: &'a str) -> RepoFuture<'a, Option<User>> {
+        let user = self.with(|d| match identifier.parse::<i64>() {
+            Ok(id) => d.users.get(&id).cloned(),
+            Err(_) => d.users.values().find(|u| u.username.as_deref() == Some(identifier)).cloned(),
+        });
+        Box::pin(async move { Ok(user) })
+    }
+
+    fn list(&self) -> RepoFuture<'_, Vec<User>> {
+        let users = self.with(|d| d.users.values().cloned().collect());
+        Box::pin(async move { Ok(users) })
+    }
+
+    fn remove(&self, id: i64) -> RepoFuture<'_, ()> {
+        self.with(|d| {
+            d.users.remove(&id);
+            d.groups.remove(&id);
+            d.submissions.retain(|s| s.user_id != id);
+            d.miss_reasons.retain(|(user_id, _), _| *user_id != id);
+        });
+        Box::pin(async move { Ok(()) })
+    }
+
+    fn remove_all(&self) -> RepoFuture<'_, ()> {
+        self.with(|d| *d = Data::default());
+        Box::pin(async move { Ok(()) })
+    }
+
+    fn groups(&self) -> RepoFuture<'_, HashMap<i64, String>> {
+        let groups = self.with(|d| d.groups.iter().filter(|(_, g)| !g.is_empty()).map(|(id, g)| (*id, g.clone())).collect());
+        Box::pin(async move { Ok(groups) })
+    }
+
+    fn set_group<'a>(&'a self, identifier: &'a str, group: &'a str) -> RepoFuture<'a, bool> {
+        let updated = self.with(|d| {
+            let id = match identifier.parse::<i64>() {
+                Ok(id) => d.users.get(&id).map(|u| u.id),
+                Err(_) => d.users.values().find(|u| u.username.as_deref() == Some(identifier)).map(|u| u.id),
+            };
+            match id {
+                Some(id) => {
+                    d.groups.insert(id, group.to_string());
+                    true
+                }
+                None => false,
+            }
+        });
+        Box::pin(async move { Ok(updated) })
+    }
+}
+
+impl SubmissionRepo for MemoryRepo {
+    fn add(&self, new: NewSubmission) -> RepoFuture<'_, i64> {
+        let id = self.with(|d| {
+            let id = d.submissions.len() as i64 + 1;
+            d.submissions.push(Submission {
+                id,
+                user_id: new.user_id,
+                kind: new.kind,
+                section: new.section,
+                topic_id: new.topic_id,
+                topic_title: new.topic_title,
+                content_type: new.content_type,
+                summary: new.summary,
+                file_id: new.file_id,
+                message_id: new.message_id as i64,
+                date: new.date,
+                ts: new.ts,
+                status: SubmissionStatus::Submitted,
+            });
+            id
+        });
+        Box::pin(async move { Ok(id) })
+    }
+
+    fn get(&self, id: i64) -> RepoFuture<'_, Option<Submission>> {
+        let submission = self.with(|d| d.submissions.iter().find(|s| s.id == id).cloned());
+        Box::pin(async move { Ok(submission) })
+    }
+
+    fn list<'a>(&'a self, filter: &'a SubmissionFilter, limit: Option<i64>, offset: i64) -> RepoFuture<'a, Vec<Submission>> {
+        let mut found: Vec<Submission> = self.with(|d| d.submissions.iter().filter(|s| filter.matches(s)).cloned().collect());
+        found.sort_by(|a, b| b.ts.cmp(&a.ts).then(b.id.cmp(&a.id)));
+        let page = found.into_iter()
+            .skip(offset.max(0) as usize)
+            .take(limit.map_or(usize::MAX, |l| l.max(0) as usize))
+            .collect();
+        Box::pin(async move { Ok(page) })
+    }
+
+    fn count<'a>(&'a self, filter: &'a SubmissionFilter) -> RepoFuture<'a, i64> {
+        let count = self.with(|d| d.submissions.iter().filter(|s| filter.matches(s)).count() as i64);
+        Box::pin(async move { Ok(count) })
+    }
+
+    fn withdraw(&self, user_id: i64, id: i64) -> RepoFuture<'_, bool> {
+        let done = self.with(|d| {
+            match d.submissions.iter_mut().find(|s| s.id == id && s.user_id == user_id && s.status == SubmissionStatus::Submitted) {
+                Some(s) => {
+                    s.status = SubmissionStatus::Withdrawn;
+                    true
+                }
+                None => false,
+            }
+        });
+        Box::pin(async move { Ok(done) })
+    }
+
+    fn tally<'a>(&'a self, filter: &'a SubmissionFilter) -> RepoFuture<'a, Vec<SubmissionTally>> {
+        let tally = self.with(|d| {
+            let mut counts: BTreeMap<(String, i64, String, String, &'static str), SubmissionTally> = BTreeMap::new();
+            for s in d.submissions.iter().filter(|s| filter.matches(s)) {
+                let key = (s.date.clone(), s.user_id, s.section.clone(), s.topic_id.clone(), s.kind.as_str());
+                counts.entry(key).or_insert_with(|| SubmissionTally {
+                    user_id: s.user_id,
+                    kind: s.kind.clone(),
+                    section: s.section.clone(),
+                    topic_id: s.topic_id.clone(),
+                    date: s.date.clone(),
+                    count: 0,
+                }).count += 1;
+            }
+            counts.into_values().collect()
+        });
+        Box::pin(async move { Ok(tally) })
+    }
+}
+
+impl MissReasonRepo for MemoryRepo {
+    fn request<'a>(&'a self, user_id: i64, date: &'a str) -> RepoFuture<'a, ()> {
+        self.with(|d| {
+            d.miss_reasons.entry((user_id, date.to_string())).or_default();
+        });
+        Box::pin(async move { Ok(()) })
+    }
+
+    fn is_waiting(&self, user_id: i64) -> RepoFuture<'_, bool> {
+        let waiting = self.with(|d| d.miss_reasons.iter().any(|((uid, _), reason)| *uid == user_id && reason.is_empty()));
+        Box::pin(async move { Ok(waiting) })
+    }
+
+    fn answer<'a>(&'a self, user_id: i64, reason: &'a str) -> RepoFuture<'a, ()> {
+        self.with(|d| {
+            for ((uid, _), stored) in d.miss_reasons.iter_mut() {
+                if *uid == user_id && stored.is_empty() {
+                    *stored = reason.to_string();
+                }
+            }
+        });
+        Box::pin(async move { Ok(()) })
+    }
+
+    fn list<'a>(&'a self, filter: &'a MissReasonFilter) -> RepoFuture<'a, Vec<MissReason>> {
+        let mut found: Vec<MissReason> = self.with(|d| {
+            d.miss_reasons.iter()
+                .map(|((user_id, date), reason)| MissReason { user_id: *user_id, date: date.clone(), reason: reason.clone() })
+                .filter(|m| filter.matches(m))
+                .collect()
+        });
+        found.sort_by(|a, b| b.date.cmp(&a.date).then(a.user_id.cmp(&b.user_id)));
+        Box::pin(async move { Ok(found) })
+    }
+    fn count_by_user<'a>(&'a self, filter: &'a MissReasonFilter) -> RepoFuture<'a, HashMap<i64, i64>> {
+        let mut counts: HashMap<i64, i64> = HashMap::new();
+        self.with(|d| {
+            for ((user_id, date), reason) in d.miss_reasons.iter() {
+                let m = MissReason { user_id: *user_id, date: date.clone(), reason: reason.clone() };
+                if filter.matches(&m) {
+                    *counts.entry(m.user_id).or_insert(0) += 1;
+                }
+            }
+        });
+        Box::pin(async move { Ok(counts) })
+    }
+}
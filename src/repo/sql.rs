@@ -0,0 +1,308 @@
+use std::collections::HashMap;
+
+use sqlx::any::AnyRow;
+use sqlx::Row;
+
+use crate::db::{self, DbPool};
+use crate::states::SubmissionType;
+
+use super::{
+    MissReason, MissReasonFilter, MissReasonRepo, NewSubmission, RepoFuture, Submission, SubmissionFilter,
+    SubmissionRepo, SubmissionStatus, SubmissionTally, User, UserRepo,
+};
+
+const SUBMISSION_COLUMNS: &str =
+    "id, user_id, type, section, topic_id, topic_title, content_type, content_summary, photo_file_id, message_id, date, ts, status";
+
+const SUBMISSION_WHERE: &str =
+    "WHERE ($1 IS NULL OR date >= $1) AND ($2 IS NULL OR date <= $2)
+       AND ($3 IS NULL OR type = $3) AND ($4 IS NULL OR section = $4)
+       AND ($5 IS NULL OR topic_id = $5) AND ($6 IS NULL OR status = $6)
+       AND ($7 IS NULL OR user_id = $7) AND ($8 = 0 OR status != 'withdrawn')";
+
+pub struct SqlRepo {
+    pool: DbPool,
+}
+
+impl SqlRepo {
+    pub fn new(pool: DbPool) -> Self {
+        SqlRepo { pool }
+    }
+}
+
+fn user_from_row(row: &AnyRow) -> User {
+    let username: Option<String> = row.get("username");
+    User {
+        id: row.get("id"),
+        username: username.filter(|u| !u.is_empty()),
+        first_name: row.get::<Option<String>, _>("first_name").unwrap_or_default(),
+    }
+}
+
+fn submission_from_row(row: &AnyRow) -> anyhow::Result<Submission> {
+    let kind: String = row.get("type");
+    let status: String = row.get("status");
+    Ok(Submission {
+        id: row.get("id"),
+        user_id: row.get("user_id"),
+        kind: SubmissionType::parse(&kind).ok_or_else(|| anyhow::anyhow!("Unknown submission type {:?}", kind))?,
+        section: row.get("section"),
+        topic_id: row.get("topic_id"),
+        topic_title: row.get("topic_title"),
+        content_type: row.get("content_type"),
+        summary: row.get("content_summary"),
+        file_id: row.get::<Option<String>, _>("photo_file_id").unwrap_or_default(),
+        message_id: row.get::<Option<i64>, _>("message_id").unwrap_or(0),
+        date: row.get("date"),
+        ts: row.get("ts"),
+        status: SubmissionStatus::parse(&status).ok_or_else(|| anyhow::anyhow!("Unknown submission status {:?}", status))?,
+    })
+}
+
+fn bind_filter<'q>(
+    query: sqlx::query::Query<'q, sqlx::Any, sqlx::any::AnyArguments<'q>>,
+    filter: &'q SubmissionFilter,
+) -> sqlx::query::Query<'q, sqlx::Any, sqlx::any::AnyArguments<'q>> {
+    query
+        .bind(&filter.from)
+        .bind(&filter.to)
+        .bind(filter.kind.as_ref().map(SubmissionType::as_str))
+        .bind(&filter.section)
+        .bind(&filter.topic_id)
+        .bind(filter.status.map(|s| s.as_str()))
+        .bind(filter.user_id)
+        .bind(filter.active_only as i64)
+}
+
+impl UserRepo for SqlRepo {
+    fn upsert(&self, user: User) -> RepoFuture<'_, bool> {
+        Box::pin(async move {
+            let known = sqlx::query("SELECT 1 FROM users WHERE id = $1").bind(user.id).fetch_optional(&self.pool).await?.is_some();
+            sqlx::query("INSERT INTO users (id, username, first_name) VALUES ($1, $2, $3)
+                 ON CONFLICT (id) DO UPDATE SET username = excluded.username, first_name = excluded.first_name")
+                .bind(user.id)
+                .bind(user.username.unwrap_or_default())
+                .bind(user.first_name)
+                .execute(&self.pool)
+                .await?;
+            Ok(!known)
+        })
+    }
+
+    fn get(&self, id: i64) -> RepoFuture<'_, Option<User>> {
+        Box::pin(async move {
+            let row = sqlx::query("SELECT id, username, first_name FROM users WHERE id = $1")
+                .bind(id)
+                .fetch_optional(&self.pool)
+                .await?;
+            Ok(row.as_ref().map(user_from_row))
+        })
+    }
+
+    fn find<'a>(&'a self, identifier: &'a str) -> RepoFuture<'a, Option<User>> {
+        Box::pin(async move {
+            if let Ok(id) = identifier.parse::<i64>() {
+                return UserRepo::get(self, id).await;
+            }
+            let row = sqlx::query("SELECT id, username, first_name FROM users WHERE username = $1")
+                .bind(identifier)
+                .fetch_optional(&self.pool)
+                .await?;
+            Ok(row.as_ref().map(user_from_row))
+        })
+    }
+
+    fn list(&self) -> RepoFuture<'_, Vec<User>> {
+        Box::pin(async move {
+            let rows = sqlx::query("SELECT id, username, first_name FROM users ORDER BY id").fetch_all(&self.pool).await?;
+            Ok(rows.iter().map(user_from_row).collect())
+        })
+    }
+
+    fn remove(&self, id: i64) -> RepoFuture<'_, ()> {
+        Box::pin(async move {
+            db::delete_user_rows(&self.pool, id).await
+        })
+    }
+
+    fn remove_all(&self) -> RepoFuture<'_, ()> {
+        Box::pin(async move {
+            db::reset_database(&self.pool).await
+        })
+    }
+
+    fn groups(&self) -> RepoFuture<'_, HashMap<i64, String>> {
+        Box::pin(async move {
+            let rows = sqlx::query_as::<_, (i64, String)>("SELECT id, group_name FROM users WHERE group_name != ''")
+                .fetch_all(&self.pool)
+                .await?;
+            Ok(rows.into_iter().collect())
+        })
+    }
+
+    fn set_group<'a>(&'a self, identifier: &'a str, group: &'a str) -> RepoFuture<'a, bool> {
+        Box::pin(async move {
+            db::set_user_group(&self.pool, identifier, group).await
+        })
+    }
+}
+
+impl SubmissionRepo for SqlRepo {
+    fn add(&self, new: NewSubmission) -> RepoFuture<'_, i64> {
+        Box::pin(async move {
+            let res = sqlx::query(
+                "INSERT INTO submissions (
+                    user_id, type, section, topic_id, topic_title, content_type,
+                    content_summary, photo_file_id, message_id, date, ts
+                ) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+                RETURNING id"
+            )
+                .bind(new.user_id)
+                .bind(new.kind.as_str())
+                .bind(new.section)
+                .bind(new.topic_id)
+                .bind(new.topic_title)
+                .bind(new.content_type)
+                .bind(new.summary)
+                .bind(new.file_id)
+                .bind(new.message_id)
+                .bind(new.date)
+                .bind(new.ts)
+                .fetch_one(&self.pool)
+                .await?;
+            Ok(res.get(0))
+        })
+    }
+
+    fn get(&self, id: i64) -> RepoFuture<'_, Option<Submission>> {
+        Box::pin(async move {
+            let row = sqlx::query(&format!("SELECT {} FROM submissions WHERE id = $1", SUBMISSION_COLUMNS))
+                .bind(id)
+                .fetch_optional(&self.pool)
+                .await?;
+            row.as_ref().map(submission_from_row).transpose()
+        })
+    }
+
+    fn list<'a>(&'a self, filter: &'a SubmissionFilter, limit: Option<i64>, offset: i64) -> RepoFuture<'a, Vec<Submission>> {
+        Box::pin(async move {
+            let sql = format!(
+                "SELECT {} FROM submissions {} ORDER BY ts DESC, id DESC LIMIT $9 OFFSET $10",
+                SUBMISSION_COLUMNS, SUBMISSION_WHERE
+            );
+            let rows = bind_filter(sqlx::query(&sql), filter)
+                .bind(limit.unwrap_or(i64::MAX))
+                .bind(offset)
+                .fetch_all(&self.pool)
+                .await?;
+            rows.iter().map(submission_from_row).collect()
+        })
+    }
+
+    fn count<'a>(&'a self, filter: &'a SubmissionFilter) -> RepoFuture<'a, i64> {
+        Box::pin(async move {
+            let sql = format!("SELECT COUNT(*) FROM submissions {}", SUBMISSION_WHERE);
+            let row = bind_filter(sqlx::query(&sql), filter).fetch_one(&self.pool).await?;
+            Ok(row.get(0))
+        })
+    }
+
+    fn withdraw(&self, user_id: i64, id: i64) -> RepoFuture<'_, bool> {
+        Box::pin(async move {
+            let res = sqlx::query("UPDATE submissions SET status = 'withdrawn' WHERE id = $1 AND user_id = $2 AND status = 'submitted'")
+                .bind(id)
+                .bind(user_id)
+                .execute(&self.pool)
+                .await?;
+            Ok(res.rows_affected() > 0)
+        })
+    }
+
+    fn tally<'a>(&'a self, filter: &'a SubmissionFilter) -> RepoFuture<'a, Vec<SubmissionTally>> {
+        Box::pin(async move {
+            let sql = format!(
+                "SELECT user_id, type, section, topic_id, date, COUNT(*) AS cnt FROM submissions {}
+                 GROUP BY user_id, type, section, topic_id, date
+                 ORDER BY date, user_id, section, topic_id, type",
+                SUBMISSION_WHERE
+            );
+            let rows = bind_filter(sqlx::query(&sql), filter).fetch_all(&self.pool).await?;
+            rows.iter().map(|row| {
+                let kind: String = row.get("type");
+                Ok(SubmissionTally {
+                    user_id: row.get("user_id"),
+                    kind: SubmissionType::parse(&kind).ok_or_else(|| anyhow::anyhow!("Unknown submission type {:?}", kind))?,
+                    section: row.get("section"),
+                    topic_id: row.get("topic_id"),
+                    date: row.get("date"),
+                    count: row.get("cnt"),
+                })
+            }).collect()
+        })
+    }
+}
+
+impl MissReasonRepo for SqlRepo {
+    fn request<'a>(&'a self, user_id: i64, date: &'a str) -> RepoFuture<'a, ()> {
+        Box::pin(async move {
+            sqlx::query("INSERT INTO miss_reasons (user_id, date, reason) VALUES ($1, $2, '') ON CONFLICT DO NOTHING")
+                .bind(user_id)
+                .bind(date)
+                .execute(&self.pool)
+                .await?;
+            Ok(())
+        })
+    }
+
+    fn is_waiting(&self, user_id: i64) -> RepoFuture<'_, bool> {
+        Box::pin(async move {
+            let result = sqlx::query("SELECT 1 FROM miss_reasons WHERE user_id = $1 AND reason = ''")
+                .bind(user_id)
+                .fetch_optional(&self.pool)
+                .await?;
+            Ok(result.is_some())
+        })
+    }
+
+    fn answer<'a>(&'a self, user_id: i64, reason: &'a str) -> RepoFuture<'a, ()> {
+        Box::pin(async move {
+            sqlx::query("UPDATE miss_reasons SET reason = $1 WHERE user_id = $2 AND reason = ''")
+                .bind(reason)
+                .bind(user_id)
+                .execute(&self.pool)
+                .await?;
+            Ok(())
+        })
+    }
+
+    fn list<'a>(&'a self, filter: &'a MissReasonFilter) -> RepoFuture<'a, Vec<MissReason>> {
+        Box::pin(async move {
+            let rows = sqlx::query_as::<_, (i64, String, String)>(
+                "SELECT user_id, date, reason FROM miss_reasons
+                 WHERE ($1 IS NULL OR date >= $1) AND ($2 IS NULL OR date <= $2) AND ($3 IS NULL OR user_id = $3)
+                 ORDER BY date DESC, user_id"
+            )
+                .bind(&filter.from)
+                .bind(&filter.to)
+                .bind(filter.user_id)
+                .fetch_all(&self.pool)
+                .await?;
+            Ok(rows.into_iter().map(|(user_id, date, reason)| MissReason { user_id, date, reason }).collect())
+        })
+    }
+    fn count_by_user<'a>(&'a self, filter: &'a MissReasonFilter) -> RepoFuture<'a, HashMap<i64, i64>> {
+        Box::pin(async move {
+            let rows = sqlx::query_as::<_, (i64, i64)>(
+                "SELECT user_id, COUNT(*) FROM miss_reasons
+                 WHERE ($1 IS NULL OR date >= $1) AND ($2 IS NULL OR date <= $2) AND ($3 IS NULL OR user_id = $3)
+                 GROUP BY user_id"
+            )
+                .bind(&filter.from)
+                .bind(&filter.to)
+                .bind(filter.user_id)
+                .fetch_all(&self.pool)
+                .await?;
+            Ok(rows.into_iter().collect())
+        })
+    }
+}
@@ -1,16 +1,27 @@
-use std::io::{Cursor, Write};
-use std::path::Path;
-use std::collections::HashMap;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 use teloxide::{prelude::*, types::InputFile};
 use teloxide::net::Download;
-use sqlx::{SqlitePool, Row};
-use rust_xlsxwriter::{Workbook, Format, Color as XlsxColor};
+use sqlx::Row;
+use rust_xlsxwriter::{Workbook, Format, Color as XlsxColor, ConditionalFormat3ColorScale};
 use plotters::prelude::*;
 use plotters::style::Color;
 use zip::write::FileOptions;
 use walkdir::WalkDir;
-use chrono::Utc;
+use chrono::{Duration, NaiveDate, Utc};
+use log::warn;
+
+use crate::db::DbPool;
+use crate::repo::{MissReason, MissReasonFilter, Repos, Submission, SubmissionFilter, User};
+use crate::states::SubmissionType;
+use crate::{db, keyboards, pdf, tasks};
+
+const CHART_SIZE: (u32, u32) = (800, 600);
+const MAX_ARCHIVE_PART_BYTES: u64 = 45 * 1024 * 1024;
+const ZIP_ENTRY_OVERHEAD: u64 = 512;
+const WITHDRAWN_DIR: &str = "_withdrawn";
 
 fn slugify(s: &str) -> String {
     s.chars()
@@ -20,21 +31,23 @@ fn slugify(s: &str) -> String {
 
 pub async fn save_text_to_disk(
     text: &str,
+    extension: &str,
     base_dir: &str,
     user_id: i64,
     section: &str,
-    topic_id: &str
-) -> anyhow::Result<()> {
+    topic_id: &str,
+    submission_id: i64
+) -> anyhow::Result<u64> {
     let safe_sec = slugify(section);
     let safe_topic = slugify(topic_id);
     let dir_path = format!("{}/{}/{}_{}", base_dir, user_id, safe_sec, safe_topic);
 
     tokio::fs::create_dir_all(&dir_path).await?;
-    let filename = format!("{}.txt", Utc::now().format("%Y%m%d_%H%M%S"));
+    let filename = format!("{}_s{}.{}", Utc::now().format("%Y%m%d_%H%M%S"), submission_id, extension);
     let full_path = format!("{}/{}", dir_path, filename);
 
     tokio::fs::write(full_path, text).await?;
-    Ok(())
+    Ok(text.len() as u64)
 }
 
 pub async fn save_file_to_disk(
@@ -43,8 +56,9 @@ pub async fn save_file_to_disk(
     base_dir: &str,
     user_id: i64,
     section: &str,
-    topic_id: &str
-) -> anyhow::Result<()> {
+    topic_id: &str,
+    submission_id: i64
+) -> anyhow::Result<u64> {
     let safe_sec = slugify(section);
     let safe_topic = slugify(topic_id);
     let dir_path = format!("{}/{}/{}_{}", base_dir, user_id, safe_sec, safe_topic);
@@ -53,16 +67,93 @@ pub async fn save_file_to_disk(
 
     let file_info = bot.get_file(file_id.to_string()).await?;
     let extension = file_info.path.split('.').last().unwrap_or("jpg");
-    let filename = format!("file_{}.{}", Utc::now().format("%Y%m%d_%H%M%S_%f"), extension);
+    let filename = format!("file_{}_s{}.{}", Utc::now().format("%Y%m%d_%H%M%S_%f"), submission_id, extension);
     let full_path = format!("{}/{}", dir_path, filename);
 
     let mut dst = tokio::fs::File::create(full_path).await?;
     bot.download_file(&file_info.path, &mut dst).await?;
 
-    Ok(())
+    Ok(dst.metadata().await?.len())
+}
+
+pub struct DaySummary {
+    pub user_id: i64,
+    pub name: String,
+    pub dz_count: i64,
+    pub conspect_count: i64,
+    pub reason: String,
+    pub task_flag: String,
 }
 
-pub async fn generate_daily_report(pool: &SqlitePool, date: &str) -> anyhow::Result<Vec<u8>> {
+pub struct TopicMatrix {
+    pub students: Vec<(i64, String)>,
+    pub counts: HashMap<(i64, String), i64>,
+}
+
+pub async fn day_submissions(repos: &Repos, date: &str) -> anyhow::Result<Vec<(Submission, Option<User>)>> {
+    let users: HashMap<i64, User> = repos.users.list().await?.into_iter().map(|u| (u.id, u)).collect();
+    let mut submissions = repos.submissions.list(&SubmissionFilter::on_date(date), None, 0).await?;
+    submissions.reverse();
+    Ok(submissions.into_iter().map(|s| {
+        let user = users.get(&s.user_id).cloned();
+        (s, user)
+    }).collect())
+}
+
+pub async fn daily_summary(repos: &Repos, date: &str) -> anyhow::Result<Vec<DaySummary>> {
+    let day = repos.submissions.list(&SubmissionFilter::on_date(date), None, 0).await?;
+    let reasons: HashMap<i64, String> = repos.miss_reasons
+        .list(&MissReasonFilter { from: Some(date.to_string()), to: Some(date.to_string()), user_id: None })
+        .await?
+        .into_iter()
+        .map(|m| (m.user_id, m.reason))
+        .collect();
+
+    let mut summary = Vec::new();
+    for user in repos.users.list().await? {
+        let own: Vec<&Submission> = day.iter().filter(|s| s.user_id == user.id).collect();
+        summary.push(DaySummary {
+            user_id: user.id,
+            name: user.display_name(),
+            dz_count: own.iter().filter(|s| s.kind == SubmissionType::Dz).count() as i64,
+            conspect_count: own.iter().filter(|s| s.kind == SubmissionType::Conspect).count() as i64,
+            reason: reasons.get(&user.id).cloned().unwrap_or_default(),
+            task_flag: own.first().map(|s| s.topic_title.clone()).unwrap_or_default(),
+        });
+    }
+    Ok(summary)
+}
+
+pub async fn users_without_submissions(repos: &Repos, date: &str) -> anyhow::Result<Vec<i64>> {
+    let submitted: HashSet<i64> = repos.submissions.list(&SubmissionFilter::on_date(date), None, 0).await?
+        .into_iter()
+        .map(|s| s.user_id)
+        .collect();
+    Ok(repos.users.list().await?.into_iter().map(|u| u.id).filter(|id| !submitted.contains(id)).collect())
+}
+
+pub async fn miss_reasons_since(repos: &Repos, since: &str) -> anyhow::Result<Vec<(MissReason, String)>> {
+    let names: HashMap<i64, String> = repos.users.list().await?.into_iter().map(|u| (u.id, u.display_name())).collect();
+    let filter = MissReasonFilter { from: Some(since.to_string()), ..Default::default() };
+    Ok(repos.miss_reasons.list(&filter).await?.into_iter().map(|m| {
+        let name = names.get(&m.user_id).cloned().unwrap_or_default();
+        (m, name)
+    }).collect())
+}
+
+pub async fn topic_matrix(repos: &Repos, section: &str) -> anyhow::Result<TopicMatrix> {
+    let students = repos.users.list().await?.into_iter().map(|u| (u.id, u.display_name())).collect();
+
+    let filter = SubmissionFilter { section: Some(section.to_string()), active_only: true, ..Default::default() };
+    let mut counts = HashMap::new();
+    for s in repos.submissions.list(&filter, None, 0).await? {
+        *counts.entry((s.user_id, s.topic_title)).or_insert(0) += 1;
+    }
+
+    Ok(TopicMatrix { students, counts })
+}
+
+pub async fn generate_daily_report(repos: &Repos, date: &str) -> anyhow::Result<Vec<u8>> {
     let mut workbook = Workbook::new();
 
     let sheet_raw = workbook.add_worksheet().set_name("raw_submissions")?;
@@ -72,26 +163,18 @@ pub async fn generate_daily_report(pool: &SqlitePool, date: &str) -> anyhow::Res
         "User ID", "Username", "Name", "Type", "Section", "Topic", "Summary", "Date", "TS"
     ], &header_format)?;
 
-    let rows = sqlx::query(
-        "SELECT s.user_id, u.username, u.first_name, s.type, s.section, s.topic_title, s.content_summary, s.date, s.ts
-         FROM submissions s LEFT JOIN users u ON s.user_id = u.id WHERE s.date = ?"
-    )
-        .bind(date)
-        .fetch_all(pool)
-        .await?;
-
-    for (i, row) in rows.iter().enumerate() {
+    for (i, (sub, user)) in day_submissions(repos, date).await?.into_iter().enumerate() {
         let r = (i + 1) as u32;
-        let uid: i64 = row.get("user_id");
-        sheet_raw.write(r, 0, uid)?;
-        sheet_raw.write(r, 1, row.get::<Option<String>, _>("username").unwrap_or_default())?;
-        sheet_raw.write(r, 2, row.get::<Option<String>, _>("first_name").unwrap_or_default())?;
-        sheet_raw.write(r, 3, row.get::<String, _>("type"))?;
-        sheet_raw.write(r, 4, row.get::<String, _>("section"))?;
-        sheet_raw.write(r, 5, row.get::<String, _>("topic_title"))?;
-        sheet_raw.write(r, 6, row.get::<String, _>("content_summary"))?;
-        sheet_raw.write(r, 7, row.get::<String, _>("date"))?;
-        sheet_raw.write(r, 8, row.get::<String, _>("ts"))?;
+        let (username, first_name) = user.map(|u| (u.username.unwrap_or_default(), u.first_name)).unwrap_or_default();
+        sheet_raw.write(r, 0, sub.user_id)?;
+        sheet_raw.write(r, 1, username)?;
+        sheet_raw.write(r, 2, first_name)?;
+        sheet_raw.write(r, 3, sub.kind.as_str())?;
+        sheet_raw.write(r, 4, sub.section)?;
+        sheet_raw.write(r, 5, sub.topic_title)?;
+        sheet_raw.write(r, 6, sub.summary)?;
+        sheet_raw.write(r, 7, sub.date)?;
+        sheet_raw.write(r, 8, sub.ts)?;
     }
     sheet_raw.autofit();
 
@@ -100,44 +183,32 @@ pub async fn generate_daily_report(pool: &SqlitePool, date: &str) -> anyhow::Res
         "User ID", "Name", "DZ Submitted", "Conspect Submitted", "Miss Reason", "Task Flag"
     ], &header_format)?;
 
-    let users = sqlx::query("SELECT id, username, first_name FROM users ORDER BY id").fetch_all(pool).await?;
-
-    for (i, user_row) in users.iter().enumerate() {
+    for (i, row) in daily_summary(repos, date).await?.into_iter().enumerate() {
         let r = (i + 1) as u32;
-        let uid: i64 = user_row.get("id");
-        let uname: Option<String> = user_row.get("username");
-        let fname: String = user_row.get("first_name");
-        let display_name = uname.unwrap_or(fname);
-
-        let dz_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM submissions WHERE user_id = ? AND date = ? AND type = 'dz'")
-            .bind(uid).bind(date).fetch_one(pool).await.unwrap_or(0);
-
-        let conspect_count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM submissions WHERE user_id = ? AND date = ? AND type = 'conspect'")
-            .bind(uid).bind(date).fetch_one(pool).await.unwrap_or(0);
-
-        let reason: String = sqlx::query_scalar("SELECT reason FROM miss_reasons WHERE user_id = ? AND date = ?")
-            .bind(uid).bind(date).fetch_one(pool).await.unwrap_or_default();
-
-        let task_flag: String = sqlx::query_scalar(
-            "SELECT topic_title FROM submissions WHERE user_id = ? AND date = ? ORDER BY ts DESC LIMIT 1"
-        ).bind(uid).bind(date).fetch_one(pool).await.unwrap_or_default();
-
-        sheet_sum.write(r, 0, uid)?;
-        sheet_sum.write(r, 1, display_name)?;
-        sheet_sum.write(r, 2, dz_count)?;
-        sheet_sum.write(r, 3, conspect_count)?;
-        sheet_sum.write(r, 4, reason)?;
-        sheet_sum.write(r, 5, task_flag)?;
+        sheet_sum.write(r, 0, row.user_id)?;
+        sheet_sum.write(r, 1, row.name)?;
+        sheet_sum.write(r, 2, row.dz_count)?;
+        sheet_sum.write(r, 3, row.conspect_count)?;
+        sheet_sum.write(r, 4, row.reason)?;
+        sheet_sum.write(r, 5, row.task_flag)?;
     }
     sheet_sum.autofit();
 
     Ok(workbook.save_to_buffer()?)
 }
 
+pub fn encode_png(buffer: Vec<u8>, (w, h): (u32, u32)) -> anyhow::Result<Vec<u8>> {
+    let img = image::RgbImage::from_raw(w, h, buffer)
+        .ok_or_else(|| anyhow::anyhow!("Chart buffer size mismatch"))?;
+    let mut out = Cursor::new(Vec::new());
+    img.write_to(&mut out, image::ImageOutputFormat::Png)?;
+    Ok(out.into_inner())
+}
+
 fn create_miss_chart(data: &HashMap<String, i64>) -> anyhow::Result<Vec<u8>> {
-    let mut buffer = vec![0; 0];
+    let mut buffer = vec![0; (CHART_SIZE.0 * CHART_SIZE.1 * 3) as usize];
     {
-        let root = BitMapBackend::with_buffer(&mut buffer, (800, 600)).into_drawing_area();
+        let root = BitMapBackend::with_buffer(&mut buffer, CHART_SIZE).into_drawing_area();
         root.fill(&WHITE)?;
 
         let mut sorted_data: Vec<(&String, &i64)> = data.iter().collect();
@@ -179,13 +250,13 @@ fn create_miss_chart(data: &HashMap<String, i64>) -> anyhow::Result<Vec<u8>> {
         )?;
         root.present()?;
     }
-    Ok(buffer)
+    encode_png(buffer, CHART_SIZE)
 }
 
 fn create_top_students_chart(data: &HashMap<String, i64>, title: &str) -> anyhow::Result<Vec<u8>> {
-    let mut buffer = vec![0; 0];
+    let mut buffer = vec![0; (CHART_SIZE.0 * CHART_SIZE.1 * 3) as usize];
     {
-        let root = BitMapBackend::with_buffer(&mut buffer, (800, 600)).into_drawing_area();
+        let root = BitMapBackend::with_buffer(&mut buffer, CHART_SIZE).into_drawing_area();
         root.fill(&WHITE)?;
 
         let mut sorted_data: Vec<(&String, &i64)> = data.iter().collect();
@@ -226,10 +297,309 @@ fn create_top_students_chart(data: &HashMap<String, i64>, title: &str) -> anyhow
         )?;
         root.present()?;
     }
-    Ok(buffer)
+    encode_png(buffer, CHART_SIZE)
 }
 
-pub async fn generate_full_history_package(pool: &SqlitePool) -> anyhow::Result<Vec<InputFile>> {
+fn create_mock_progress_chart(points: &[MockResult], target_score: i64, title: &str) -> anyhow::Result<Vec<u8>> {
+    let mut buffer = vec![0; (CHART_SIZE.0 * CHART_SIZE.1 * 3) as usize];
+    {
+        let root = BitMapBackend::with_buffer(&mut buffer, CHART_SIZE).into_drawing_area();
+        root.fill(&WHITE)?;
+
+        if points.is_empty() { return Ok(vec![]); }
+        let n = points.len();
+        let max_primary = (1..=27).map(tasks::max_points).sum::<i64>() as i32;
+
+        let mut chart = ChartBuilder::on(&root)
+            .caption(title, ("sans-serif", 30))
+            .margin(10)
+            .x_label_area_size(40)
+            .y_label_area_size(40)
+            .right_y_label_area_size(40)
+            .build_cartesian_2d((0..(n - 1).max(1)).into_segmented(), 0..100i32)?
+            .set_secondary_coord((0..(n - 1).max(1)).into_segmented(), 0..max_primary);
+
+        chart.configure_mesh()
+            .x_labels(n)
+            .y_desc("Тестовый балл")
+            .x_label_formatter(&|x| match x {
+                SegmentValue::CenterOf(i) if *i < n => points[*i].date[5..].to_string(),
+                _ => "".to_string()
+            })
+            .draw()?;
+        chart.configure_secondary_axes()
+            .y_desc("Первичный балл")
+            .draw()?;
+
+        let center = |i: usize| SegmentValue::CenterOf(i);
+
+        chart.draw_series(LineSeries::new(
+            [(SegmentValue::Exact(0), target_score as i32), (SegmentValue::Last, target_score as i32)],
+            RED.stroke_width(2)
+        ))?
+            .label(format!("Цель: {}", target_score))
+            .legend(|(x, y)| PathElement::new([(x, y), (x + 20, y)], RED.stroke_width(2)));
+
+        chart.draw_series(LineSeries::new(
+            points.iter().enumerate().map(|(i, p)| (center(i), p.class_avg.round() as i32)),
+            BLACK.mix(0.4).stroke_width(2)
+        ))?
+            .label("Среднее по классу")
+            .legend(|(x, y)| PathElement::new([(x, y), (x + 20, y)], BLACK.mix(0.4).stroke_width(2)));
+
+        chart.draw_series(LineSeries::new(
+            points.iter().enumerate().map(|(i, p)| (center(i), p.test as i32)),
+            BLUE.stroke_width(3)
+        ).point_size(5))?
+            .label("Тестовый")
+            .legend(|(x, y)| PathElement::new([(x, y), (x + 20, y)], BLUE.stroke_width(3)));
+
+        chart.draw_secondary_series(LineSeries::new(
+            points.iter().enumerate().map(|(i, p)| (center(i), p.primary as i32)),
+            GREEN.stroke_width(2)
+        ).point_size(4))?
+            .label("Первичный")
+            .legend(|(x, y)| PathElement::new([(x, y), (x + 20, y)], GREEN.stroke_width(2)));
+
+        chart.configure_series_labels()
+            .position(SeriesLabelPosition::LowerRight)
+            .background_style(WHITE.mix(0.8))
+            .border_style(BLACK)
+            .draw()?;
+        root.present()?;
+    }
+    encode_png(buffer, CHART_SIZE)
+}
+
+fn create_activity_chart(days: &[(String, i64, i64)]) -> anyhow::Result<Vec<u8>> {
+    let mut buffer = vec![0; (CHART_SIZE.0 * CHART_SIZE.1 * 3) as usize];
+    {
+        let root = BitMapBackend::with_buffer(&mut buffer, CHART_SIZE).into_drawing_area();
+        root.fill(&WHITE)?;
+
+        if days.is_empty() { return Ok(vec![]); }
+        let max_val = days.iter().map(|(_, dz, c)| (dz + c) as i32).max().unwrap_or(0) + 1;
+
+        let mut chart = ChartBuilder::on(&root)
+            .caption("Моя активность за 2 недели", ("sans-serif", 30))
+            .margin(10)
+            .x_label_area_size(40)
+            .y_label_area_size(40)
+            .build_cartesian_2d(
+                (0..days.len()).into_segmented(),
+                0..max_val
+            )?;
+
+        chart.configure_mesh()
+            .x_labels(days.len())
+            .x_label_formatter(&|x| match x {
+                SegmentValue::CenterOf(i) | SegmentValue::Exact(i) => {
+                    if *i < days.len() { days[*i].0.clone() } else { "".to_string() }
+                },
+                _ => "".to_string()
+            })
+            .draw()?;
+
+        chart.draw_series(
+            days.iter().enumerate().map(|(x, (_, dz, _))| {
+                Rectangle::new(
+                    [(x.into(), 0), (x.into(), *dz as i32)],
+                    BLUE.filled()
+                )
+            })
+        )?
+            .label("ДЗ")
+            .legend(|(x, y)| Rectangle::new([(x, y - 5), (x + 10, y + 5)], BLUE.filled()));
+
+        chart.draw_series(
+            days.iter().enumerate().map(|(x, (_, dz, conspect))| {
+                Rectangle::new(
+                    [(x.into(), *dz as i32), (x.into(), (dz + conspect) as i32)],
+                    GREEN.filled()
+                )
+            })
+        )?
+            .label("Конспекты")
+            .legend(|(x, y)| Rectangle::new([(x, y - 5), (x + 10, y + 5)], GREEN.filled()));
+
+        chart.configure_series_labels()
+            .background_style(WHITE.mix(0.8))
+            .border_style(BLACK)
+            .draw()?;
+        root.present()?;
+    }
+    encode_png(buffer, CHART_SIZE)
+}
+
+pub fn compute_streaks(dates: &[NaiveDate], today: NaiveDate) -> (i64, i64) {
+    let mut best = 0;
+    let mut run = 0;
+    let mut prev: Option<NaiveDate> = None;
+
+    for &d in dates {
+        run = match prev {
+            Some(p) if d - p == Duration::days(1) => run + 1,
+            Some(p) if d == p => run,
+            _ => 1,
+        };
+        best = best.max(run);
+        prev = Some(d);
+    }
+
+    let current = match prev {
+        Some(last) if today - last <= Duration::days(1) => run,
+        _ => 0,
+    };
+
+    (current, best)
+}
+
+pub async fn generate_user_stats(pool: &DbPool, repos: &Repos, user_id: i64) -> anyhow::Result<(String, Vec<u8>)> {
+    let today = Utc::now().date_naive();
+    let retention_since = (today - Duration::days(29)).format("%Y-%m-%d").to_string();
+    let retention = db::card_retention(pool, user_id, &retention_since).await?;
+    let (text, days, total) = user_stats(repos, user_id, today, &retention).await?;
+    let png = if total > 0 { create_activity_chart(&days)? } else { Vec::new() };
+    Ok((text, png))
+}
+
+type CardRetention = (String, String, i64, i64, i64, i64);
+type ActivityDay = (String, i64, i64);
+
+async fn user_stats(
+    repos: &Repos,
+    user_id: i64,
+    today: NaiveDate,
+    retention: &[CardRetention]
+) -> anyhow::Result<(String, Vec<ActivityDay>, i64)> {
+    let mut text = String::from("📈 Твоя статистика\n");
+    let tally = repos.submissions.tally(&SubmissionFilter { user_id: Some(user_id), active_only: true, ..Default::default() }).await?;
+
+    let mut totals: BTreeMap<(String, &str), i64> = BTreeMap::new();
+    for t in tally.iter() {
+        let label = if t.kind == SubmissionType::Dz { "ДЗ" } else { "Конспекты" };
+        *totals.entry((t.section.clone(), label)).or_insert(0) += t.count;
+    }
+    let dz_total: i64 = tally.iter().filter(|t| t.kind == SubmissionType::Dz).map(|t| t.count).sum();
+    let conspect_total: i64 = tally.iter().filter(|t| t.kind == SubmissionType::Conspect).map(|t| t.count).sum();
+    let per_section: Vec<String> = totals.iter()
+        .map(|((section, label), cnt)| format!("  • {} / {}: {}", section, label, cnt))
+        .collect();
+
+    text.push_str(&format!("\nВсего ДЗ: {}\nВсего конспектов: {}\n", dz_total, conspect_total));
+    if !per_section.is_empty() {
+        text.push_str(&format!("{}\n", per_section.join("\n")));
+    }
+
+    let mut dates: Vec<NaiveDate> = tally.iter()
+        .filter_map(|t| NaiveDate::parse_from_str(&t.date, "%Y-%m-%d").ok())
+        .collect();
+    dates.sort();
+    dates.dedup();
+
+    let (current, best) = compute_streaks(&dates, today);
+    text.push_str(&format!("\n🔥 Серия: {} дн. (рекорд: {} дн.)\n", current, best));
+
+    if !retention.is_empty() {
+        text.push_str("\n🃏 Карточки за 30 дней (вспомнил / выучено):\n");
+        for (section, topic_id, knew, reviews, learned, cards) in retention.iter() {
+            text.push_str(&format!(
+                "  • {}: {}% ({} из {}), выучено {}/{}\n",
+                keyboards::get_topic_title(section, topic_id).unwrap_or_else(|| topic_id.clone()),
+                knew * 100 / reviews.max(&1), knew, reviews, learned, cards
+            ));
+        }
+    }
+
+    text.push_str("\nЕщё не сдано:\n");
+    for section in keyboards::SECTIONS {
+        let missing: Vec<String> = keyboards::section_topics(section).into_iter()
+            .filter(|(id, _)| !tally.iter().any(|t| t.section == section && t.topic_id == *id))
+            .map(|(_, title)| title)
+            .collect();
+
+        if missing.is_empty() {
+            text.push_str(&format!("  • {}: всё сдано ✅\n", section));
+        } else {
+            text.push_str(&format!("  • {}: {}\n", section, missing.join(", ")));
+        }
+    }
+
+    let mut days: Vec<ActivityDay> = Vec::new();
+    for offset in (0..14).rev() {
+        let day = (today - Duration::days(offset)).format("%Y-%m-%d").to_string();
+        let count = |kind: SubmissionType| tally.iter()
+            .filter(|t| t.date == day && t.kind == kind)
+            .map(|t| t.count)
+            .sum::<i64>();
+        days.push((day[5..].to_string(), count(SubmissionType::Dz), count(SubmissionType::Conspect)));
+    }
+
+    let week: Vec<&ActivityDay> = days.iter().skip(7).collect();
+    let week_total: i64 = week.iter().map(|(_, dz, c)| dz + c).sum();
+    let week_active = week.iter().filter(|(_, dz, c)| dz + c > 0).count();
+    text.push_str(&format!("\nЗа последние 7 дней: {} работ, активных дней: {}/7", week_total, week_active));
+
+    Ok((text, days, dz_total + conspect_total))
+}
+
+struct MockResult {
+    date: String,
+    variant: String,
+    primary: i64,
+    test: i64,
+    class_avg: f64,
+}
+
+async fn mock_results(pool: &DbPool, user_id: i64) -> anyhow::Result<Vec<MockResult>> {
+    let rows = sqlx::query(
+        "SELECT substr(m.finished_ts, 1, 10) AS date, v.title,
+                COALESCE(m.primary_score, 0) AS primary_score, COALESCE(m.test_score, 0) AS test_score,
+                (SELECT CAST(AVG(o.test_score) AS DOUBLE PRECISION) FROM mock_attempts o
+                 WHERE o.variant_id = m.variant_id AND o.finished_ts IS NOT NULL) AS class_avg
+         FROM mock_attempts m JOIN mock_variants v ON v.id = m.variant_id
+         WHERE m.user_id = $1 AND m.finished_ts IS NOT NULL
+         ORDER BY m.finished_ts"
+    ).bind(user_id).fetch_all(pool).await?;
+
+    Ok(rows.iter().map(|row| MockResult {
+        date: row.get("date"),
+        variant: row.get("title"),
+        primary: row.get("primary_score"),
+        test: row.get("test_score"),
+        class_avg: row.get::<Option<f64>, _>("class_avg").unwrap_or(0.0),
+    }).collect())
+}
+
+pub async fn generate_mock_progress(pool: &DbPool, user_id: i64, target_score: i64) -> anyhow::Result<(String, Vec<u8>)> {
+    let results = mock_results(pool, user_id).await?;
+    if results.is_empty() {
+        return Ok(("Ты ещё не писал(а) пробники.".to_string(), Vec::new()));
+    }
+
+    let mut text = String::from("📈 Результаты пробников\n");
+    for r in results.iter() {
+        text.push_str(&format!(
+            "\n{} · {}: {} перв. / {} тест. (класс: {:.0})",
+            r.date, r.variant, r.primary, r.test, r.class_avg
+        ));
+    }
+    if let (Some(first), Some(last)) = (results.first(), results.last()) {
+        if results.len() > 1 {
+            text.push_str(&format!("\n\nРост с первого пробника: {:+} тест. баллов", last.test - first.test));
+        }
+        if last.test < target_score {
+            text.push_str(&format!("\nДо цели {} осталось {} баллов", target_score, target_score - last.test));
+        } else {
+            text.push_str(&format!("\nЦель {} достигнута 🎉", target_score));
+        }
+    }
+
+    let png = create_mock_progress_chart(&results, target_score, "Мои пробники")?;
+    Ok((text, png))
+}
+
+pub async fn generate_full_history_package(pool: &DbPool, repos: &Repos, target_score: i64) -> anyhow::Result<Vec<InputFile>> {
     let mut files = Vec::new();
 
     let mut workbook = Workbook::new();
@@ -240,41 +610,57 @@ pub async fn generate_full_history_package(pool: &SqlitePool) -> anyhow::Result<
         "Date", "User ID", "Name", "Type", "Topic", "Summary"
     ], &header_format)?;
 
-    let rows = sqlx::query(
-        "SELECT s.date, s.user_id, u.first_name, s.type, s.topic_title, s.content_summary
-         FROM submissions s LEFT JOIN users u ON s.user_id = u.id ORDER BY s.date DESC"
-    ).fetch_all(pool).await?;
+    let names: HashMap<i64, String> = repos.users.list().await?.into_iter().map(|u| (u.id, u.first_name)).collect();
+    let submissions = repos.submissions.list(&SubmissionFilter { active_only: true, ..Default::default() }, None, 0).await?;
 
     let mut dz_stats: HashMap<String, i64> = HashMap::new();
     let mut conspect_stats: HashMap<String, i64> = HashMap::new();
     let mut miss_stats: HashMap<String, i64> = HashMap::new();
 
-    for (i, row) in rows.iter().enumerate() {
+    for (i, sub) in submissions.iter().enumerate() {
         let r = (i + 1) as u32;
-        let date: String = row.get("date");
-        let uid: i64 = row.get("user_id");
-        let name: String = row.get("first_name");
-        let type_: String = row.get("type");
+        let name = names.get(&sub.user_id).cloned().unwrap_or_default();
 
-        sheet.write(r, 0, &date)?;
-        sheet.write(r, 1, uid)?;
+        sheet.write(r, 0, &sub.date)?;
+        sheet.write(r, 1, sub.user_id)?;
         sheet.write(r, 2, &name)?;
-        sheet.write(r, 3, &type_)?;
-        sheet.write(r, 4, row.get::<String, _>("topic_title"))?;
-        sheet.write(r, 5, row.get::<String, _>("content_summary"))?;
-
-        if type_ == "dz" {
-            *dz_stats.entry(name.clone()).or_insert(0) += 1;
-        } else if type_ == "conspect" {
-            *conspect_stats.entry(name.clone()).or_insert(0) += 1;
+        sheet.write(r, 3, sub.kind.as_str())?;
+        sheet.write(r, 4, &sub.topic_title)?;
+        sheet.write(r, 5, &sub.summary)?;
+
+        let stats = match sub.kind { SubmissionType::Dz => &mut dz_stats, SubmissionType::Conspect => &mut conspect_stats };
+        *stats.entry(name).or_insert(0) += 1;
+    }
+
+    for (uid, count) in repos.miss_reasons.count_by_user(&MissReasonFilter::default()).await? {
+        if let Some(name) = names.get(&uid) {
+            *miss_stats.entry(name.clone()).or_insert(0) += count;
         }
     }
 
-    let misses = sqlx::query("SELECT u.first_name FROM miss_reasons m JOIN users u ON m.user_id = u.id")
-        .fetch_all(pool).await?;
-    for row in misses {
-        let name: String = row.get("first_name");
-        *miss_stats.entry(name).or_insert(0) += 1;
+    let mock_sheet = workbook.add_worksheet().set_name("MOCK_EXAMS")?;
+    mock_sheet.write_row_with_format(0, 0, [
+        "Finished", "User ID", "Name", "Variant", "Primary", "Test"
+    ], &header_format)?;
+
+    let mock_rows = sqlx::query(
+        "SELECT m.finished_ts, m.user_id, COALESCE(u.first_name, '') AS first_name, v.title,
+                COALESCE(m.primary_score, 0) AS primary_score, COALESCE(m.test_score, 0) AS test_score
+         FROM mock_attempts m
+         JOIN mock_variants v ON v.id = m.variant_id
+         LEFT JOIN users u ON u.id = m.user_id
+         WHERE m.finished_ts IS NOT NULL
+         ORDER BY m.finished_ts DESC"
+    ).fetch_all(pool).await?;
+
+    for (i, row) in mock_rows.iter().enumerate() {
+        let r = (i + 1) as u32;
+        mock_sheet.write(r, 0, row.get::<String, _>("finished_ts").get(..10).unwrap_or(""))?;
+        mock_sheet.write(r, 1, row.get::<i64, _>("user_id"))?;
+        mock_sheet.write(r, 2, row.get::<String, _>("first_name"))?;
+        mock_sheet.write(r, 3, row.get::<String, _>("title"))?;
+        mock_sheet.write(r, 4, row.get::<i64, _>("primary_score"))?;
+        mock_sheet.write(r, 5, row.get::<i64, _>("test_score"))?;
     }
 
     files.push(InputFile::memory(workbook.save_to_buffer()?).file_name("history.xlsx"));
@@ -289,71 +675,665 @@ pub async fn generate_full_history_package(pool: &SqlitePool) -> anyhow::Result<
         if !png.is_empty() { files.push(InputFile::memory(png).file_name("misses.png")); }
     }
 
+    let mock_users = sqlx::query(
+        "SELECT DISTINCT m.user_id, COALESCE(u.first_name, '') AS first_name
+         FROM mock_attempts m LEFT JOIN users u ON u.id = m.user_id
+         WHERE m.finished_ts IS NOT NULL ORDER BY m.user_id"
+    ).fetch_all(pool).await?;
+    for row in mock_users {
+        let uid: i64 = row.get("user_id");
+        let name: String = row.get("first_name");
+        let results = mock_results(pool, uid).await?;
+        if let Ok(png) = create_mock_progress_chart(&results, target_score, &format!("Пробники: {}", name)) {
+            if !png.is_empty() { files.push(InputFile::memory(png).file_name(format!("mock_progress_{}.png", uid))); }
+        }
+    }
+
     Ok(files)
 }
 
-pub async fn archive_user_conspects(base_dir: &str, user_id: i64) -> anyhow::Result<Vec<u8>> {
-    let user_path = format!("{}/{}", base_dir, user_id);
-    let path = Path::new(&user_path);
+pub struct TaskErrorReport {
+    pub text: String,
+    pub heatmap: Vec<u8>,
+    pub excel: Vec<u8>,
+}
 
-    if !path.exists() {
-        return Ok(Vec::new());
+#[derive(Default, Clone, Copy)]
+struct SolveCell {
+    auto: i64,
+    teacher: i64,
+    total: i64,
+}
+
+impl SolveCell {
+    fn add(&mut self, other: &SolveCell) {
+        self.auto += other.auto;
+        self.teacher += other.teacher;
+        self.total += other.total;
+    }
+
+    fn rate(&self) -> Option<f64> {
+        (self.total > 0).then(|| (self.auto + self.teacher) as f64 / self.total as f64)
+    }
+}
+
+struct SolveStats {
+    students: Vec<(i64, String, String)>,
+    cells: HashMap<(i64, i64), SolveCell>,
+    groups: BTreeMap<String, HashMap<i64, SolveCell>>,
+    class: HashMap<i64, SolveCell>,
+}
+
+impl SolveStats {
+    fn rates(cells: impl Fn(i64) -> Option<SolveCell>) -> Vec<Option<f64>> {
+        (1..=27).map(|n| cells(n).and_then(|c| c.rate())).collect()
+    }
+}
+
+async fn collect_solve_stats(pool: &DbPool, repos: &Repos, since: Option<NaiveDate>) -> anyhow::Result<SolveStats> {
+    let since = since.map(|d| d.format("%Y-%m-%d").to_string()).unwrap_or_default();
+
+    let mut cells: HashMap<(i64, i64), SolveCell> = HashMap::new();
+    let mut class: HashMap<i64, SolveCell> = HashMap::new();
+    for (uid, n, auto, teacher, total) in db::solve_counts(pool, &since).await? {
+        let part = SolveCell { auto, teacher, total };
+        cells.entry((uid, n)).or_default().add(&part);
+        class.entry(n).or_default().add(&part);
     }
 
-    let mut buf = Vec::new();
-    let mut zip = zip::ZipWriter::new(Cursor::new(&mut buf));
-    let options = FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+    let groups_by_user = repos.users.groups().await?;
+    let mut students: Vec<(i64, String, String)> = repos.users.list().await?.into_iter()
+        .filter(|u| cells.keys().any(|(uid, _)| *uid == u.id))
+        .map(|u| {
+            let name = Some(u.first_name.clone())
+                .filter(|n| !n.is_empty())
+                .or(u.username)
+                .unwrap_or_else(|| format!("user_{}", u.id));
+            (u.id, name, groups_by_user.get(&u.id).cloned().unwrap_or_default())
+        })
+        .collect();
+    students.sort_by(|a, b| a.2.cmp(&b.2).then(a.1.cmp(&b.1)).then(a.0.cmp(&b.0)));
 
-    let walker = WalkDir::new(path).into_iter();
-    for entry in walker.filter_map(|e| e.ok()) {
-        let path = entry.path();
-        if path.is_file() {
-            let name = path.strip_prefix(&user_path).unwrap().to_str().unwrap();
-            zip.start_file(name, options)?;
-            let content = std::fs::read(path)?;
-            zip.write_all(&content)?;
+    let mut groups: BTreeMap<String, HashMap<i64, SolveCell>> = BTreeMap::new();
+    for (uid, _, group) in students.iter().filter(|(_, _, g)| !g.is_empty()) {
+        let totals = groups.entry(group.clone()).or_default();
+        for n in 1..=27 {
+            if let Some(cell) = cells.get(&(*uid, n)) {
+                totals.entry(n).or_default().add(cell);
+            }
         }
     }
-    zip.finish()?;
-    drop(zip);
 
-    Ok(buf)
+    Ok(SolveStats { students, cells, groups, class })
 }
 
-pub async fn export_user_data(pool: &SqlitePool, base_dir: &str, identifier: &str) -> anyhow::Result<(Vec<u8>, Option<Vec<u8>>)> {
-    let user_opt = if let Ok(id) = identifier.parse::<i64>() {
-        sqlx::query("SELECT id, username FROM users WHERE id = ?").bind(id).fetch_optional(pool).await?
+fn heat_color(rate: f64) -> RGBColor {
+    let lerp = |a: u8, b: u8, t: f64| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+    let (from, to, t) = if rate < 0.5 {
+        ((0xF8, 0x69, 0x6B), (0xFF, 0xEB, 0x84), rate * 2.0)
     } else {
-        sqlx::query("SELECT id, username FROM users WHERE username = ?").bind(identifier).fetch_optional(pool).await?
+        ((0xFF, 0xEB, 0x84), (0x63, 0xBE, 0x7B), (rate - 0.5) * 2.0)
     };
+    RGBColor(lerp(from.0, to.0, t), lerp(from.1, to.1, t), lerp(from.2, to.2, t))
+}
 
-    let (uid, _) = match user_opt {
-        Some(row) => (row.get::<i64, _>("id"), row.get::<Option<String>, _>("username").unwrap_or_default()),
-        None => return Err(anyhow::anyhow!("User not found")),
-    };
+fn create_solve_heatmap(rows: &[(String, Vec<Option<f64>>)]) -> anyhow::Result<Vec<u8>> {
+    if rows.len() < 2 { return Ok(vec![]); }
+
+    let size = (1200, 120 + 32 * rows.len() as u32);
+    let mut buffer = vec![0; (size.0 * size.1 * 3) as usize];
+    {
+        let root = BitMapBackend::with_buffer(&mut buffer, size).into_drawing_area();
+        root.fill(&WHITE)?;
+
+        let mut chart = ChartBuilder::on(&root)
+            .caption("Решаемость заданий ЕГЭ", ("sans-serif", 30))
+            .margin(10)
+            .x_label_area_size(30)
+            .y_label_area_size(160)
+            .build_cartesian_2d(
+                (1..27i32).into_segmented(),
+                (0..rows.len() - 1).into_segmented()
+            )?;
+
+        chart.configure_mesh()
+            .disable_mesh()
+            .x_labels(27)
+            .y_labels(rows.len())
+            .x_label_formatter(&|x| match x {
+                SegmentValue::CenterOf(n) => n.to_string(),
+                _ => "".to_string()
+            })
+            .y_label_formatter(&|y| match y {
+                SegmentValue::CenterOf(i) if *i < rows.len() => rows[rows.len() - 1 - *i].0.clone(),
+                _ => "".to_string()
+            })
+            .draw()?;
+
+        for (i, (_, rates)) in rows.iter().enumerate() {
+            let y = rows.len() - 1 - i;
+            for (n, rate) in (1..28i32).zip(rates.iter()) {
+                let color = rate.map(heat_color).unwrap_or(RGBColor(235, 235, 235));
+                chart.draw_series(std::iter::once(Rectangle::new(
+                    [(SegmentValue::Exact(n), SegmentValue::Exact(y)), (SegmentValue::Exact(n + 1), SegmentValue::Exact(y + 1))],
+                    color.filled()
+                )))?;
+                if let Some(rate) = rate {
+                    chart.draw_series(std::iter::once(Text::new(
+                        format!("{:.0}", rate * 100.0),
+                        (SegmentValue::CenterOf(n), SegmentValue::CenterOf(y)),
+                        ("sans-serif", 13).into_font().color(&BLACK)
+                    )))?;
+                }
+            }
+        }
+        root.present()?;
+    }
+    encode_png(buffer, size)
+}
+
+pub async fn generate_task_error_report(pool: &DbPool, repos: &Repos, since: Option<NaiveDate>) -> anyhow::Result<TaskErrorReport> {
+    let stats = collect_solve_stats(pool, repos, since).await?;
+    let period = since.map(|d| format!("с {}", d.format("%d.%m.%Y"))).unwrap_or_else(|| "за всё время".to_string());
+
+    if stats.students.is_empty() {
+        return Ok(TaskErrorReport {
+            text: format!("🔥 Ошибки по заданиям {}: данных пока нет.", period),
+            heatmap: vec![],
+            excel: vec![],
+        });
+    }
+
+    let mut rows: Vec<(String, Vec<Option<f64>>)> = vec![
+        ("Весь класс".to_string(), SolveStats::rates(|n| stats.class.get(&n).copied()))
+    ];
+    for (group, cells) in stats.groups.iter() {
+        rows.push((format!("Группа {}", group), SolveStats::rates(|n| cells.get(&n).copied())));
+    }
+    for (uid, name, _) in stats.students.iter() {
+        rows.push((name.clone(), SolveStats::rates(|n| stats.cells.get(&(*uid, n)).copied())));
+    }
+
+    let mut workbook = Workbook::new();
+    let header_format = Format::new().set_bold().set_background_color(XlsxColor::RGB(0xA7F3D0));
+    let percent_format = Format::new().set_num_format("0%");
+
+    let sheet = workbook.add_worksheet().set_name("solve_rate")?;
+    sheet.write_with_format(0, 0, "Ученик", &header_format)?;
+    for n in 1..=27u16 {
+        sheet.write_with_format(0, n, n as i64, &header_format)?;
+    }
+    for (i, (name, rates)) in rows.iter().enumerate() {
+        let r = (i + 1) as u32;
+        sheet.write(r, 0, name)?;
+        for (col, rate) in (1..=27u16).zip(rates.iter()) {
+            if let Some(rate) = rate {
+                sheet.write_with_format(r, col, *rate, &percent_format)?;
+            }
+        }
+    }
+    sheet.add_conditional_format(1, 1, rows.len() as u32, 27, &ConditionalFormat3ColorScale::new())?;
+    sheet.set_freeze_panes(1, 1)?;
+    sheet.autofit();
+
+    let raw = workbook.add_worksheet().set_name("attempts")?;
+    raw.write_row_with_format(0, 0, ["User ID", "Name", "Group", "Task", "Auto Points", "Teacher Points", "Max Points"], &header_format)?;
+    let mut r = 1;
+    for (uid, name, group) in stats.students.iter() {
+        for n in 1..=27 {
+            if let Some(cell) = stats.cells.get(&(*uid, n)) {
+                raw.write(r, 0, *uid)?;
+                raw.write(r, 1, name)?;
+                raw.write(r, 2, group)?;
+                raw.write(r, 3, n)?;
+                raw.write(r, 4, cell.auto)?;
+                raw.write(r, 5, cell.teacher)?;
+                raw.write(r, 6, cell.total)?;
+                r += 1;
+            }
+        }
+    }
+    raw.autofit();
+
+    let mut worst: Vec<(i64, f64, i64)> = stats.class.iter()
+        .filter_map(|(n, cell)| cell.rate().map(|rate| (*n, rate, cell.total)))
+        .collect();
+    worst.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+
+    let lines: Vec<String> = worst.iter().take(5)
+        .map(|(n, rate, total)| format!("Задание {}: решено {:.0}% (баллов из {})", n, rate * 100.0, total))
+        .collect();
+    let mut text = format!("🔥 Ошибки по заданиям {}.\nХуже всего решают:\n{}", period, lines.join("\n"));
+    if !stats.groups.is_empty() {
+        text.push_str("\n\nПо группам:");
+        for (group, cells) in stats.groups.iter() {
+            let mut total = SolveCell::default();
+            cells.values().for_each(|cell| total.add(cell));
+            if let Some(rate) = total.rate() {
+                text.push_str(&format!("\n{}: решено {:.0}%", group, rate * 100.0));
+            }
+        }
+    }
+
+    Ok(TaskErrorReport {
+        text,
+        heatmap: create_solve_heatmap(&rows)?,
+        excel: workbook.save_to_buffer()?,
+    })
+}
+
+#[derive(Clone, Debug)]
+pub enum ConspectSelection {
+    All,
+    Section(String),
+    Topic(String, String),
+    Dates(NaiveDate, NaiveDate),
+}
+
+fn conspect_file_date(path: &Path) -> Option<NaiveDate> {
+    let name = path.file_name()?.to_str()?;
+    let stamp = name.trim_start_matches("file_").get(..8)?;
+    NaiveDate::parse_from_str(stamp, "%Y%m%d").ok()
+}
+
+fn conspect_sort_key(path: &Path) -> (String, String) {
+    let dir = path.parent().map(|p| p.to_string_lossy().to_string()).unwrap_or_default();
+    let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    (dir, name.trim_start_matches("file_").to_string())
+}
+
+fn conspect_submission_id(path: &Path) -> Option<i64> {
+    let stem = path.file_stem()?.to_str()?;
+    stem.rsplit_once("_s")?.1.parse().ok()
+}
+
+pub async fn withdraw_conspect_files(base_dir: &str, user_id: i64, submission_id: i64) -> anyhow::Result<usize> {
+    let user_path = PathBuf::from(format!("{}/{}", base_dir, user_id));
+    let files: Vec<PathBuf> = collect_conspect_files(base_dir, user_id, &ConspectSelection::All)
+        .into_iter()
+        .filter(|p| conspect_submission_id(p) == Some(submission_id))
+        .collect();
+
+    for path in files.iter() {
+        let topic_dir = path.parent().and_then(|p| p.file_name()).unwrap_or_default();
+        let target = user_path.join(WITHDRAWN_DIR).join(topic_dir);
+        tokio::fs::create_dir_all(&target).await?;
+        tokio::fs::rename(path, target.join(path.file_name().unwrap_or_default())).await?;
+    }
+    Ok(files.len())
+}
+
+pub fn collect_conspect_files(base_dir: &str, user_id: i64, selection: &ConspectSelection) -> Vec<PathBuf> {
+    let user_path = format!("{}/{}", base_dir, user_id);
+
+    let mut files: Vec<PathBuf> = WalkDir::new(&user_path).into_iter()
+        .filter_entry(|e| !(e.depth() == 1 && e.file_name() == WITHDRAWN_DIR))
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_file())
+        .map(|e| e.into_path())
+        .filter(|path| {
+            let dir = path.parent()
+                .and_then(|p| p.file_name())
+                .and_then(|n| n.to_str())
+                .unwrap_or("");
+
+            match selection {
+                ConspectSelection::All => true,
+                ConspectSelection::Section(section) => dir.starts_with(&format!("{}_", slugify(section))),
+                ConspectSelection::Topic(section, topic_id) => dir == format!("{}_{}", slugify(section), slugify(topic_id)),
+                ConspectSelection::Dates(from, to) => conspect_file_date(path).is_some_and(|d| d >= *from && d <= *to),
+            }
+        })
+        .collect();
+
+    files.sort_by_key(|p| conspect_sort_key(p));
+    files
+}
+
+fn write_zip_parts(root: &Path, files: &[PathBuf], user_id: i64, parts: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+    let options = FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    let stamp = Utc::now().format("%Y%m%d_%H%M%S_%f");
+    let mut zip: Option<zip::ZipWriter<std::fs::File>> = None;
+    let mut part_size = 0u64;
+
+    for path in files {
+        let size = std::fs::metadata(path)?.len() + ZIP_ENTRY_OVERHEAD;
+
+        let writer = match zip.as_mut() {
+            Some(writer) if part_size + size <= MAX_ARCHIVE_PART_BYTES => writer,
+            _ => {
+                if let Some(mut full) = zip.take() {
+                    full.finish()?;
+                }
+                let part_path = std::env::temp_dir()
+                    .join(format!("conspects_{}_{}_part{}.zip", user_id, stamp, parts.len() + 1));
+                parts.push(part_path.clone());
+                part_size = 0;
+                zip.insert(zip::ZipWriter::new(std::fs::File::create(part_path)?))
+            }
+        };
+
+        let name = path.strip_prefix(root)?.to_string_lossy().to_string();
+        writer.start_file(name, options)?;
+        std::io::copy(&mut std::fs::File::open(path)?, writer)?;
+        part_size += size;
+    }
+
+    if let Some(mut last) = zip {
+        last.finish()?;
+    }
+    Ok(())
+}
+
+pub async fn archive_user_conspects(base_dir: &str, user_id: i64, selection: &ConspectSelection) -> anyhow::Result<Vec<PathBuf>> {
+    let root = PathBuf::from(format!("{}/{}", base_dir, user_id));
+    if !root.exists() {
+        return Ok(Vec::new());
+    }
+
+    let files = collect_conspect_files(base_dir, user_id, selection);
+    if files.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    tokio::task::spawn_blocking(move || {
+        let mut parts = Vec::new();
+        if let Err(e) = write_zip_parts(&root, &files, user_id, &mut parts) {
+            for part in parts {
+                let _ = std::fs::remove_file(part);
+            }
+            return Err(e);
+        }
+        Ok(parts)
+    }).await?
+}
+
+pub async fn generate_conspects_pdf(
+    base_dir: &str,
+    user_id: i64,
+    selection: &ConspectSelection,
+    title: &str
+) -> anyhow::Result<Vec<u8>> {
+    let mut images: Vec<PathBuf> = collect_conspect_files(base_dir, user_id, selection)
+        .into_iter()
+        .filter(|p| pdf::is_image_file(p))
+        .collect();
+
+    if images.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    images.sort_by_key(|p| (conspect_sort_key(p).1, p.clone()));
+
+    let title = title.to_string();
+    let doc = tokio::task::spawn_blocking(move || pdf::build_conspect_pdf(&images, |written| {
+        let mut dates: Vec<NaiveDate> = written.iter().filter_map(|p| conspect_file_date(p)).collect();
+        dates.sort();
+        let mut title_lines = vec![title];
+        match (dates.first(), dates.last()) {
+            (Some(first), Some(last)) if first == last => title_lines.push(first.format("%d.%m.%Y").to_string()),
+            (Some(first), Some(last)) => title_lines.push(format!("{} — {}", first.format("%d.%m.%Y"), last.format("%d.%m.%Y"))),
+            _ => {}
+        }
+        title_lines.push(format!("Страниц с фото: {}", written.len()));
+        title_lines
+    })).await??;
+    if doc.len() as u64 > MAX_ARCHIVE_PART_BYTES {
+        return Err(anyhow::anyhow!("PDF exceeds upload limit ({} bytes)", doc.len()));
+    }
+    Ok(doc)
+}
+
+pub struct UserExport {
+    pub excel: Vec<u8>,
+    pub zip_parts: Vec<PathBuf>,
+    pub pdfs: Vec<(String, Vec<u8>)>,
+}
+
+pub async fn export_user_data(pool: &DbPool, repos: &Repos, base_dir: &str, identifier: &str) -> anyhow::Result<UserExport> {
+    let uid = repos.users.find(identifier).await?
+        .ok_or_else(|| anyhow::anyhow!("User not found"))?
+        .id;
 
     let mut workbook = Workbook::new();
     let sheet = workbook.add_worksheet();
-    sheet.write_row(0, 0, ["Type", "Section", "Topic", "Summary", "Date"])?;
+    sheet.write_row(0, 0, ["Type", "Section", "Topic", "Summary", "Date", "Status"])?;
 
-    let rows = sqlx::query("SELECT type, section, topic_title, content_summary, date FROM submissions WHERE user_id = ?")
-        .bind(uid)
-        .fetch_all(pool)
-        .await?;
+    let mut submissions = repos.submissions.list(&SubmissionFilter { user_id: Some(uid), ..Default::default() }, None, 0).await?;
+    submissions.reverse();
+    for (i, sub) in submissions.into_iter().enumerate() {
+        let r = (i + 1) as u32;
+        sheet.write(r, 0, sub.kind.as_str())?;
+        sheet.write(r, 1, sub.section)?;
+        sheet.write(r, 2, sub.topic_title)?;
+        sheet.write(r, 3, sub.summary)?;
+        sheet.write(r, 4, sub.date)?;
+        sheet.write(r, 5, sub.status.as_str())?;
+    }
 
-    for (i, row) in rows.iter().enumerate() {
+    let quiz_rows = sqlx::query(
+        "SELECT q.section, q.topic_id, q.ts, COUNT(p.poll_id) AS sent,
+                COUNT(p.option_id) AS answered, CAST(COALESCE(SUM(p.is_correct), 0) AS BIGINT) AS correct
+         FROM quiz_polls p
+         JOIN quiz_questions qq ON qq.id = p.question_id
+         JOIN quizzes q ON q.id = qq.quiz_id
+         WHERE p.user_id = $1
+         GROUP BY q.id
+         ORDER BY q.ts"
+    ).bind(uid).fetch_all(pool).await?;
+
+    let quiz_sheet = workbook.add_worksheet().set_name("Quizzes")?;
+    quiz_sheet.write_row(0, 0, ["Section", "Topic", "Date", "Questions", "Answered", "Correct", "Score %"])?;
+    for (i, row) in quiz_rows.iter().enumerate() {
         let r = (i + 1) as u32;
-        sheet.write(r, 0, row.get::<String, _>("type"))?;
-        sheet.write(r, 1, row.get::<String, _>("section"))?;
-        sheet.write(r, 2, row.get::<String, _>("topic_title"))?;
-        sheet.write(r, 3, row.get::<String, _>("content_summary"))?;
-        sheet.write(r, 4, row.get::<String, _>("date"))?;
+        let section: String = row.get("section");
+        let topic_id: String = row.get("topic_id");
+        let sent: i64 = row.get("sent");
+        let correct: i64 = row.get("correct");
+        quiz_sheet.write(r, 0, &section)?;
+        quiz_sheet.write(r, 1, keyboards::get_topic_title(&section, &topic_id).unwrap_or(topic_id))?;
+        quiz_sheet.write(r, 2, row.get::<String, _>("ts").get(..10).unwrap_or(""))?;
+        quiz_sheet.write(r, 3, sent)?;
+        quiz_sheet.write(r, 4, row.get::<i64, _>("answered"))?;
+        quiz_sheet.write(r, 5, correct)?;
+        quiz_sheet.write(r, 6, (correct * 100 / sent.max(1)) as f64)?;
     }
 
     let excel_buf = workbook.save_to_buffer()?;
 
-    let zip_buf = archive_user_conspects(base_dir, uid).await?;
-    let zip_opt = if zip_buf.is_empty() { None } else { Some(zip_buf) };
+    let zip_parts = archive_user_conspects(base_dir, uid, &ConspectSelection::All).await?;
+
+    let mut pdfs = Vec::new();
+    for section in keyboards::SECTIONS {
+        let selection = ConspectSelection::Section(section.to_string());
+        match generate_conspects_pdf(base_dir, uid, &selection, section).await {
+            Ok(doc) if !doc.is_empty() => pdfs.push((format!("{}.pdf", slugify(section)), doc)),
+            Ok(_) => {}
+            Err(e) => warn!("PDF export for user {} / {} failed: {:?}", uid, section, e),
+        }
+    }
+
+    Ok(UserExport { excel: excel_buf, zip_parts, pdfs })
+}
+
+pub async fn delete_user_fully(repos: &Repos, base_dir: &str, identifier: &str) -> anyhow::Result<()> {
+    let uid = repos.users.find(identifier).await?
+        .ok_or_else(|| anyhow::anyhow!("User not found"))?
+        .id;
 
-    Ok((excel_buf, zip_opt))
-}
\ No newline at end of file
+    repos.users.remove(uid).await?;
+
+    let user_path = format!("{}/{}", base_dir, uid);
+    if Path::new(&user_path).exists() {
+        tokio::fs::remove_dir_all(user_path).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::NewSubmission;
+
+    fn submission(user_id: i64, kind: SubmissionType, topic: &str, date: &str, ts: &str) -> NewSubmission {
+        NewSubmission {
+            user_id,
+            kind,
+            section: "Основы Питона".to_string(),
+            topic_id: topic.to_string(),
+            topic_title: topic.to_string(),
+            content_type: "text".to_string(),
+            summary: String::new(),
+            file_id: String::new(),
+            message_id: 1,
+            date: date.to_string(),
+            ts: ts.to_string(),
+        }
+    }
+
+    async fn seeded() -> Repos {
+        let repos = Repos::in_memory();
+        for (id, username) in [(1, Some("anna")), (2, None), (3, Some("oleg"))] {
+            let user = User { id, username: username.map(String::from), first_name: format!("User{}", id) };
+            repos.users.upsert(user).await.unwrap();
+        }
+        repos.submissions.add(submission(1, SubmissionType::Dz, "Циклы", "2024-05-01", "2024-05-01T10:00:00Z")).await.unwrap();
+        repos.submissions.add(submission(1, SubmissionType::Conspect, "Списки", "2024-05-01", "2024-05-01T12:00:00Z")).await.unwrap();
+        let withdrawn = repos.submissions.add(submission(2, SubmissionType::Dz, "Циклы", "2024-05-01", "2024-05-01T11:00:00Z")).await.unwrap();
+        repos.submissions.withdraw(2, withdrawn).await.unwrap();
+        repos.submissions.add(submission(3, SubmissionType::Dz, "Строки", "2024-04-30", "2024-04-30T09:00:00Z")).await.unwrap();
+        repos
+    }
+
+    #[tokio::test]
+    async fn daily_summary_counts_active_submissions_and_reasons() {
+        let repos = seeded().await;
+        repos.miss_reasons.request(3, "2024-05-01").await.unwrap();
+        repos.miss_reasons.answer(3, "болел").await.unwrap();
+
+        let summary = daily_summary(&repos, "2024-05-01").await.unwrap();
+        let rows: Vec<_> = summary.iter()
+            .map(|r| (r.user_id, r.name.as_str(), r.dz_count, r.conspect_count, r.reason.as_str(), r.task_flag.as_str()))
+            .collect();
+        assert_eq!(rows, vec![
+            (1, "anna", 1, 1, "", "Списки"),
+            (2, "User2", 0, 0, "", ""),
+            (3, "oleg", 0, 0, "болел", ""),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn withdrawn_submissions_do_not_count_as_submitted() {
+        let repos = seeded().await;
+        assert_eq!(users_without_submissions(&repos, "2024-05-01").await.unwrap(), vec![2, 3]);
+
+        let day = day_submissions(&repos, "2024-05-01").await.unwrap();
+        let topics: Vec<_> = day.iter().map(|(s, _)| s.topic_title.as_str()).collect();
+        assert_eq!(topics, vec!["Циклы", "Списки"]);
+    }
+
+    #[tokio::test]
+    async fn miss_reason_is_waiting_until_answered() {
+        let repos = seeded().await;
+        repos.miss_reasons.request(2, "2024-05-01").await.unwrap();
+        assert!(repos.miss_reasons.is_waiting(2).await.unwrap());
+
+        repos.miss_reasons.answer(2, "забыл").await.unwrap();
+        assert!(!repos.miss_reasons.is_waiting(2).await.unwrap());
+
+        let reasons = miss_reasons_since(&repos, "2024-04-01").await.unwrap();
+        assert_eq!(reasons.len(), 1);
+        assert_eq!((reasons[0].0.reason.as_str(), reasons[0].1.as_str()), ("забыл", "User2"));
+    }
+
+    #[tokio::test]
+    async fn user_stats_count_only_active_submissions() {
+        let repos = Repos::in_memory();
+        let sub = |kind, topic: &str, date: &str| {
+            let mut new = submission(5, kind, topic, date, &format!("{}T10:00:00Z", date));
+            new.topic_title = keyboards::get_topic_title("Основы Питона", topic).unwrap();
+            new
+        };
+        for new in [
+            sub(SubmissionType::Dz, "op1", "2024-04-30"),
+            sub(SubmissionType::Conspect, "op2", "2024-05-01"),
+            sub(SubmissionType::Dz, "op3", "2024-05-01"),
+        ] {
+            repos.submissions.add(new).await.unwrap();
+        }
+        let withdrawn = repos.submissions.add(sub(SubmissionType::Dz, "op4", "2024-05-02")).await.unwrap();
+        repos.submissions.withdraw(5, withdrawn).await.unwrap();
+        repos.submissions.add(submission(6, SubmissionType::Dz, "op5", "2024-05-02", "2024-05-02T10:00:00Z")).await.unwrap();
+
+        let today = NaiveDate::from_ymd_opt(2024, 5, 2).unwrap();
+        let retention = vec![("Основы Питона".to_string(), "op1".to_string(), 3, 4, 2, 5)];
+        let (text, days, total) = user_stats(&repos, 5, today, &retention).await.unwrap();
+
+        assert_eq!(total, 3);
+        assert!(text.contains("Всего ДЗ: 2\nВсего конспектов: 1\n  • Основы Питона / ДЗ: 2\n  • Основы Питона / Конспекты: 1\n"));
+        assert!(text.contains("🔥 Серия: 2 дн. (рекорд: 2 дн.)"));
+        assert!(text.contains("  • Вводный урок: 75% (3 из 4), выучено 2/5"));
+        assert!(text.contains("  • Основы Питона: Цикл while, Практика: циклы, Строки и срезы, Списки\n"));
+        assert!(text.ends_with("За последние 7 дней: 3 работ, активных дней: 2/7"));
+        assert_eq!(days.len(), 14);
+        assert_eq!(days[11..], [("04-30".to_string(), 1, 0), ("05-01".to_string(), 1, 1), ("05-02".to_string(), 0, 0)]);
+    }
+
+    #[tokio::test]
+    async fn solve_stats_prefer_teacher_scores_and_split_by_group() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = crate::db::init_db(&format!("sqlite:{}", dir.path().join("bot.db").display())).await.unwrap();
+        for sql in [
+            "INSERT INTO users (id, username, first_name, group_name) VALUES (1, 'anna', 'Anna', '11А'), (2, 'oleg', 'Oleg', '11Б'), (3, 'ivan', 'Ivan', '')",
+            "INSERT INTO mock_variants (id, title) VALUES (1, 'v1')",
+            "INSERT INTO mock_attempts (id, user_id, variant_id, finished_ts) VALUES (1, 1, 1, '2026-10-01T10:00:00Z'), (2, 2, 1, '2026-10-01T10:00:00Z')",
+            "INSERT INTO mock_answers (attempt_id, ege_number, answer, points, teacher_points) VALUES
+                (1, 27, 'a', 0, 2), (1, 1, 'b', 1, NULL), (2, 27, 'c', 2, 1), (2, 1, 'd', 0, NULL)",
+        ] {
+            sqlx::query(sql).execute(&pool).await.unwrap();
+        }
+
+        let repos = Repos::sql(pool.clone());
+        let stats = collect_solve_stats(&pool, &repos, None).await.unwrap();
+        let names: Vec<_> = stats.students.iter().map(|(_, name, group)| (name.as_str(), group.as_str())).collect();
+        assert_eq!(names, vec![("Anna", "11А"), ("Oleg", "11Б")]);
+
+        let anna = stats.cells[&(1, 27)];
+        assert_eq!((anna.auto, anna.teacher, anna.total), (0, 2, 2));
+        assert_eq!(stats.class[&27].rate(), Some(0.75));
+        assert_eq!(stats.groups["11А"][&1].rate(), Some(1.0));
+        assert_eq!(stats.groups["11Б"][&1].rate(), Some(0.0));
+
+        let report = generate_task_error_report(&pool, &repos, None).await.unwrap();
+        assert!(report.text.contains("11Б: решено 33%"));
+        assert!(!report.excel.is_empty());
+    }
+
+    #[tokio::test]
+    async fn empty_task_error_report_has_no_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = crate::db::init_db(&format!("sqlite:{}", dir.path().join("bot.db").display())).await.unwrap();
+        let report = generate_task_error_report(&pool, &Repos::sql(pool.clone()), None).await.unwrap();
+        assert!(report.heatmap.is_empty() && report.excel.is_empty());
+    }
+
+    #[tokio::test]
+    async fn withdrawn_conspect_files_are_left_out_of_exports() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().to_str().unwrap();
+        let topic = dir.path().join("7").join("Основы_Питона_op1");
+        std::fs::create_dir_all(&topic).unwrap();
+        for name in ["file_20240501_100000_000001_s3.jpg", "file_20240501_100000_000002_s3.jpg", "file_20240502_090000_000001_s4.jpg", "20240503_120000_s5.txt"] {
+            std::fs::write(topic.join(name), b"x").unwrap();
+        }
+
+        assert_eq!(withdraw_conspect_files(base, 7, 3).await.unwrap(), 2);
+        let names: Vec<String> = collect_conspect_files(base, 7, &ConspectSelection::All).iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(names, vec!["file_20240502_090000_000001_s4.jpg", "20240503_120000_s5.txt"]);
+        assert!(dir.path().join("7/_withdrawn/Основы_Питона_op1/file_20240501_100000_000001_s3.jpg").exists());
+        assert_eq!(withdraw_conspect_files(base, 7, 3).await.unwrap(), 0);
+    }
+}
@@ -5,13 +5,45 @@ pub enum DialogueState {
     ChoosingSection { kind: SubmissionType },
     ChoosingTopic { kind: SubmissionType, section: String },
     WaitingForContent { kind: SubmissionType, section: String, topic_id: String, topic_title: String },
+    WaitingForConspectRange,
+    ChoosingTaskTopic,
+    WaitingForTaskAnswer { task_id: i64 },
+    WaitingForDailyAnswer { task_id: i64 },
+    MockExam { attempt_id: i64, ege_number: i64 },
     AdminPanel,
     AdminWaitingForExportUser,
     AdminWaitingForDeleteUser,
+    AdminWaitingForTaskNumber,
+    AdminWaitingForTaskImport,
+    AdminWaitingForMockVariant,
+    AdminWaitingForCodeTests,
+    AdminWaitingForCards,
+    AdminWaitingForQuiz,
+    AdminWaitingForGroups,
+    AdminWaitingForTeacherScores,
+    AdminWaitingForTaskStatement { ege_number: i64 },
+    AdminWaitingForTaskAnswer { ege_number: i64, statement: String, file_id: String, file_kind: String },
 }
 
 #[derive(Clone, PartialEq, Debug)]
 pub enum SubmissionType {
     Dz,
     Conspect,
+}
+
+impl SubmissionType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SubmissionType::Dz => "dz",
+            SubmissionType::Conspect => "conspect",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "dz" => Some(SubmissionType::Dz),
+            "conspect" => Some(SubmissionType::Conspect),
+            _ => None,
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,197 @@
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum AnswerKind {
+    Exact,
+    Set,
+    Sequence,
+}
+
+impl AnswerKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AnswerKind::Exact => "exact",
+            AnswerKind::Set => "set",
+            AnswerKind::Sequence => "seq",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "exact" => Some(AnswerKind::Exact),
+            "set" => Some(AnswerKind::Set),
+            "seq" | "sequence" => Some(AnswerKind::Sequence),
+            _ => None,
+        }
+    }
+
+    pub fn default_for(ege_number: i64) -> Self {
+        match ege_number {
+            // Each of these asks for several numbers in a fixed order; task 20 wants two values of S in ascending order.
+            17 | 20 | 25 | 26 | 27 => AnswerKind::Sequence,
+            _ => AnswerKind::Exact,
+        }
+    }
+}
+
+pub fn parse_answer_spec(input: &str, ege_number: i64) -> (AnswerKind, String) {
+    if let Some((prefix, rest)) = input.split_once(':') {
+        if let Some(kind) = AnswerKind::parse(prefix) {
+            return (kind, rest.trim().to_string());
+        }
+    }
+    (AnswerKind::default_for(ege_number), input.trim().to_string())
+}
+
+fn tokens(answer: &str) -> Vec<String> {
+    answer.to_lowercase()
+        .replace('ё', "е")
+        .split(|c: char| c.is_whitespace() || c == ',' || c == ';')
+        .filter(|t| !t.is_empty())
+        .map(|t| t.to_string())
+        .collect()
+}
+
+pub fn normalize(answer: &str, kind: AnswerKind) -> String {
+    match kind {
+        AnswerKind::Exact => answer.to_lowercase()
+            .replace('ё', "е")
+            .replace(',', ".")
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" "),
+        AnswerKind::Sequence => tokens(answer).join(" "),
+        AnswerKind::Set => {
+            let mut t = tokens(answer);
+            t.sort();
+            t.dedup();
+            t.join(" ")
+        }
+    }
+}
+
+pub fn check_answer(given: &str, canonical: &str, kind: AnswerKind) -> bool {
+    let expected = normalize(canonical, kind);
+    !expected.is_empty() && normalize(given, kind) == expected
+}
+
+pub const DEFAULT_SCORE_TABLE: [i64; 30] = [
+    0, 7, 14, 20, 27, 34, 40, 43, 46, 48, 51, 54, 56, 59, 62,
+    64, 67, 70, 72, 75, 78, 80, 83, 85, 88, 90, 93, 95, 98, 100,
+];
+
+pub fn max_points(ege_number: i64) -> i64 {
+    match ege_number {
+        26 | 27 => 2,
+        _ => 1,
+    }
+}
+
+pub fn score_answer(ege_number: i64, given: &str, canonical: &str, kind: AnswerKind) -> i64 {
+    if check_answer(given, canonical, kind) {
+        return max_points(ege_number);
+    }
+    if max_points(ege_number) == 2 && kind == AnswerKind::Sequence {
+        let expected = tokens(canonical);
+        let actual = tokens(given);
+        if expected.len() == 2 && actual.len() == 2 {
+            return expected.iter().zip(actual.iter()).filter(|(e, a)| e == a).count() as i64;
+        }
+    }
+    0
+}
+
+pub fn parse_score_table(spec: &str) -> anyhow::Result<Vec<i64>> {
+    let table = spec.split(',')
+        .map(|v| v.trim().parse::<i64>())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let max_primary: i64 = (1..=27).map(max_points).sum();
+    if table.len() as i64 != max_primary + 1 {
+        return Err(anyhow::anyhow!("Score table needs {} values (primary 0..={})", max_primary + 1, max_primary));
+    }
+    if let Some(i) = table.windows(2).position(|w| w[1] < w[0]) {
+        return Err(anyhow::anyhow!("Score table must not decrease: {} primary gives {}, {} gives {}", i, table[i], i + 1, table[i + 1]));
+    }
+    Ok(table)
+}
+
+pub fn primary_to_test(primary: i64, table: &[i64]) -> i64 {
+    table.get(primary.max(0) as usize)
+        .or(table.last())
+        .copied()
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exact_answers_ignore_case_spacing_and_decimal_comma() {
+        assert_eq!(normalize("  Ёлка   ЗЕЛЁНАЯ ", AnswerKind::Exact), "елка зеленая");
+        assert!(check_answer(" 3,5 ", "3.5", AnswerKind::Exact));
+        assert!(check_answer("ABC", "abc", AnswerKind::Exact));
+        assert!(!check_answer("12", "13", AnswerKind::Exact));
+        assert!(!check_answer("1 2", "12", AnswerKind::Exact));
+        assert!(!check_answer("", "  ", AnswerKind::Exact));
+    }
+
+    #[test]
+    fn set_answers_ignore_order_separators_and_repeats() {
+        assert_eq!(normalize("3, 1;2  1", AnswerKind::Set), "1 2 3");
+        assert!(check_answer("3,1 2", "1 2 3", AnswerKind::Set));
+        assert!(check_answer("b\nA", "a; b", AnswerKind::Set));
+        assert!(!check_answer("1 2", "1 2 3", AnswerKind::Set));
+    }
+
+    #[test]
+    fn sequence_answers_keep_order() {
+        assert_eq!(normalize(" 10,20;\t30 ", AnswerKind::Sequence), "10 20 30");
+        assert!(check_answer("10, 20", "10 20", AnswerKind::Sequence));
+        assert!(check_answer("10\n20", "10 20", AnswerKind::Sequence));
+        assert!(!check_answer("20 10", "10 20", AnswerKind::Sequence));
+        assert!(!check_answer("10", "10 20", AnswerKind::Sequence));
+    }
+
+    #[test]
+    fn long_answers_get_partial_credit_per_number() {
+        assert_eq!(score_answer(26, "5 120", "5 120", AnswerKind::Sequence), 2);
+        assert_eq!(score_answer(26, "5 121", "5 120", AnswerKind::Sequence), 1);
+        assert_eq!(score_answer(27, "120 5", "5 120", AnswerKind::Sequence), 0);
+        assert_eq!(score_answer(27, "5", "5 120", AnswerKind::Sequence), 0);
+        assert_eq!(score_answer(17, "3 40", "3 41", AnswerKind::Sequence), 0);
+        assert_eq!(score_answer(1, "7", "7", AnswerKind::Exact), 1);
+    }
+
+    #[test]
+    fn score_table_needs_every_primary_score_in_order() {
+        let spec = DEFAULT_SCORE_TABLE.map(|v| v.to_string()).join(", ");
+        assert_eq!(parse_score_table(&spec).unwrap(), DEFAULT_SCORE_TABLE.to_vec());
+
+        let short = DEFAULT_SCORE_TABLE[..29].iter().map(|v| v.to_string()).collect::<Vec<_>>().join(",");
+        assert!(parse_score_table(&short).unwrap_err().to_string().contains("30 values"));
+
+        let mut table = DEFAULT_SCORE_TABLE;
+        table.swap(10, 11);
+        let unordered = table.map(|v| v.to_string()).join(",");
+        assert!(parse_score_table(&unordered).unwrap_err().to_string().contains("10 primary gives 54, 11 gives 51"));
+
+        assert!(parse_score_table("0,7,x").is_err());
+    }
+
+    #[test]
+    fn primary_score_is_clamped_to_the_table() {
+        assert_eq!(primary_to_test(0, &DEFAULT_SCORE_TABLE), 0);
+        assert_eq!(primary_to_test(-3, &DEFAULT_SCORE_TABLE), 0);
+        assert_eq!(primary_to_test(15, &DEFAULT_SCORE_TABLE), 64);
+        assert_eq!(primary_to_test(29, &DEFAULT_SCORE_TABLE), 100);
+        assert_eq!(primary_to_test(35, &DEFAULT_SCORE_TABLE), 100);
+        assert_eq!(primary_to_test(5, &[]), 0);
+    }
+
+    #[test]
+    fn answer_spec_prefix_overrides_default_kind() {
+        assert_eq!(parse_answer_spec("set: 1 2", 1), (AnswerKind::Set, "1 2".to_string()));
+        assert_eq!(parse_answer_spec(" 12 35 ", 20), (AnswerKind::Sequence, "12 35".to_string()));
+        assert_eq!(parse_answer_spec("12:30", 1), (AnswerKind::Exact, "12:30".to_string()));
+    }
+}
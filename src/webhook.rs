@@ -0,0 +1,110 @@
+use std::convert::Infallible;
+
+use futures::{stream, Stream, StreamExt};
+use teloxide::prelude::*;
+use teloxide::stop::{mk_stop_token, StopToken};
+use teloxide::types::{AllowedUpdate, Update};
+use teloxide::update_listeners::{StatefulListener, UpdateListener};
+use tokio::sync::mpsc;
+use warp::filters::BoxedFilter;
+use warp::http::StatusCode;
+use warp::path::FullPath;
+use warp::reply::Response;
+use warp::{Filter, Reply};
+
+const SECRET_HEADER: &str = "x-telegram-bot-api-secret-token";
+const MAX_BODY_BYTES: u64 = 1024 * 1024;
+
+pub type UpdateSender = mpsc::UnboundedSender<Result<Update, Infallible>>;
+
+#[derive(Clone)]
+pub struct WebhookConfig {
+    pub url: url::Url,
+    pub secret: String,
+}
+
+impl WebhookConfig {
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        match std::env::var("BOT_MODE").unwrap_or_else(|_| "polling".into()).as_str() {
+            "polling" => Ok(None),
+            "webhook" => {
+                let url = std::env::var("WEBHOOK_URL").map_err(|_| anyhow::anyhow!("WEBHOOK_URL required in webhook mode"))?;
+                let secret = std::env::var("WEBHOOK_SECRET").map_err(|_| anyhow::anyhow!("WEBHOOK_SECRET required in webhook mode"))?;
+                let valid = (1..=256).contains(&secret.len())
+                    && secret.bytes().all(|c| c.is_ascii_alphanumeric() || c == b'_' || c == b'-');
+                if !valid {
+                    anyhow::bail!("WEBHOOK_SECRET must be 1-256 characters of A-Z, a-z, 0-9, _ and -");
+                }
+                Ok(Some(WebhookConfig { url: url::Url::parse(&url)?, secret }))
+            }
+            other => anyhow::bail!("Unknown BOT_MODE «{}», expected polling or webhook", other),
+        }
+    }
+}
+
+fn first_mut<A, B>(pair: &mut (A, B)) -> &mut A {
+    &mut pair.0
+}
+
+pub fn listener() -> (impl UpdateListener<Err = Infallible>, UpdateSender) {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let (stop_token, stop_flag) = mk_stop_token();
+
+    let updates: std::pin::Pin<Box<dyn Stream<Item = Result<Update, Infallible>> + Send>> =
+        Box::pin(stream::poll_fn(move |cx| rx.poll_recv(cx)).take_until(stop_flag));
+    let listener = StatefulListener::new(
+        (updates, stop_token),
+        first_mut,
+        |state: &mut (_, StopToken)| state.1.clone(),
+    );
+    (listener, tx)
+}
+
+pub fn secret_matches(given: Option<&str>, expected: &str) -> bool {
+    let Some(given) = given else { return false };
+    given.len() == expected.len()
+        && given.bytes().zip(expected.bytes()).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+fn accept_update(config: &WebhookConfig, tx: &UpdateSender, token: Option<String>, body: &[u8]) -> StatusCode {
+    if !secret_matches(token.as_deref(), &config.secret) {
+        return StatusCode::UNAUTHORIZED;
+    }
+    match serde_json::from_slice::<Update>(body) {
+        Ok(update) => {
+            if tx.send(Ok(update)).is_err() {
+                return StatusCode::SERVICE_UNAVAILABLE;
+            }
+        }
+        Err(e) => {
+            log::error!("Cannot parse webhook update: {:?}\n{}", e, String::from_utf8_lossy(body));
+        }
+    }
+    StatusCode::OK
+}
+
+pub fn route(config: WebhookConfig, tx: UpdateSender) -> BoxedFilter<(Response,)> {
+    let path = config.url.path().to_string();
+    warp::post()
+        .and(warp::path::full())
+        .and_then(move |full: FullPath| {
+            let matches = full.as_str() == path;
+            async move { if matches { Ok(()) } else { Err(warp::reject::not_found()) } }
+        })
+        .untuple_one()
+        .and(warp::header::optional::<String>(SECRET_HEADER))
+        .and(warp::body::content_length_limit(MAX_BODY_BYTES))
+        .and(warp::body::bytes())
+        .map(move |token: Option<String>, body: warp::hyper::body::Bytes| {
+            accept_update(&config, &tx, token, &body).into_response()
+        })
+        .boxed()
+}
+
+pub async fn set_webhook(bot: &Bot, config: &WebhookConfig) -> anyhow::Result<()> {
+    bot.set_webhook(config.url.clone())
+        .secret_token(config.secret.clone())
+        .allowed_updates(vec![AllowedUpdate::Message, AllowedUpdate::CallbackQuery, AllowedUpdate::PollAnswer])
+        .await?;
+    Ok(())
+}
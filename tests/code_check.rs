@@ -0,0 +1,51 @@
+use homework_bot::code_check::{Checker, Verdict};
+
+// These run python3 inside the namespace jail, so they need unprivileged user namespaces:
+// cargo test --test code_check -- --ignored
+
+async fn verdict(code: &str, input: &str, expected: &str) -> Verdict {
+    let checker = Checker::new("python3".into(), 1, 256);
+    let mut outcomes = checker.run(code.as_bytes(), &[(input.to_string(), expected.to_string())]).await.unwrap();
+    outcomes.remove(0).verdict
+}
+
+#[tokio::test]
+#[ignore = "needs python3 and unprivileged user namespaces"]
+async fn correct_and_wrong_answers() {
+    assert_eq!(verdict("print(int(input()) ** 2)", "5", "25").await, Verdict::Passed);
+    assert_eq!(verdict("print(int(input()) * 2)", "5", "25").await, Verdict::WrongAnswer);
+    assert_eq!(verdict("print(1 / 0)", "", "").await, Verdict::RuntimeError);
+}
+
+#[tokio::test]
+#[ignore = "needs python3 and unprivileged user namespaces"]
+async fn endless_loop_hits_time_limit() {
+    assert_eq!(verdict("while True:\n    pass", "", "").await, Verdict::TimeLimit);
+    assert_eq!(verdict("import time\ntime.sleep(60)", "", "").await, Verdict::TimeLimit);
+}
+
+#[tokio::test]
+#[ignore = "needs python3 and unprivileged user namespaces"]
+async fn flood_of_output_is_cut() {
+    assert_eq!(verdict("while True:\n    print('x' * 1000)", "", "").await, Verdict::OutputLimit);
+}
+
+#[tokio::test]
+#[ignore = "needs python3 and unprivileged user namespaces"]
+async fn network_and_host_files_are_unreachable() {
+    let code = "import socket\nsocket.create_connection(('1.1.1.1', 53), timeout=1)\nprint('online')";
+    assert_eq!(verdict(code, "", "").await, Verdict::RuntimeError);
+    assert_eq!(verdict("print(open('/etc/passwd').read())", "", "").await, Verdict::RuntimeError);
+}
+
+#[tokio::test]
+#[ignore = "needs python3 and unprivileged user namespaces"]
+async fn probe_reports_a_working_sandbox() {
+    let checker = Checker::new("python3".into(), 1, 256);
+    assert!(checker.probe().await);
+    assert!(checker.available());
+
+    let broken = Checker::new("/nonexistent/python3".into(), 1, 256);
+    assert!(!broken.probe().await);
+    assert!(!broken.available());
+}
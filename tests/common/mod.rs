@@ -0,0 +1,355 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::Utc;
+use serde_json::{json, Map, Value};
+use teloxide::dispatching::dialogue::InMemStorage;
+use teloxide::prelude::*;
+use tempfile::TempDir;
+use warp::Filter;
+
+use homework_bot::backup::{BackupConfig, Backups};
+use homework_bot::code_check::Checker;
+use homework_bot::db::{self, DbPool};
+use homework_bot::events::Events;
+use homework_bot::health::Health;
+use homework_bot::metrics::Metrics;
+use homework_bot::repo::Repos;
+use homework_bot::states::DialogueState;
+use homework_bot::{schema, tasks, AppState};
+
+pub const TOKEN: &str = "123456:TEST-TOKEN";
+pub const ADMIN: i64 = 1000;
+pub const STUDENT: i64 = 2000;
+pub const FILE_CONTENT: &[u8] = b"fake telegram file";
+
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Debug)]
+pub struct Call {
+    pub method: String,
+    pub chat_id: Option<i64>,
+    pub params: Value,
+    pub message_id: Option<i64>,
+}
+
+impl Call {
+    pub fn text(&self) -> &str {
+        self.params.get("text").or_else(|| self.params.get("caption")).and_then(Value::as_str).unwrap_or("")
+    }
+
+    pub fn buttons(&self) -> Vec<String> {
+        let markup = match self.params.get("reply_markup") {
+            Some(Value::String(raw)) => serde_json::from_str(raw).unwrap_or(Value::Null),
+            Some(markup) => markup.clone(),
+            None => Value::Null,
+        };
+        markup["inline_keyboard"].as_array().into_iter().flatten()
+            .flat_map(|row| row.as_array().into_iter().flatten())
+            .filter_map(|button| button["callback_data"].as_str().map(String::from))
+            .collect()
+    }
+
+    pub fn file_name(&self) -> Option<&str> {
+        self.params.get("document").and_then(|d| d["filename"].as_str())
+    }
+}
+
+#[derive(Default)]
+struct Inner {
+    calls: Mutex<Vec<Call>>,
+    cursors: Mutex<HashMap<i64, usize>>,
+    updates: Mutex<Vec<Value>>,
+    callbacks: Mutex<HashMap<String, i64>>,
+    new_updates: tokio::sync::Notify,
+    next_id: AtomicI64,
+}
+
+impl Inner {
+    fn next_id(&self) -> i64 {
+        self.next_id.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    fn answer(&self, method: &str, params: Value) -> Value {
+        let chat_id = match &params["chat_id"] {
+            Value::Number(n) => n.as_i64(),
+            Value::String(s) => s.parse().ok(),
+            _ => None,
+        };
+        let chat_id = chat_id.or_else(|| {
+            let callback = params["callback_query_id"].as_str()?;
+            self.callbacks.lock().unwrap().get(callback).copied()
+        });
+
+        let result = match method {
+            "getMe" => json!({
+                "id": 1, "is_bot": true, "first_name": "Homework", "username": "homework_test_bot",
+                "can_join_groups": false, "can_read_all_group_messages": false, "supports_inline_queries": false,
+            }),
+            "getWebhookInfo" => json!({ "url": "", "has_custom_certificate": false, "pending_update_count": 0 }),
+            "getFile" => {
+                let file_id = params["file_id"].as_str().unwrap_or("file");
+                json!({
+                    "file_id": file_id, "file_unique_id": format!("{}-unique", file_id),
+                    "file_size": FILE_CONTENT.len(), "file_path": format!("files/{}.jpg", file_id),
+                })
+            }
+            m if m.starts_with("send") || m.starts_with("edit") => {
+                let message_id = params["message_id"].as_i64().unwrap_or_else(|| self.next_id());
+                message(message_id, chat_id.unwrap_or(0), json!({ "text": "ok" }), true)
+            }
+            _ => json!(true),
+        };
+
+        let message_id = result["message_id"].as_i64();
+        self.calls.lock().unwrap().push(Call { method: method.to_string(), chat_id, params, message_id });
+        json!({ "ok": true, "result": result })
+    }
+
+    async fn updates(&self) -> Value {
+        let _ = tokio::time::timeout(Duration::from_millis(100), async {
+            while self.updates.lock().unwrap().is_empty() {
+                self.new_updates.notified().await;
+            }
+        }).await;
+        let updates: Vec<Value> = self.updates.lock().unwrap().drain(..).collect();
+        json!({ "ok": true, "result": updates })
+    }
+}
+
+fn user(id: i64) -> Value {
+    json!({ "id": id, "is_bot": false, "first_name": format!("User{}", id), "username": format!("user{}", id) })
+}
+
+fn message(message_id: i64, chat_id: i64, content: Value, from_bot: bool) -> Value {
+    let mut message = json!({
+        "message_id": message_id,
+        "date": Utc::now().timestamp(),
+        "chat": { "id": chat_id, "type": "private", "first_name": format!("User{}", chat_id), "username": format!("user{}", chat_id) },
+        "from": if from_bot { json!({ "id": 1, "is_bot": true, "first_name": "Homework", "username": "homework_test_bot" }) } else { user(chat_id) },
+    });
+    message.as_object_mut().unwrap().extend(content.as_object().cloned().unwrap_or_default());
+    message
+}
+
+fn parse_multipart(content_type: &str, body: &[u8]) -> Value {
+    let boundary = content_type.split("boundary=").nth(1).unwrap_or("").trim_matches('"');
+    let body = String::from_utf8_lossy(body);
+    let mut fields = Map::new();
+    for part in body.split(&format!("--{}", boundary)) {
+        let Some((headers, value)) = part.split_once("\r\n\r\n") else { continue };
+        let attr = |key: &str| {
+            let start = headers.find(&format!("{}=\"", key))? + key.len() + 2;
+            headers[start..].split('"').next().map(String::from)
+        };
+        let Some(name) = attr("name") else { continue };
+        let value = value.strip_suffix("\r\n").unwrap_or(value);
+        let value = match attr("filename") {
+            Some(filename) => json!({ "filename": filename, "size": value.len() }),
+            None => Value::String(value.to_string()),
+        };
+        fields.insert(name, value);
+    }
+    let attached: Vec<(String, String)> = fields.iter()
+        .filter_map(|(k, v)| Some((k.clone(), v.as_str()?.strip_prefix("attach://")?.to_string())))
+        .collect();
+    for (field, part) in attached {
+        if let Some(file) = fields.remove(&part) {
+            fields.insert(field, file);
+        }
+    }
+    Value::Object(fields)
+}
+
+pub struct FakeTelegram {
+    inner: Arc<Inner>,
+    pub url: reqwest::Url,
+}
+
+impl FakeTelegram {
+    pub async fn start() -> Self {
+        let inner = Arc::new(Inner::default());
+
+        let api_inner = inner.clone();
+        let api = warp::post()
+            .and(warp::path!(String / String))
+            .and(warp::header::optional::<String>("content-type"))
+            .and(warp::body::bytes())
+            .then(move |bot: String, method: String, content_type: Option<String>, body: warp::hyper::body::Bytes| {
+                let inner = api_inner.clone();
+                async move {
+                    assert_eq!(bot, format!("bot{}", TOKEN));
+                    let method = method[..1].to_lowercase() + &method[1..];
+                    let content_type = content_type.unwrap_or_default();
+                    let reply = if method == "getUpdates" {
+                        inner.updates().await
+                    } else if content_type.starts_with("multipart/form-data") {
+                        inner.answer(&method, parse_multipart(&content_type, &body))
+                    } else {
+                        inner.answer(&method, serde_json::from_slice(&body).unwrap_or(Value::Null))
+                    };
+                    warp::reply::json(&reply)
+                }
+            });
+        let files = warp::get()
+            .and(warp::path("file"))
+            .and(warp::path::tail())
+            .map(|_| FILE_CONTENT.to_vec());
+
+        let (addr, server) = warp::serve(api.or(files)).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+        let url = reqwest::Url::parse(&format!("http://{}/", addr)).unwrap();
+        FakeTelegram { inner, url }
+    }
+
+    fn push(&self, update: Value) {
+        let mut update = update;
+        update["update_id"] = json!(self.inner.next_id());
+        self.inner.updates.lock().unwrap().push(update);
+        self.inner.new_updates.notify_waiters();
+    }
+
+    pub fn text(&self, from: i64, text: &str) {
+        let message = message(self.inner.next_id(), from, json!({ "text": text }), false);
+        self.push(json!({ "message": message }));
+    }
+
+    pub fn photo(&self, from: i64, file_id: &str, caption: &str, media_group: Option<&str>) {
+        let mut content = json!({
+            "photo": [{ "file_id": file_id, "file_unique_id": format!("{}-unique", file_id), "width": 640, "height": 480, "file_size": 100 }],
+            "caption": caption,
+        });
+        if let Some(group) = media_group {
+            content["media_group_id"] = json!(group);
+        }
+        let message = message(self.inner.next_id(), from, content, false);
+        self.push(json!({ "message": message }));
+    }
+
+    pub fn document(&self, from: i64, file_id: &str, file_name: &str) {
+        let content = json!({
+            "document": { "file_id": file_id, "file_unique_id": format!("{}-unique", file_id), "file_name": file_name, "file_size": 100 },
+        });
+        let message = message(self.inner.next_id(), from, content, false);
+        self.push(json!({ "message": message }));
+    }
+
+    pub fn press(&self, from: i64, on: &Call, data: &str) {
+        assert!(on.buttons().iter().any(|b| b == data), "no button {:?} in {:?}", data, on.buttons());
+        let id = format!("cb{}", self.inner.next_id());
+        self.inner.callbacks.lock().unwrap().insert(id.clone(), from);
+        let message = message(on.message_id.expect("pressed call has no message"), from, json!({ "text": on.text() }), true);
+        self.push(json!({
+            "callback_query": { "id": id, "from": user(from), "chat_instance": "test", "data": data, "message": message },
+        }));
+    }
+
+    pub async fn next(&self, chat_id: i64) -> Call {
+        let found = tokio::time::timeout(REPLY_TIMEOUT, async {
+            loop {
+                {
+                    let calls = self.inner.calls.lock().unwrap();
+                    let mut cursors = self.inner.cursors.lock().unwrap();
+                    let cursor = cursors.entry(chat_id).or_insert(0);
+                    if let Some(offset) = calls[*cursor..].iter().position(|c| c.chat_id == Some(chat_id)) {
+                        *cursor += offset + 1;
+                        return calls[*cursor - 1].clone();
+                    }
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }).await;
+        found.unwrap_or_else(|_| panic!("bot sent nothing more to chat {}", chat_id))
+    }
+
+    pub async fn expect(&self, chat_id: i64, method: &str, text: &str) -> Call {
+        let call = self.next(chat_id).await;
+        assert_eq!(call.method, method, "unexpected call {:?}", call);
+        assert!(call.text().contains(text), "expected {:?} in {:?}", text, call.text());
+        call
+    }
+
+    pub async fn expect_until(&self, chat_id: i64, method: &str, text: &str) -> Vec<Call> {
+        let mut seen = Vec::new();
+        loop {
+            let call = self.next(chat_id).await;
+            let done = call.method == method && call.text().contains(text);
+            seen.push(call);
+            if done {
+                return seen;
+            }
+        }
+    }
+
+    pub fn calls(&self) -> Vec<Call> {
+        self.inner.calls.lock().unwrap().clone()
+    }
+}
+
+pub struct TestBot {
+    pub tg: FakeTelegram,
+    pub bot: Bot,
+    pub state: AppState,
+    _dir: TempDir,
+}
+
+impl TestBot {
+    pub async fn start() -> Self {
+        let tg = FakeTelegram::start().await;
+        let dir = tempfile::tempdir().unwrap();
+        let pool = db::init_db(&format!("sqlite:{}", dir.path().join("bot.db").display())).await.unwrap();
+        let repos = Repos::sql(pool.clone());
+        let conspects_dir = dir.path().join("conspects").to_string_lossy().to_string();
+        tokio::fs::create_dir_all(&conspects_dir).await.unwrap();
+
+        let state = AppState {
+            pool: pool.clone(),
+            repos: repos.clone(),
+            admin_id: ADMIN,
+            conspects_dir: conspects_dir.clone(),
+            task_files_dir: dir.path().join("task_files").to_string_lossy().to_string(),
+            withdraw_grace_minutes: 30,
+            mock_score_table: Arc::new(tasks::DEFAULT_SCORE_TABLE.to_vec()),
+            mock_target_score: 80,
+            code_checker: Arc::new(Checker::new("python3".into(), 5, 256)),
+            health: Arc::new(Health::new()),
+            metrics: Arc::new(Metrics::default()),
+            events: Arc::new(Events::new(pool.clone(), repos.submissions.clone(), None).unwrap()),
+            backups: Arc::new(Backups::new(pool, conspects_dir, BackupConfig {
+                dir: dir.path().join("backups"),
+                keep_daily: 7,
+                keep_weekly: 4,
+            })),
+            media_groups: Arc::new(dashmap::DashMap::new()),
+        };
+
+        let bot = Bot::new(TOKEN).set_api_url(tg.url.clone());
+        let mut dispatcher = Dispatcher::builder(bot.clone(), schema())
+            .dependencies(dptree::deps![InMemStorage::<DialogueState>::new(), state.clone()])
+            .build();
+        tokio::spawn(async move { dispatcher.dispatch().await });
+
+        TestBot { tg, bot, state, _dir: dir }
+    }
+
+    pub fn pool(&self) -> &DbPool {
+        &self.state.pool
+    }
+
+    pub async fn open_admin_panel(&self) -> Call {
+        self.tg.text(ADMIN, "🛠️ Админ-панель");
+        self.tg.expect(ADMIN, "sendMessage", "Админ-панель:").await
+    }
+
+    pub async fn choose_topic(&self, uid: i64, menu: &str, section: &str, topic_id: &str) {
+        self.tg.text(uid, menu);
+        let sections = self.tg.expect(uid, "sendMessage", "Выбери раздел:").await;
+        self.tg.press(uid, &sections, &format!("sec|{}", section));
+        let topics = self.tg.expect(uid, "editMessageText", "Выбери тему:").await;
+        self.tg.press(uid, &topics, &format!("topic|{}|{}", section, topic_id));
+        self.tg.expect(uid, "editMessageText", "Тема:").await;
+        self.tg.expect(uid, "answerCallbackQuery", "").await;
+    }
+}
@@ -0,0 +1,203 @@
+mod common;
+
+use chrono::Utc;
+use common::{TestBot, ADMIN, FILE_CONTENT, STUDENT};
+use homework_bot::{backup, db, handlers};
+use walkdir::WalkDir;
+
+async fn submissions(t: &TestBot) -> Vec<(i64, String, String, String, String, String)> {
+    sqlx::query_as("SELECT user_id, type, topic_id, content_type, content_summary, photo_file_id FROM submissions ORDER BY id")
+        .fetch_all(t.pool())
+        .await
+        .unwrap()
+}
+
+async fn count(t: &TestBot, table: &str) -> i64 {
+    sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {}", table)).fetch_one(t.pool()).await.unwrap()
+}
+
+fn stored_files(t: &TestBot) -> Vec<Vec<u8>> {
+    WalkDir::new(&t.state.conspects_dir)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|e| e.file_type().is_file())
+        .map(|e| std::fs::read(e.path()).unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn student_submits_text_homework() {
+    let t = TestBot::start().await;
+
+    t.tg.text(STUDENT, "/start");
+    let menu = t.tg.expect(STUDENT, "sendMessage", "Привет! Я бот для сдачи ДЗ и конспектов.").await;
+    assert!(menu.params["reply_markup"]["keyboard"].to_string().contains("📚 Сдать ДЗ"));
+
+    t.choose_topic(STUDENT, "📚 Сдать ДЗ", "Основы Питона", "op3").await;
+    t.tg.text(STUDENT, "Решил все задачи");
+    t.tg.expect(STUDENT, "sendMessage", "Цикл for").await;
+    t.tg.expect(ADMIN, "sendMessage", "✅ Новый ДЗ от @user2000: Цикл for - Решил все задачи").await;
+
+    assert_eq!(submissions(&t).await, vec![
+        (STUDENT, "dz".into(), "op3".into(), "text".into(), "Решил все задачи".into(), String::new()),
+    ]);
+    let user: (String, String) = sqlx::query_as("SELECT username, first_name FROM users WHERE id = $1")
+        .bind(STUDENT)
+        .fetch_one(t.pool())
+        .await
+        .unwrap();
+    assert_eq!(user, ("user2000".to_string(), "User2000".to_string()));
+}
+
+#[tokio::test]
+async fn photo_conspect_is_downloaded_to_disk() {
+    let t = TestBot::start().await;
+
+    t.choose_topic(STUDENT, "📘 Сдать конспект", "Основы Питона", "op7").await;
+    t.tg.photo(STUDENT, "photo-1", "Списки, стр. 1", None);
+    t.tg.expect(STUDENT, "sendMessage", "Списки").await;
+    let notice = t.tg.expect(ADMIN, "sendMessage", "📸 Новый Конспект от @user2000: Списки - Списки, стр. 1").await;
+
+    let rows = submissions(&t).await;
+    assert_eq!(rows, vec![
+        (STUDENT, "conspect".into(), "op7".into(), "photo".into(), "Списки, стр. 1".into(), "photo-1".into()),
+    ]);
+    assert_eq!(notice.buttons(), vec!["share|admin|1".to_string()]);
+    assert!(t.tg.calls().iter().any(|c| c.method == "getFile" && c.params["file_id"] == "photo-1"));
+    assert_eq!(stored_files(&t), vec![FILE_CONTENT.to_vec()]);
+}
+
+#[tokio::test]
+async fn album_is_stored_as_one_submission() {
+    let t = TestBot::start().await;
+
+    t.choose_topic(STUDENT, "📘 Сдать конспект", "Основы Питона", "op2").await;
+    t.tg.photo(STUDENT, "album-1", "Условия", Some("group-1"));
+    t.tg.photo(STUDENT, "album-2", "", Some("group-1"));
+
+    for _ in 0..500 {
+        if t.state.media_groups.iter().any(|g| g.value().0.len() == 2) {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    assert_eq!(count(&t, "submissions").await, 0);
+
+    handlers::flush_media_groups(&t.bot, &t.state, Utc::now().timestamp() + 10).await;
+    t.tg.expect(STUDENT, "sendMessage", "Альбом принят! (2 фото)").await;
+    t.tg.expect(ADMIN, "sendMessage", "📸 Новый Конспект (АЛЬБОМ) от user_2000: Условия и операторы - Условия").await;
+
+    assert_eq!(submissions(&t).await, vec![
+        (STUDENT, "conspect".into(), "op2".into(), "photo_album".into(), "Условия".into(), "album-1;album-2".into()),
+    ]);
+    assert_eq!(stored_files(&t).len(), 2);
+    assert!(t.state.media_groups.is_empty());
+}
+
+#[tokio::test]
+async fn document_homework_is_accepted() {
+    let t = TestBot::start().await;
+
+    t.choose_topic(STUDENT, "📚 Сдать ДЗ", "ЕГЭ 1-27", "ege5").await;
+    t.tg.document(STUDENT, "doc-1", "answers.pdf");
+    t.tg.expect(STUDENT, "sendMessage", "Задание 5 (Файл принят)").await;
+    t.tg.expect(ADMIN, "sendMessage", "📄 Новый ДЗ (ФАЙЛ) от @user2000: Задание 5 - answers.pdf").await;
+
+    assert_eq!(submissions(&t).await, vec![
+        (STUDENT, "dz".into(), "ege5".into(), "document".into(), "answers.pdf".into(), "doc-1".into()),
+    ]);
+    assert!(stored_files(&t).is_empty());
+}
+
+#[tokio::test]
+async fn admin_exports_student() {
+    let t = TestBot::start().await;
+
+    t.choose_topic(STUDENT, "📘 Сдать конспект", "Основы Питона", "op1").await;
+    t.tg.photo(STUDENT, "photo-1", "Вводный", None);
+    t.tg.expect(STUDENT, "sendMessage", "Вводный урок").await;
+    t.tg.expect(ADMIN, "sendMessage", "📸 Новый Конспект").await;
+
+    let panel = t.open_admin_panel().await;
+    t.tg.press(ADMIN, &panel, "admin|export_user");
+    t.tg.expect(ADMIN, "sendMessage", "Пришли ID или @username пользователя:").await;
+    t.tg.expect(ADMIN, "answerCallbackQuery", "").await;
+    t.tg.text(ADMIN, "@user2000");
+
+    let sent = t.tg.expect_until(ADMIN, "sendMessage", "Готово.").await;
+    let documents: Vec<_> = sent.iter().filter(|c| c.method == "sendDocument").filter_map(|c| c.file_name()).collect();
+    assert_eq!(documents, vec!["submissions.xlsx", "files.zip", "Основы_Питона.pdf"]);
+    assert!(sent.iter().any(|c| c.text().contains("Начинаю выгрузку")));
+
+    t.tg.press(ADMIN, sent.last().unwrap(), "admin|export_user");
+    t.tg.expect(ADMIN, "sendMessage", "Пришли ID или @username пользователя:").await;
+    t.tg.expect(ADMIN, "answerCallbackQuery", "").await;
+    t.tg.text(ADMIN, "@nobody");
+    t.tg.expect(ADMIN, "sendMessage", "Начинаю выгрузку...").await;
+    t.tg.expect(ADMIN, "sendMessage", "Пользователь не найден или ошибка.").await;
+}
+
+#[tokio::test]
+async fn admin_deletes_student() {
+    let t = TestBot::start().await;
+
+    t.choose_topic(STUDENT, "📚 Сдать ДЗ", "Основы Питона", "op4").await;
+    t.tg.text(STUDENT, "while True: pass");
+    t.tg.expect(STUDENT, "sendMessage", "Цикл while").await;
+    t.tg.expect(ADMIN, "sendMessage", "✅ Новый ДЗ").await;
+    assert_eq!(count(&t, "submissions").await, 1);
+
+    let panel = t.open_admin_panel().await;
+    t.tg.press(ADMIN, &panel, "admin|delete_user");
+    t.tg.expect(ADMIN, "sendMessage", "Пришли ID или @username для УДАЛЕНИЯ:").await;
+    t.tg.expect(ADMIN, "answerCallbackQuery", "").await;
+    t.tg.text(ADMIN, &STUDENT.to_string());
+    t.tg.expect(ADMIN, "sendMessage", "Пользователь удален.").await;
+
+    assert_eq!(count(&t, "submissions").await, 0);
+    let remaining: Vec<i64> = sqlx::query_scalar("SELECT id FROM users").fetch_all(t.pool()).await.unwrap();
+    assert_eq!(remaining, vec![ADMIN]);
+}
+
+#[tokio::test]
+async fn admin_resets_database() {
+    let t = TestBot::start().await;
+
+    t.choose_topic(STUDENT, "📘 Сдать конспект", "Основы Питона", "op6").await;
+    t.tg.photo(STUDENT, "photo-1", "Срезы", None);
+    t.tg.expect(STUDENT, "sendMessage", "Строки и срезы").await;
+    t.tg.expect(ADMIN, "sendMessage", "📸 Новый Конспект").await;
+    assert_eq!(stored_files(&t).len(), 1);
+
+    let panel = t.open_admin_panel().await;
+    t.tg.press(ADMIN, &panel, "admin|reset_all");
+    let alert = t.tg.expect_until(ADMIN, "answerCallbackQuery", "База сброшена!").await;
+    assert_eq!(alert.last().unwrap().params["show_alert"], true);
+
+    assert_eq!(count(&t, "submissions").await, 0);
+    assert_eq!(count(&t, "users").await, 0);
+    assert!(stored_files(&t).is_empty());
+
+    let backups = t.state.backups.list().await.unwrap();
+    assert_eq!(backups.len(), 1);
+    assert!(backups[0].name.ends_with("-reset"));
+
+    let scratch = t.state.backups.dir().parent().unwrap().join("restored");
+    let restored_db = scratch.join("bot.db");
+    let restored_files = scratch.join("conspects");
+    tokio::fs::create_dir_all(&scratch).await.unwrap();
+    backup::restore(&t.state.backups.dir().join(&backups[0].name), &restored_db, &restored_files).await.unwrap();
+    let restored = db::init_db(&format!("sqlite:{}", restored_db.display())).await.unwrap();
+    let submissions: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM submissions").fetch_one(&restored).await.unwrap();
+    assert_eq!(submissions, 1);
+    let files: Vec<_> = WalkDir::new(&restored_files).into_iter().filter_map(Result::ok).filter(|e| e.file_type().is_file()).collect();
+    assert_eq!(files.len(), 1);
+}
+
+#[tokio::test]
+async fn student_cannot_open_admin_panel() {
+    let t = TestBot::start().await;
+
+    t.tg.text(STUDENT, "🛠️ Админ-панель");
+    t.tg.expect(STUDENT, "sendMessage", "Доступ запрещён.").await;
+}
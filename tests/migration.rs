@@ -0,0 +1,49 @@
+use homework_bot::db::{self, DbPool};
+use homework_bot::repo::Repos;
+
+async fn count(pool: &DbPool, table: &str) -> i64 {
+    sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {}", table)).fetch_one(pool).await.unwrap()
+}
+
+async fn submission(conn: &mut sqlx::AnyConnection, user_id: i64) {
+    sqlx::query(
+        "INSERT INTO submissions (user_id, type, section, topic_id, topic_title, content_type, content_summary, photo_file_id, message_id, date, ts)
+         VALUES ($1, 'dz', 'Основы Питона', 'op1', 'Вводный урок', 'text', 'ok', '', 1, '2026-10-01', '2026-10-01T10:00:00')"
+    ).bind(user_id).execute(conn).await.unwrap();
+}
+
+// Set TEST_POSTGRES_URL to an empty Postgres database to run the copy against a backend that enforces foreign keys.
+async fn target(dir: &tempfile::TempDir) -> DbPool {
+    let url = std::env::var("TEST_POSTGRES_URL")
+        .unwrap_or_else(|_| format!("sqlite:{}", dir.path().join("target.db").display()));
+    db::init_db(&url).await.unwrap()
+}
+
+#[tokio::test]
+async fn migration_skips_rows_of_removed_users() {
+    let dir = tempfile::tempdir().unwrap();
+    let source = db::init_db(&format!("sqlite:{}", dir.path().join("bot.db").display())).await.unwrap();
+    // Older bot.db files were written without foreign key enforcement and still hold rows of removed users.
+    let mut conn = source.acquire().await.unwrap();
+    sqlx::query("PRAGMA foreign_keys = OFF").execute(&mut *conn).await.unwrap();
+    sqlx::query("INSERT INTO users (id, username, first_name) VALUES (1, 'kept', 'Kept')").execute(&mut *conn).await.unwrap();
+    submission(&mut conn, 1).await;
+    submission(&mut conn, 2).await;
+    sqlx::query("INSERT INTO shared_conspects (submission_id, author_id, section, topic_id, topic_title, status, ts)
+                 VALUES (2, 2, 'Основы Питона', 'op1', 'Вводный урок', 'approved', '2026-10-01T10:00:00')")
+        .execute(&mut *conn).await.unwrap();
+    drop(conn);
+
+    let target = target(&dir).await;
+    let copied = db::copy_database(&source, &target).await.unwrap();
+
+    let stats = |table: &str| copied.iter().find(|t| t.table == table).map(|t| (t.copied, t.orphaned)).unwrap();
+    assert_eq!(stats("users"), (1, 0));
+    assert_eq!(stats("submissions"), (1, 1));
+    assert_eq!(stats("shared_conspects"), (0, 1));
+    assert_eq!(count(&target, "submissions").await, 1);
+
+    Repos::sql(target.clone()).users.remove(1).await.unwrap();
+    assert_eq!(count(&target, "users").await, 0);
+    assert_eq!(count(&target, "submissions").await, 0);
+}